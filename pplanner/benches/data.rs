@@ -0,0 +1,186 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::time::{Duration, Instant};
+
+use criterion::{black_box, BatchSize, Criterion};
+
+use pplanner::{astr, data, save, support, synth};
+use pplanner::astr::AStr;
+use pplanner::storage::Storage;
+
+/*
+Measures the data layer on made up records, the same every run.
+The records are written to a data directory of their own, HOME points there while measuring.
+PPLANNER_BENCH_N sets how many records of each kind, 10000 by default.
+*/
+const SEED: u64 = 49;
+
+fn records() -> usize{
+    std::env::var("PPLANNER_BENCH_N").ok().and_then(|x| x.parse().ok()).unwrap_or(10000)
+}
+
+fn path(name: &str) -> std::path::PathBuf{
+    save::get_data_dir_path(name).expect("bench: HOME should be set")
+}
+
+fn read_file<T: save::Bufferable + Ord + Clone>(name: &str) -> save::BufferFile<T>{
+    let mut file = save::BufferFile::new(path(name));
+    assert!(file.read(true), "bench: could not read {}", name);
+    file
+}
+
+//edits are appended to the log, it goes before each run so every run starts from the same file
+fn reset(name: &str, pristine: &save::Buffer){
+    assert!(save::replace_file(&path(name), pristine));
+    let _ = std::fs::remove_file(path(&format!("{}.log", name)));
+}
+
+//stdout goes to /dev/null while pretty_print is measured, so the report stays readable
+struct Quiet{
+    saved: i32,
+}
+
+impl Quiet{
+    fn new() -> Quiet{
+        let _ = std::io::stdout().flush();
+        let null = std::fs::OpenOptions::new().write(true).open("/dev/null").expect("bench: no /dev/null");
+        let saved = unsafe {libc::dup(1)};
+        unsafe {libc::dup2(null.as_raw_fd(), 1)};
+        Quiet{saved}
+    }
+}
+
+impl Drop for Quiet{
+    fn drop(&mut self){
+        let _ = std::io::stdout().flush();
+        unsafe{
+            libc::dup2(self.saved, 1);
+            libc::close(self.saved);
+        }
+    }
+}
+
+fn bench_read(c: &mut Criterion){
+    let mut group = c.benchmark_group("BufferFile::read");
+    group.bench_function("points", |b| b.iter(|| black_box(read_file::<data::Point>(save::POINT_DIR))));
+    group.bench_function("plans", |b| b.iter(|| black_box(read_file::<data::Plan>(save::PLAN_DIR))));
+    group.bench_function("slices", |b| b.iter(|| black_box(read_file::<data::Slice>(save::SLICE_DIR))));
+    group.bench_function("todos", |b| b.iter(|| black_box(read_file::<data::Todo>(save::TODO_DIR))));
+    group.finish();
+}
+
+//the records are read sorted, a percent is added after them like new items are
+fn bench_sort(c: &mut Criterion){
+    let added = std::cmp::max(1, records() / 100);
+    let mut group = c.benchmark_group("BufferFile::sort");
+    group.bench_function("points", |b| b.iter_batched(||{
+        let mut file = read_file::<data::Point>(save::POINT_DIR);
+        for x in synth::points(added, &mut synth::Rng::new(SEED + 1)){
+            file.add_item(x);
+        }
+        file
+    }, |mut file|{
+        file.sort(true);
+        file
+    }, BatchSize::LargeInput));
+    group.bench_function("todos", |b| b.iter_batched(||{
+        let mut file = read_file::<data::Todo>(save::TODO_DIR);
+        for x in synth::todos(added, &mut synth::Rng::new(SEED + 1)){
+            file.add_item(x);
+        }
+        file
+    }, |mut file|{
+        file.sort(true);
+        file
+    }, BatchSize::LargeInput));
+    group.finish();
+}
+
+//every tenth record, it includes writing the change
+fn bench_remove_indices(c: &mut Criterion){
+    let mut group = c.benchmark_group("BufferFile::remove_indices");
+    let pristine = std::fs::read(path(save::POINT_DIR)).unwrap();
+    group.bench_function("points", |b| b.iter_batched(||{
+        reset(save::POINT_DIR, &pristine);
+        read_file::<data::Point>(save::POINT_DIR)
+    }, |mut file|{
+        let indices = (0..file.get_items().len()).step_by(10).collect();
+        assert!(file.remove_indices(indices));
+        file
+    }, BatchSize::PerIteration));
+    reset(save::POINT_DIR, &pristine);
+    group.finish();
+}
+
+//every tenth record gets another title, it includes writing the change
+fn bench_replace(c: &mut Criterion){
+    let mut group = c.benchmark_group("BufferFile::replace");
+    let pristine = std::fs::read(path(save::TODO_DIR)).unwrap();
+    group.bench_function("todos", |b| b.iter_batched(||{
+        reset(save::TODO_DIR, &pristine);
+        let mut file = read_file::<data::Todo>(save::TODO_DIR);
+        let indices: Vec<usize> = (0..file.get_items().len()).step_by(10).collect();
+        let replacements = indices.iter().map(|i|{
+            let mut todo = file.get_items()[*i].clone();
+            todo.title = todo.title.concat(astr::from_str(" edited"));
+            todo
+        }).collect();
+        (file, indices, replacements)
+    }, |(mut file, indices, replacements)|{
+        assert!(file.replace(indices, replacements));
+        file
+    }, BatchSize::PerIteration));
+    reset(save::TODO_DIR, &pristine);
+    group.finish();
+}
+
+fn search(words: &[&str]) -> Option<VecDeque<astr::Astr>>{
+    Option::Some(words.iter().map(|x| astr::from_str(x)).collect())
+}
+
+fn bench_get_matches(c: &mut Criterion){
+    let points = read_file::<data::Point>(save::POINT_DIR).get_items().clone();
+    let todos = read_file::<data::Todo>(save::TODO_DIR).get_items().clone();
+    let mut group = c.benchmark_group("support::get_matches");
+    group.bench_function("points", |b| b.iter(|| support::get_matches(&points, &mut search(&["exam call"]))));
+    group.bench_function("todos", |b| b.iter(|| support::get_matches(&todos, &mut search(&["gym"]))));
+    group.finish();
+}
+
+fn print_quiet<T: pplanner::conz::PrettyPrintable>(items: &[T], arg: &T::ArgType, iters: u64) -> Duration{
+    let _quiet = Quiet::new();
+    let start = Instant::now();
+    for _ in 0..iters{
+        support::pretty_print(items, arg);
+    }
+    start.elapsed()
+}
+
+fn bench_pretty_print(c: &mut Criterion){
+    let points = read_file::<data::Point>(save::POINT_DIR).get_items().clone();
+    let slices = read_file::<data::Slice>(save::SLICE_DIR).get_items().clone();
+    let todos = read_file::<data::Todo>(save::TODO_DIR).get_items().clone();
+    let now = data::DT::new();
+    let mut group = c.benchmark_group("support::pretty_print");
+    group.sample_size(20);
+    group.bench_function("points", |b| b.iter_custom(|iters| print_quiet(&points, &now, iters)));
+    group.bench_function("slices", |b| b.iter_custom(|iters| print_quiet(&slices, &0, iters)));
+    group.bench_function("todos", |b| b.iter_custom(|iters| print_quiet(&todos, &0, iters)));
+    group.finish();
+}
+
+fn main(){
+    let home = std::env::temp_dir().join(format!("pplanner-bench-{}", std::process::id()));
+    std::env::set_var("HOME", &home);
+    assert!(synth::write_dataset(&path(""), records(), SEED), "bench: could not write the records");
+    let mut c = Criterion::default().configure_from_args();
+    bench_read(&mut c);
+    bench_sort(&mut c);
+    bench_remove_indices(&mut c);
+    bench_replace(&mut c);
+    bench_get_matches(&mut c);
+    bench_pretty_print(&mut c);
+    c.final_summary();
+    let _ = std::fs::remove_dir_all(&home);
+}
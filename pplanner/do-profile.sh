@@ -1,6 +1,10 @@
 #!/bin/bash
+#profiles one command on generated data, the same every run: ./do-profile.sh [records] [command]
 cargo build --release
-perf record --call-graph=dwarf ./target/release/pplanner
+export HOME=$(mktemp -d)
+./target/release/pplanner gen-data ${1:-100000} $HOME/.config/pplanner
+perf record --call-graph=dwarf ./target/release/pplanner -e "${2:-ls points}" > /dev/null
 perf report --hierarchy -M intel
+rm -r $HOME
 rm *.data
-rm *.data.old
\ No newline at end of file
+rm *.data.old
@@ -0,0 +1,9 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use pplanner::{data, save};
+
+//a file of these records as it could be found on disk
+fuzz_target!(|bytes: &[u8]|{
+    let _ = save::decode_records::<data::Point>(&bytes.to_vec());
+});
@@ -5,8 +5,6 @@ sudo cp -f target/release/pplanner /usr/local/bin
 sudo chmod 755 /usr/local/bin/pplanner
 # install other files
 sudo mkdir -p ~/.config/pplanner
-sudo rm -rf ~/.config/pplanner/help
-sudo cp -rf help ~/.config/pplanner/help
 sudo cp LICENSE ~/.config/pplanner/LICENSE
 # install manpage
 sudo mkdir -p /usr/local/share/man/man1
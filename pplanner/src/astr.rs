@@ -45,7 +45,6 @@ pub trait AStr{
     fn new() -> Self;
     fn len(&self) -> usize;
     fn is_empty(&self) -> bool;
-    fn clear(&mut self);
     fn to_string(&self) -> std::string::String;
     fn split_str(&self, splitchars: &Astr) -> AstrVec;
     fn copy_from_ref(&self) -> Astr;
@@ -56,7 +55,7 @@ pub trait AStr{
     fn to_lower(&self) -> Astr;
     fn cut(&self, max: u16) -> Astr;
     fn disp(&self) -> DisplayableAstr;
-    fn sameness(&self, other: &Astr) -> f32;
+    fn distance(&self, other: &Astr) -> usize;
 }
 
 impl AStr for Astr{
@@ -70,9 +69,6 @@ impl AStr for Astr{
 
     fn is_empty(&self) -> bool{
         self.0.is_empty()
-   }
-    fn clear(&mut self){
-        self.0.clear();
     }
 
     fn to_string(&self) -> std::string::String{
@@ -130,18 +126,16 @@ impl AStr for Astr{
         }
         let mut newstr = Vec::new();
         for i in 0..(max-3){
-            newstr.push(self.0[i as usize] as u8);
-        }
-        for _ in 0..3 {
-            newstr.push(b'.');
+            newstr.push(self.0[i as usize]);
         }
+        newstr.extend_from_slice(b"...");
         Astr(newstr)
     }
 
     fn cut(&self, max: u16) -> Astr{
         let mut newstr = Vec::new();
         for i in 0..(std::cmp::min(max, std::cmp::max(max, self.len() as u16))){
-            newstr.push(self.0[i as usize] as u8);
+            newstr.push(self.0[i as usize]);
         }
         Astr(newstr)
     }
@@ -182,9 +176,10 @@ impl AStr for Astr{
         let mut newstr = Vec::new();
         for ch in &self.0{
             if char_is_letter_upper(*ch){
-                newstr.push(ch - 26);
+                newstr.push(ch + 32);
+            }else{
+                newstr.push(*ch);
             }
-            newstr.push(*ch);
         }
         Astr(newstr)
     }
@@ -195,18 +190,25 @@ impl AStr for Astr{
         }
     }
 
-    fn sameness(&self, other: &Astr) -> f32{
-        if self.0 == other.0 { return 1.0; }
-        let mut sum = 0.0;
-        for sel in &self.0{
-            for oth in &other.0{
-                if sel == oth{
-                    sum += 1.0;
-                    break;
+    //Damerau-Levenshtein, swapping two neighbours counts as one edit
+    fn distance(&self, other: &Astr) -> usize{
+        let a = &self.0;
+        let b = &other.0;
+        let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for (i, row) in d.iter_mut().enumerate() {row[0] = i;}
+        for (j, cell) in d[0].iter_mut().enumerate() {*cell = j;}
+        for i in 1..=a.len(){
+            for j in 1..=b.len(){
+                let cost = if a[i - 1] == b[j - 1] {0} else {1};
+                let mut best = std::cmp::min(d[i - 1][j] + 1, d[i][j - 1] + 1);
+                best = std::cmp::min(best, d[i - 1][j - 1] + cost);
+                if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1]{
+                    best = std::cmp::min(best, d[i - 2][j - 2] + 1);
                 }
+                d[i][j] = best;
             }
         }
-        sum / std::cmp::max(self.len(), other.len()) as f32
+        d[a.len()][b.len()]
     }
 }
 
@@ -219,18 +221,12 @@ impl save::Bufferable for Astr{
     }
 
     fn from_buffer(vec: &Vec<u8>, iter: &mut u32) -> Option<Self>{
-        let res_len = u32::from_buffer(vec, iter);
-        res_len?;
-        let len = res_len.unwrap();
-        if (vec.len() as i32) - (*iter as i32) < (len as i32) {
-            return Option::None;
-        }
-        let mut string = Vec::new();
-        for i in *iter..(*iter+len){
-            string.push(vec[i as usize]);
-        }
-        *iter += len;
-        Option::Some(Astr(string))
+        let len = u32::from_buffer(vec, iter)? as usize;
+        //the length comes from the file, it can be anything
+        let start = *iter as usize;
+        let end = start.checked_add(len).filter(|end| *end <= vec.len())?;
+        *iter = end as u32;
+        Option::Some(Astr(vec[start..end].to_vec()))
     }
 }
 
@@ -242,16 +238,14 @@ impl Default for Astr{
 
 pub fn unsplit(vec: &AstrVec, divider: u8) -> Astr{
     let mut newstr = Vec::new();
-    let mut counter = 0;
     let max = vec.len() - 1;
-    for v in vec{
+    for (counter, v) in vec.iter().enumerate(){
         for ch in &v.0{
             newstr.push(*ch);
         }
         if counter != max{
             newstr.push(divider);
         }
-        counter+=1;
     }
     Astr(newstr)
 }
@@ -264,7 +258,7 @@ pub const CHAR_START_UPPER: u8 = 65;
 }*/
 
 pub fn char_is_letter_upper(ch: u8) -> bool{
-    ch >= CHAR_START_UPPER && ch <= 90
+    (CHAR_START_UPPER..=90).contains(&ch)
 }
 
 pub fn to_u32_checked(string: &Astr) -> Option<u32>{
@@ -7,45 +7,33 @@ use super::conz;
 use super::conz::Printable;
 use super::data;
 use super::astr;
-use super::astr::{AStr};
+use super::astr::{AStr,ToAstr};
 use super::state;
 use super::support;
 use super::save;
-
-pub fn missing_help(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+use super::wizard;
+use super::report;
+use super::hooks;
+use super::sync;
+use super::crypt;
+use super::storage;
+use super::error;
+use super::error::{CmdError};
+
+pub fn missing_help(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_inputs(&inputs);
     support::warn_unused_arguments(&args);
     let mut missing = Vec::new();
-    for f in state.fset.clone(){
-        let mut path = std::path::PathBuf::from("./help");
-        let mut metatdata = std::fs::metadata(path.as_path());
-        if metatdata.is_err(){
-            let res = save::get_data_dir_path("help");
-            if let Some(resv) = res{
-                path = resv;
-                metatdata = std::fs::metadata(path.as_path());
-            }
-        }
-        if metatdata.is_err(){
-            conz::println_type("Error: Help directory not found.", conz::MsgType::Error);
-            return;
-        }
-        let res = state.fset.contains(&f);
-        if !res { // really should not happen xd
-            conz::println_type("Fail: command does not exist, so help for it neither.", conz::MsgType::Error);
-            continue;
-        }
-        path.push(astr::unsplit(&f.split_str(&astr::astr_whitespace()), b'_').to_string());
-        let res = std::fs::metadata(path.clone());
-        if res.is_err(){
-            missing.push(f);
+    for (name, help) in &state.commands{
+        if help.examples.is_empty() || !help.usage.starts_with(&name.to_string()){
+            missing.push(name);
         }
     }
-    conz::println_type("These commands do exist but have no help file: ", conz::MsgType::Highlight);
-    missing.sort();
+    conz::println_type("These commands have no examples or a usage that does not match: ", conz::MsgType::Highlight);
     for c in missing{
         conz::println_type(c.disp(), conz::MsgType::Normal);
     }
+    Ok(())
 }
 
 pub fn help_cli(){
@@ -57,11 +45,20 @@ pub fn help_cli(){
     conz::println_type("Give a pplanner command as cli argument to run it directly from the terminal.", conz::MsgType::Normal);
     conz::print_type("For example: ", conz::MsgType::Normal);
     conz::println_type("pplanner \'ls todos\'", conz::MsgType::Highlight);
+    conz::print_type("Run a file of commands with ", conz::MsgType::Normal);
+    conz::print_type("pplanner -f script", conz::MsgType::Highlight);
+    conz::println_type(", use - to read it from stdin.", conz::MsgType::Normal);
+    conz::print_type("Shell completion: ", conz::MsgType::Normal);
+    conz::println_type("pplanner completions bash|zsh|fish", conz::MsgType::Highlight);
+    conz::print_type("Reminders before points and slices: ", conz::MsgType::Normal);
+    conz::println_type("pplanner daemon", conz::MsgType::Highlight);
+    conz::print_type("Merge diverged data files: ", conz::MsgType::Normal);
+    conz::println_type("pplanner merge base ours theirs", conz::MsgType::Highlight);
     conz::print_type("pplanner is made by ", conz::MsgType::Normal);
     conz::println_type("Cody Bloemhard.", conz::MsgType::Prompt);
 }
 
-pub fn now(_: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn now(_: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     support::warn_unused_inputs(&inputs);
     let dt = data::DT::new();
@@ -75,101 +72,90 @@ pub fn now(_: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<as
     conz::println_type(dt.str_dayname().disp(), conz::MsgType::Value);
     conz::print_type("Month: ", conz::MsgType::Normal);
     conz::println_type(dt.str_monthname().disp(), conz::MsgType::Value);
+    Ok(())
 }
 
-pub fn status(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
-    now(state, args.clone(), inputs.clone());
-    ls_points(state, args.clone(), inputs.clone());
-    ls_plans(state, args.clone(), inputs.clone());
+pub fn status(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    now(state, args.clone(), inputs.clone())?;
+    if let Some(session) = state.sessions.get_items().iter().find(|s| s.running){
+        conz::print_type("Timer: ", conz::MsgType::Normal);
+        conz::print_type(session.title.disp(), conz::MsgType::Highlight);
+        conz::print_type(" for ", conz::MsgType::Normal);
+        conz::println_type(data::Span::from_secs(session.secs(), false).string_significant(true), conz::MsgType::Value);
+    }
+    ls_points(state, args.clone(), inputs.clone())?;
+    ls_plans(state, args.clone(), inputs.clone())?;
+    if state.habits.get_items().is_empty() {return Ok(());}
+    ls_habits(state, args, inputs)
 }
 
-pub fn license(_: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn license(_: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     support::warn_unused_inputs(&inputs);
     let path = save::get_data_dir_path("LICENSE");
     if path.is_none(){
         conz::println_type("Error: Could not find license file.", conz::MsgType::Error);
-        return;
+        return Err(CmdError::NotFound);
     }
     let path = path.unwrap();
     let metatdata = std::fs::metadata(path.as_path());
     if metatdata.is_err(){
         conz::println_type("Error: Could not find license file.", conz::MsgType::Error);
-        return;
+        return Err(CmdError::NotFound);
     }
     let f = File::open(path.as_path());
     if f.is_err(){
         conz::println_type("Error: could not open file.", conz::MsgType::Error);
-        return;
+        return Err(CmdError::Io);
     }
     let mut f = f.unwrap();
     let mut string = String::new();
     let ok = f.read_to_string(&mut string);
     if ok.is_err(){
         conz::println_type("Error: could not read file.", conz::MsgType::Error);
-        return;
+        return Err(CmdError::Io);
     }
     conz::println_type(string, conz::MsgType::Normal);
+    Ok(())
 }
 
-pub fn help(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn help(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_inputs(&inputs);
     if args.is_empty(){
         conz::print_type("Help, type ", conz::MsgType::Normal);
-        conz::print_type("help(command) ", conz::MsgType::Highlight);
+        conz::print_type("help command ", conz::MsgType::Highlight);
         conz::println_type("to find help.", conz::MsgType::Normal);
         conz::print_type("For example: ", conz::MsgType::Normal);
-        conz::print_type("help (mk point)", conz::MsgType::Highlight);
+        conz::print_type("help mk point", conz::MsgType::Highlight);
         conz::println_type(".", conz::MsgType::Normal);
         conz::print_type("To list all commands use ", conz::MsgType::Normal);
         conz::print_type("ls commands", conz::MsgType::Highlight);
         conz::println_type(".", conz::MsgType::Normal);
-        return;
+        return Ok(());
     }
-    let mut path = std::path::PathBuf::from("./help");
-    let mut metatdata = std::fs::metadata(path.as_path());
-    if metatdata.is_err(){
-        let res = save::get_data_dir_path("help");
-        if let Some(resv) = res{
-            path = resv;
-            metatdata = std::fs::metadata(path.as_path());
+    //both help(ls points) and help ls points
+    let name = astr::unsplit(&args, b' ');
+    match state.commands.get(&name){
+        Option::None => {
+            conz::println_type("Fail: command does not exist, so help for it neither.", conz::MsgType::Error);
+            Err(CmdError::NotFound)
+        }
+        Option::Some(help) => {
+            help.print(&name.to_string());
+            Ok(())
         }
     }
-    if metatdata.is_err(){
-        conz::println_type("Error: Help directory not found.", conz::MsgType::Error);
-        return;
-    }
-    let res = state.fset.contains(&args[0]);
-    if !res {
-        conz::println_type("Fail: command does not exist, so help for it neither.", conz::MsgType::Error);
-        return;
-    }
-    path.push(astr::unsplit(&args[0].split_str(&astr::astr_whitespace()), b'_').to_string());
-    let res = std::fs::metadata(path.clone());
-    if res.is_err(){
-        conz::println_type("Error: help file not found.", conz::MsgType::Error);
-        return;
-    }
-    let f = File::open(path.as_path());
-    if f.is_err(){
-        conz::println_type("Error: could not open file.", conz::MsgType::Error);
-        return;
-    }
-    let mut f = f.unwrap();
-    let mut string = String::new();
-    let ok = f.read_to_string(&mut string);
-    if ok.is_err(){
-        conz::println_type("Error: could not read file.", conz::MsgType::Error);
-        return;
-    }
-    conz::print_type("Command: ", conz::MsgType::Normal);
-    conz::println_type(astr::unsplit(&args, b' ').to_string(), conz::MsgType::Highlight);
-    conz::println_type(string, conz::MsgType::Normal);
 }
 
-pub fn ls_days(_: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn ls_days(_: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     support::warn_unused_inputs(&inputs);
+    if conz::is_machine(){
+        let rows: Vec<Vec<conz::Raw>> = (0..7).map(|i| vec![conz::Raw::Num(i64::from(i)),
+            conz::Raw::Text(data::day_name(i)), conz::Raw::Text(data::day_name_short(i))]).collect();
+        support::machine_print(&["nr", "name", "short"], &rows);
+        return Ok(());
+    }
     conz::println_type("All names of the days in the week: ", conz::MsgType::Normal);
     for i in 0..7 {
         conz::print_type(format!("{} ", i), conz::MsgType::Normal);
@@ -178,11 +164,18 @@ pub fn ls_days(_: &mut state::State, args: astr::AstrVec, inputs: Option<VecDequ
         conz::print_type(format!("{}", data::day_name_short(i).disp()), conz::MsgType::Value);
         conz::println_type(")", conz::MsgType::Normal);
     }
+    Ok(())
 }
 
-pub fn ls_months(_: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn ls_months(_: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     support::warn_unused_inputs(&inputs);
+    if conz::is_machine(){
+        let rows: Vec<Vec<conz::Raw>> = (1..13).map(|i| vec![conz::Raw::Num(i64::from(i)),
+            conz::Raw::Text(data::month_name(i)), conz::Raw::Text(data::month_name_short(i))]).collect();
+        support::machine_print(&["nr", "name", "short"], &rows);
+        return Ok(());
+    }
     conz::println_type("All names of the months in the year: ", conz::MsgType::Normal);
     for i in 1..13 {
         conz::print_type(format!("{} ", i), conz::MsgType::Normal);
@@ -192,38 +185,43 @@ pub fn ls_months(_: &mut state::State, args: astr::AstrVec, inputs: Option<VecDe
         conz::print_type(data::month_name_short(i).disp(), conz::MsgType::Value);
         conz::println_type(")", conz::MsgType::Normal);
     }
+    Ok(())
 }
 
-pub fn ls_commands(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn ls_commands(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     support::warn_unused_inputs(&inputs);
-    conz::println_type("All commands: ", conz::MsgType::Normal);
-    let mut commands = Vec::new();
-    for f in state.fset.clone(){
-        commands.push(f);
+    if conz::is_machine(){
+        let rows: Vec<Vec<conz::Raw>> = state.commands.iter()
+            .map(|(f,h)| vec![conz::Raw::Text(f.clone()), conz::Raw::Text(astr::from_str(h.summary))])
+            .collect();
+        support::machine_print(&["command", "summary"], &rows);
+        return Ok(());
     }
-    commands.sort();
-    for f in commands{
-        conz::println_type(f.disp(), conz::MsgType::Normal);
+    conz::println_type("All commands: ", conz::MsgType::Normal);
+    let width = state.commands.keys().map(|f| f.len()).max().unwrap_or(0);
+    for (f,h) in &state.commands{
+        conz::print_type(format!("{:w$}  ", f.to_string(), w = width), conz::MsgType::Highlight);
+        conz::println_type(h.summary, conz::MsgType::Normal);
     }
+    Ok(())
 }
 
-pub fn mk_point(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
-    support::warn_unused_arguments(&args);
-    support::mk_item(&mut state.points, &mut inputs);
+pub fn mk_point(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    support::mk_item(state.points.as_mut(), &args, &mut inputs)
 }
 
-pub fn rm_points(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
+pub fn rm_points(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     let items = state.points.get_items().clone();
-    support::rm_items(items, &mut state.points, &mut state.points_archive, &mut inputs);
+    support::rm_items(items, state.points.as_mut(), state.points_archive.as_mut(), &mut inputs)
 }
 
-pub fn clean_points(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
+pub fn clean_points(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     conz::println_type("Remove all points that are in the past: ", conz::MsgType::Normal);
     if !conz::read_bool("Sure to remove them?: ", &mut inputs){
-        return;
+        return Ok(());
     }
     let points = state.points.get_items().clone();
     let mut vec = Vec::new();
@@ -234,72 +232,82 @@ pub fn clean_points(state: &mut state::State, args: astr::AstrVec, mut inputs: O
         }
         vec.push(i);
     }
-    support::remove_and_archive(&mut state.points, &mut state.points_archive, vec, &points);
+    support::remove_and_archive(state.points.as_mut(), state.points_archive.as_mut(), vec, &points)
 }
 
-pub fn edit_points(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn edit_points(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     check_unsupported_inputs!(inputs);
-    support::edit_items(&mut state.points);
+    support::edit_items(state.points.as_mut())
 }
 
-pub fn ls_points(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn ls_points(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     support::warn_unused_inputs(&inputs);
     support::pretty_print(state.points.get_items(), &data::DT::new());
+    Ok(())
 }
 
-pub fn ls_points_archive(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
-    support::warn_unused_arguments(&args);
-    support::warn_unused_inputs(&inputs);
-    let res = state.points_archive.read();
+pub fn ls_points_archive(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    let query = support::archive_query(&args, &mut inputs, true);
+    if query.is_none() {return Err(CmdError::BadInput);}
+    let res: Vec<data::Point> = state.points_archive.select(&query.unwrap()).collect();
     support::pretty_print(&res, &data::DT::new());
+    Ok(())
 }
 
-pub fn inspect_point(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
+pub fn inspect_point(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     conz::println_type("Inspect point(search first): ", conz::MsgType::Normal);
     loop{
         let points = state.points.get_items();
-        let (match_res, vec) = support::get_matches(&points,&mut inputs);
+        let (match_res, vec) = support::get_matches(points,&mut inputs);
         if match_res == support::MatchResult::None || vec.len() > 1{
             if vec.len() > 1{
                 conz::println_type("Fail: more than one result.", conz::MsgType::Error);
             }else{
                 conz::println_type("Fail: no results found.", conz::MsgType::Error);
             }
-            if inputs.is_some() {return;}
-            if conz::read_bool("Try again?: ", &mut Option::None) {continue;}
-            else {return;}
+            if inputs.is_some() || !conz::read_bool("Try again?: ", &mut Option::None){
+                return Err(CmdError::NoMatch);
+            }
+            continue;
         }
-        points[vec[0]].print();
         let now = data::DT::new();
+        if conz::is_machine(){
+            support::pretty_print(&points[vec[0]..=vec[0]], &now);
+            return Ok(());
+        }
+        points[vec[0]].print();
         let diff = now.diff(&points[vec[0]].dt);
         diff.print();
-        return;
+        return Ok(());
     }
 }
 
-pub fn mk_plan(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
-    support::warn_unused_arguments(&args);
-    support::mk_item(&mut state.plans, &mut inputs);
+pub fn mk_plan(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    support::mk_item(state.plans.as_mut(), &args, &mut inputs)
 }
 
-pub fn rm_plans(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
+pub fn rm_plans(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     let items = state.plans.get_items().clone();
-    support::rm_items(items, &mut state.plans, &mut state.plans_archive, &mut inputs);
+    support::rm_items(items, state.plans.as_mut(), state.plans_archive.as_mut(), &mut inputs)
 }
 
-pub fn edit_plans(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn edit_plans(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     check_unsupported_inputs!(inputs);
-    support::edit_items(&mut state.plans);
+    support::edit_items(state.plans.as_mut())
 }
 
-pub fn ls_plans(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn ls_plans(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     support::warn_unused_inputs(&inputs);
+    if conz::is_machine(){
+        support::pretty_print(state.plans.get_items(), &true);
+        return Ok(());
+    }
     let (doi,tod,lon,ide) = support::split_todos(state.plans.get_items());
     conz::print_type("Current: ", conz::MsgType::Normal);
     support::pretty_print(&doi, &false);
@@ -309,16 +317,18 @@ pub fn ls_plans(state: &mut state::State, args: astr::AstrVec, inputs: Option<Ve
     support::pretty_print(&lon, &false);
     conz::print_type("Idea: ", conz::MsgType::Normal);
     support::pretty_print(&ide, &false);
+    Ok(())
 }
 
-pub fn ls_plans_archive(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
-    support::warn_unused_arguments(&args);
-    support::warn_unused_inputs(&inputs);
-    let res = state.plans_archive.read();
+pub fn ls_plans_archive(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    let query = support::archive_query(&args, &mut inputs, false);
+    if query.is_none() {return Err(CmdError::BadInput);}
+    let res: Vec<data::Plan> = state.plans_archive.select(&query.unwrap()).collect();
     support::pretty_print(&res, &true);
+    Ok(())
 }
 
-pub fn mv_plans(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
+pub fn mv_plans(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     conz::println_type("Move plans (search first): ", conz::MsgType::Normal);
     let cli = inputs.is_some();
@@ -328,9 +338,10 @@ pub fn mv_plans(state: &mut state::State, args: astr::AstrVec, mut inputs: Optio
         match match_res{
             support::MatchResult::None =>{
                 conz::println_type("Fail: no matches found.", conz::MsgType::Error);
-                if cli {return;}
-                if conz::read_bool("Try again?: ", &mut inputs) {continue;}
-                else {return;}
+                if cli || !conz::read_bool("Try again?: ", &mut inputs) {
+                    return Err(CmdError::NoMatch);
+                }
+                continue;
             }
             support::MatchResult::Some =>{
                 conz::print_type("Found ", conz::MsgType::Normal);
@@ -341,9 +352,12 @@ pub fn mv_plans(state: &mut state::State, args: astr::AstrVec, mut inputs: Optio
                 }
                 if !cli && !conz::read_bool("Move all?: ", &mut inputs){
                     if conz::read_bool("Try again?: ", &mut inputs) {continue;}
-                    else {return;}
+                    else {return Ok(());}
                 }
-                let x = conz::prompt("New type: ");
+                let x = match conz::prompt("New type: "){
+                    Option::Some(x) => x,
+                    Option::None => return Ok(()),
+                };
                 let ttype = data::PlanType::from_astr(&astr::from_str(&x), true);
                 let mut replacements = Vec::new();
                 let mut indices = Vec::new();
@@ -356,30 +370,29 @@ pub fn mv_plans(state: &mut state::State, args: astr::AstrVec, mut inputs: Optio
                 let ok = state.plans.replace(indices, replacements);
                 if ok {
                     conz::println_type("Success: Plans moved.", conz::MsgType::Highlight);
-                }else{
-                    conz::println_type("Error: Plans moving failed.", conz::MsgType::Highlight);
+                    return Ok(());
                 }
-                return;
+                conz::println_type("Error: Plans moving failed.", conz::MsgType::Error);
+                return Err(CmdError::Io);
             }
         }
     }
 }
 
-pub fn mk_slice(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
-    support::warn_unused_arguments(&args);
-    support::mk_item(&mut state.slices, &mut inputs);
+pub fn mk_slice(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    support::mk_item(state.slices.as_mut(), &args, &mut inputs)
 }
 
-pub fn rm_slices(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
+pub fn rm_slices(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     let items = state.slices.get_items().clone();
-    support::rm_items(items, &mut state.slices, &mut state.slices_archive, &mut inputs);
+    support::rm_items(items, state.slices.as_mut(), state.slices_archive.as_mut(), &mut inputs)
 }
 
-pub fn clean_slices(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
+pub fn clean_slices(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     conz::println_type("Remove all slices that are in the past: ", conz::MsgType::Normal);
-    if !conz::read_bool("Sure to remove them?: ", &mut inputs) {return;}
+    if !conz::read_bool("Sure to remove them?: ", &mut inputs) {return Ok(());}
     let slices = state.slices.get_items().clone();
     let mut vec = Vec::new();
     let now = data::DT::new();
@@ -389,43 +402,50 @@ pub fn clean_slices(state: &mut state::State, args: astr::AstrVec, mut inputs: O
         }
         vec.push(i);
     }
-    support::remove_and_archive(&mut state.slices, &mut state.slices_archive, vec, &slices);
+    support::remove_and_archive(state.slices.as_mut(), state.slices_archive.as_mut(), vec, &slices)
 }
 
-pub fn edit_slices(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn edit_slices(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     check_unsupported_inputs!(inputs);
-    support::edit_items(&mut state.slices);
+    support::edit_items(state.slices.as_mut())
 }
 
-pub fn ls_slices(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn ls_slices(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     support::warn_unused_inputs(&inputs);
     support::pretty_print(state.slices.get_items(), &0);
+    Ok(())
 }
 
-pub fn ls_slices_archive(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
-    support::warn_unused_arguments(&args);
-    support::warn_unused_inputs(&inputs);
-    let res = state.slices_archive.read();
+pub fn ls_slices_archive(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    let query = support::archive_query(&args, &mut inputs, true);
+    if query.is_none() {return Err(CmdError::BadInput);}
+    let res: Vec<data::Slice> = state.slices_archive.select(&query.unwrap()).collect();
     support::pretty_print(&res, &0);
+    Ok(())
 }
 
-pub fn inspect_slice(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
+pub fn inspect_slice(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     conz::println_type("Inspect slice(search first): ", conz::MsgType::Normal);
     loop{
         let slices = state.slices.get_items();
-        let (match_res, vec) = support::get_matches(&slices,&mut inputs);
+        let (match_res, vec) = support::get_matches(slices,&mut inputs);
         if match_res == support::MatchResult::None || vec.len() > 1{
             if vec.len() > 1{
                 conz::println_type("Fail: more than one result.", conz::MsgType::Error);
             }else{
                 conz::println_type("Fail: no results found.", conz::MsgType::Error);
             }
-            if inputs.is_some() {return;}
-            if conz::read_bool("Try again?: ", &mut Option::None) {continue;}
-            else {return;}
+            if inputs.is_some() || !conz::read_bool("Try again?: ", &mut Option::None){
+                return Err(CmdError::NoMatch);
+            }
+            continue;
+        }
+        if conz::is_machine(){
+            support::pretty_print(&slices[vec[0]..=vec[0]], &0);
+            return Ok(());
         }
         let slice = &slices[vec[0]];
         slice.print();
@@ -439,16 +459,39 @@ pub fn inspect_slice(state: &mut state::State, args: astr::AstrVec, mut inputs:
         conz::println_type("End:", conz::MsgType::Highlight);
         let diff = now.diff(&slice.end);
         diff.print();
-        return;
+        return Ok(());
     }
 }
 
-pub fn mk_todo(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
-    support::warn_unused_arguments(&args);
-    support::mk_item(&mut state.todos, &mut inputs);
+pub fn report(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    let mut fields = wizard::FieldVec::new();
+    fields.add("from", wizard::InputType::Date, astr::from_str("From(date): "), wizard::PromptType::Once);
+    fields.add("to", wizard::InputType::Date, astr::from_str("To(date): "), wizard::PromptType::Once);
+    let wres = fields.execute(&args, &mut inputs);
+    if wres.is_none() {return Err(CmdError::BadInput);}
+    let mut wres = wres.unwrap();
+    let from = wres.get_dt().unwrap_or_default();
+    //the to date is included, so up to the midnight after it
+    let to = wres.get_dt().unwrap_or_default();
+    let to = to.next_midnight().unwrap_or(to);
+    if to <= from{
+        conz::println_type("Fail: from should be before to.", conz::MsgType::Error);
+        return Err(CmdError::BadInput);
+    }
+    //slices that start before the end of the range, the ones that end before it are cut away
+    let query = storage::Query{to: Option::Some(to.dt.timestamp()), ..storage::Query::default()};
+    let mut slices: Vec<data::Slice> = state.slices_archive.select(&query).collect();
+    slices.extend(state.slices.get_items().iter().cloned());
+    let rows = report::build(&slices, &from, &to);
+    support::pretty_print(&rows, &0);
+    Ok(())
+}
+
+pub fn mk_todo(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    support::mk_item(state.todos.as_mut(), &args, &mut inputs)
 }
 
-pub fn tick_todos(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
+pub fn tick_todos(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     conz::print_type("Tick todo(search first): ", conz::MsgType::Normal);
     let items = state.todos.get_items();
@@ -458,7 +501,7 @@ pub fn tick_todos(state: &mut state::State, args: astr::AstrVec, mut inputs: Opt
             support::MatchResult::None =>{
                 conz::println_type("Fail: no matches found.", conz::MsgType::Error);
                 if conz::read_bool("Try again?: ", &mut Option::None) {continue;}
-                else {return;}
+                return Err(CmdError::NoMatch);
             }
             support::MatchResult::Some =>{
                 conz::print_type("Found ", conz::MsgType::Normal);
@@ -478,31 +521,32 @@ pub fn tick_todos(state: &mut state::State, args: astr::AstrVec, mut inputs: Opt
                         indices.push(*i);
                         replacements.push(ntodo);
                     }
-                    let ok = state.todos.replace(indices, replacements);
+                    let ok = state.todos.replace(indices, replacements.clone());
                     if ok {
                         conz::println_type("Success: Todos edited.", conz::MsgType::Highlight);
-                    }else{
-                        conz::println_type("Error: Todos editing failed.", conz::MsgType::Highlight);
+                        hooks::fire(hooks::Event::Tick, "todo", &replacements);
+                        return Ok(());
                     }
-                    return;
+                    conz::println_type("Error: Todos editing failed.", conz::MsgType::Error);
+                    return Err(CmdError::Io);
                 }
                 if conz::read_bool("Try again?: ", &mut Option::None) {continue;}
-                else {return;}
+                return Ok(());
             }
         }
     }
 }
 
-pub fn rm_todos(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
+pub fn rm_todos(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     let items = state.todos.get_items().clone();
-    support::rm_items(items, &mut state.todos, &mut state.todos_archive, &mut inputs);
+    support::rm_items(items, state.todos.as_mut(), state.todos_archive.as_mut(), &mut inputs)
 }
 
-pub fn clean_todos(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>){
+pub fn clean_todos(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     conz::println_type("Remove all todos that are done: ", conz::MsgType::Normal);
-    if !conz::read_bool("Sure to remove them?: ", &mut inputs) {return;}
+    if !conz::read_bool("Sure to remove them?: ", &mut inputs) {return Ok(());}
     let todos = state.todos.get_items().clone();
     let mut vec = Vec::new();
     for (i,item) in todos.iter().enumerate(){
@@ -511,41 +555,356 @@ pub fn clean_todos(state: &mut state::State, args: astr::AstrVec, mut inputs: Op
         }
         vec.push(i);
     }
-    support::remove_and_archive(&mut state.todos, &mut state.todos_archive, vec, &todos);
+    support::remove_and_archive(state.todos.as_mut(), state.todos_archive.as_mut(), vec, &todos)
 }
 
-pub fn ls_todos(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn ls_todos(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     support::warn_unused_inputs(&inputs);
     support::pretty_print(state.todos.get_items(), &0);
+    Ok(())
 }
 
-pub fn ls_todos_archive(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn ls_todos_archive(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    let query = support::archive_query(&args, &mut inputs, false);
+    if query.is_none() {return Err(CmdError::BadInput);}
+    let res: Vec<data::Todo> = state.todos_archive.select(&query.unwrap()).collect();
+    support::pretty_print(&res, &0);
+    Ok(())
+}
+
+//everything time can be spent on, with its estimate
+fn trackables(state: &mut state::State) -> Vec<(astr::Astr, data::SessionKind, u32)>{
+    let mut all = Vec::new();
+    for plan in state.plans.get_items(){
+        all.push((plan.title.clone(), data::SessionKind::Plan, plan.estimate));
+    }
+    for todo in state.todos.get_items(){
+        all.push((todo.title.clone(), data::SessionKind::Todo, todo.estimate));
+    }
+    all
+}
+
+//plans and todos that have the title, exact first, else a unique part of it
+fn find_trackable(state: &mut state::State, title: &astr::Astr) -> Result<(astr::Astr, data::SessionKind, u32), CmdError>{
+    let all = trackables(state);
+    let titles: Vec<astr::Astr> = all.iter().map(|(t,_,_)| t.clone()).collect();
+    let found: Vec<_> = support::match_title(&titles, title).into_iter().map(|i| &all[i]).collect();
+    match found.len(){
+        0 =>{
+            conz::println_type("Fail: no plan or todo with that title.", conz::MsgType::Error);
+            Err(CmdError::NoMatch)
+        }
+        1 => Ok(found[0].clone()),
+        _ =>{
+            conz::println_type("Fail: more than one plan or todo matches:", conz::MsgType::Error);
+            for (t,kind,_) in found{
+                conz::print_type(format!("  {} ", kind.to_astr().disp()), conz::MsgType::Normal);
+                conz::println_type(t.disp(), conz::MsgType::Highlight);
+            }
+            Err(CmdError::BadInput)
+        }
+    }
+}
+
+//stops the running session, if any, and returns it
+fn stop_session(state: &mut state::State) -> Option<data::Session>{
+    let sessions = state.sessions.get_items();
+    let index = sessions.iter().position(|s| s.running)?;
+    let mut session = sessions[index].clone();
+    session.stop();
+    if !state.sessions.replace(vec![index], vec![session.clone()]) {return Option::None;}
+    Option::Some(session)
+}
+
+fn print_stopped(state: &mut state::State, session: &data::Session){
+    conz::print_type("Stopped: ", conz::MsgType::Normal);
+    conz::print_type(session.title.disp(), conz::MsgType::Highlight);
+    conz::print_type(" after ", conz::MsgType::Normal);
+    conz::println_type(data::Span::from_secs(session.secs(), false).string_significant(true), conz::MsgType::Value);
+    let estimate = match find_trackable(state, &session.title){
+        Ok((_,_,estimate)) => estimate,
+        Err(_) => 0,
+    };
+    let tracked = data::Tracked::new(session.title.clone(), session.kind, estimate, state.sessions.get_items());
+    conz::print_type("Spent in total: ", conz::MsgType::Normal);
+    conz::print_type(tracked.str_spent().disp(), conz::MsgType::Value);
+    if let Some(progress) = tracked.progress(){
+        conz::print_type(" of estimated ", conz::MsgType::Normal);
+        conz::print_type(data::str_minutes(estimate).disp(), conz::MsgType::Value);
+        conz::print_type(" ", conz::MsgType::Normal);
+        let ptype = if progress > 100 {conz::MsgType::Error} else {conz::MsgType::Value};
+        conz::print_type(format!("({}%)", progress), ptype);
+    }
+    conz::println_type("", conz::MsgType::Normal);
+}
+
+pub fn start(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    let title = if !args.is_empty(){
+        support::warn_unused_inputs(&inputs);
+        astr::unsplit(&args, b' ')
+    }else if let Some(x) = inputs.as_mut().and_then(|i| i.pop_front()){
+        x
+    }else if inputs.is_none(){
+        astr::from_str(&conz::prompt("Plan or todo to start: ").unwrap_or_default())
+    }else{
+        conz::println_type("Error: Not enough inputs provided for command, missing: title", conz::MsgType::Error);
+        return Err(CmdError::BadInput);
+    };
+    let (title, kind, _) = find_trackable(state, &title)?;
+    if let Some(session) = stop_session(state){
+        print_stopped(state, &session);
+    }
+    if !state.sessions.add_item(data::Session::start(title.clone(), kind)) {return Err(CmdError::Io);}
+    if !state.sessions.write() {return Err(CmdError::Io);}
+    conz::print_type("Started: ", conz::MsgType::Normal);
+    conz::print_type(title.disp(), conz::MsgType::Highlight);
+    conz::print_type(" at ", conz::MsgType::Normal);
+    conz::println_type(data::DT::new().str_time().disp(), conz::MsgType::Value);
+    Ok(())
+}
+
+pub fn stop(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     support::warn_unused_inputs(&inputs);
-    let res = state.todos_archive.read();
-    support::pretty_print(&res, &0);
+    let session = stop_session(state);
+    if session.is_none(){
+        conz::println_type("Fail: no session is running.", conz::MsgType::Error);
+        return Err(CmdError::NotFound);
+    }
+    if !state.sessions.write() {return Err(CmdError::Io);}
+    print_stopped(state, &session.unwrap());
+    Ok(())
+}
+
+pub fn ls_sessions(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    support::warn_unused_arguments(&args);
+    support::warn_unused_inputs(&inputs);
+    support::pretty_print(state.sessions.get_items(), &0);
+    Ok(())
 }
 
+pub fn ls_spent(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    support::warn_unused_arguments(&args);
+    support::warn_unused_inputs(&inputs);
+    let items = trackables(state);
+    let sessions = state.sessions.get_items();
+    let tracked: Vec<data::Tracked> = items.into_iter()
+        .map(|(title, kind, estimate)| data::Tracked::new(title, kind, estimate, sessions))
+        .filter(|t| t.estimate > 0 || t.secs > 0)
+        .collect();
+    support::pretty_print(&tracked, &0);
+    Ok(())
+}
+
+pub fn mk_habit(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    support::mk_item(state.habits.as_mut(), &args, &mut inputs)
+}
+
+pub fn check_habit(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    let mut fields = wizard::FieldVec::new();
+    fields.add("title", wizard::InputType::Text, astr::from_str("Habit: "), wizard::PromptType::Once);
+    fields.add("date", wizard::InputType::Date, astr::from_str("Date(empty for today): "), wizard::PromptType::Partial);
+    let wres = fields.execute(&args, &mut inputs);
+    if wres.is_none() {return Err(CmdError::BadInput);}
+    let mut wres = wres.unwrap();
+    let title = wres.get_text().unwrap_or_default();
+    let date = wres.get_dt().unwrap_or_default();
+    let date = if date == data::DT::default() {data::DT::new()} else {date};
+    let habits = state.habits.get_items();
+    let titles: Vec<astr::Astr> = habits.iter().map(|h| h.title.clone()).collect();
+    let found = support::match_title(&titles, &title);
+    if found.is_empty(){
+        conz::println_type("Fail: no habit with that title.", conz::MsgType::Error);
+        return Err(CmdError::NoMatch);
+    }
+    if found.len() > 1{
+        conz::println_type("Fail: more than one habit matches:", conz::MsgType::Error);
+        for i in found{
+            conz::print_type("  ", conz::MsgType::Normal);
+            conz::println_type(titles[i].disp(), conz::MsgType::Highlight);
+        }
+        return Err(CmdError::BadInput);
+    }
+    let mut habit = habits[found[0]].clone();
+    if !habit.check(date.day_number()){
+        conz::print_type("Already checked on ", conz::MsgType::Normal);
+        conz::println_type(date.str_date().disp(), conz::MsgType::Value);
+        return Ok(());
+    }
+    if !state.habits.replace(vec![found[0]], vec![habit.clone()]) {return Err(CmdError::Io);}
+    conz::print_type("Checked: ", conz::MsgType::Normal);
+    conz::print_type(habit.title.disp(), conz::MsgType::Highlight);
+    conz::print_type(" on ", conz::MsgType::Normal);
+    conz::println_type(date.str_date().disp(), conz::MsgType::Value);
+    habit.print();
+    Ok(())
+}
+
+pub fn ls_habits(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    support::warn_unused_arguments(&args);
+    support::warn_unused_inputs(&inputs);
+    support::pretty_print(state.habits.get_items(), &0);
+    Ok(())
+}
+
+//unsaved changes are written first, so they are part of what is pulled and pushed
+fn sync_with(state: &mut state::State, args: &astr::AstrVec, inputs: &Option<VecDeque<astr::Astr>>,
+    pull: bool, push: bool) -> error::CmdRes{
+    support::warn_unused_arguments(args);
+    support::warn_unused_inputs(inputs);
+    if !state.flush_files() {return Err(CmdError::Io);}
+    if pull{
+        let ok = sync::pull();
+        //merged files are read again, like after a change by another process
+        state.refresh();
+        if !ok {return Err(CmdError::Io);}
+        conz::println_type("Success: pulled.", conz::MsgType::Highlight);
+    }
+    if push{
+        if !sync::push() {return Err(CmdError::Io);}
+        conz::println_type("Success: pushed.", conz::MsgType::Highlight);
+    }
+    Ok(())
+}
+
+pub fn sync(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    sync_with(state, &args, &inputs, true, true)
+}
+
+pub fn sync_pull(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    sync_with(state, &args, &inputs, true, false)
+}
+
+pub fn sync_push(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    sync_with(state, &args, &inputs, false, true)
+}
+
+fn convert_data(encrypt: bool) -> bool{
+    crypt::set_converting(true);
+    let ok = convert_files(encrypt);
+    crypt::set_converting(false);
+    ok
+}
+
+fn convert_files(encrypt: bool) -> bool{
+    //the changes in the logs go into the files first, then only the files are converted
+    if !storage::compact_all() {return false;}
+    for name in save::DATA_FILES.iter(){
+        if !save::convert_file(name, encrypt){
+            conz::print_type("Error: could not convert, the rest is left as it was: ", conz::MsgType::Error);
+            conz::println_type(name, conz::MsgType::Highlight);
+            return false;
+        }
+    }
+    true
+}
+
+/*
+The key file is written first, then the files are encrypted one by one.
+A file is replaced whole, so when it stops halfway no file is lost,
+the files that are still plain are turned down until encrypt data is run again to finish.
+*/
+pub fn encrypt_data(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    support::warn_unused_arguments(&args);
+    support::warn_unused_inputs(&inputs);
+    if storage::Backend::current() == storage::Backend::Sqlite{
+        conz::println_type("Error: the sqlite storage can not be encrypted, migrate storage flat first.", conz::MsgType::Error);
+        return Err(CmdError::BadInput);
+    }
+    if crypt::enabled() && !crypt::unlocked() {return Err(CmdError::BadInput);}
+    if !crypt::enabled(){
+        let passphrase = conz::prompt_secret("New passphrase: ");
+        if passphrase.is_empty(){
+            conz::println_type("Error: the passphrase can not be empty.", conz::MsgType::Error);
+            return Err(CmdError::BadInput);
+        }
+        if conz::prompt_secret("Again: ") != passphrase{
+            conz::println_type("Error: the passphrases are not the same.", conz::MsgType::Error);
+            return Err(CmdError::BadInput);
+        }
+        if !state.flush_files() {return Err(CmdError::Io);}
+        if !crypt::create_key(&passphrase) {return Err(CmdError::Io);}
+    }
+    if !convert_data(true) {return Err(CmdError::Io);}
+    //the commands typed so far are no longer kept
+    if let Some(path) = save::get_data_dir_path(save::HISTORY_FILE){
+        let _ = std::fs::remove_file(path);
+    }
+    conz::println_type("Success: data encrypted, keep your passphrase safe, without it the data is lost.",
+        conz::MsgType::Highlight);
+    Ok(())
+}
+
+//the files are decrypted first and the key file goes last, the other way around from encrypt data
+pub fn decrypt_data(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    support::warn_unused_arguments(&args);
+    support::warn_unused_inputs(&inputs);
+    if !crypt::enabled(){
+        conz::println_type("Error: data is not encrypted.", conz::MsgType::Error);
+        return Err(CmdError::BadInput);
+    }
+    if !crypt::unlocked() {return Err(CmdError::BadInput);}
+    if !state.flush_files() {return Err(CmdError::Io);}
+    if !convert_data(false) || !crypt::remove_key() {return Err(CmdError::Io);}
+    conz::println_type("Success: data decrypted.", conz::MsgType::Highlight);
+    Ok(())
+}
+
+//everything is flushed first, so the new storage has all of it
+pub fn migrate_storage(state: &mut state::State, args: astr::AstrVec, mut inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+    let mut fields = wizard::FieldVec::new();
+    fields.add("to", wizard::InputType::Text, astr::from_str("Storage(flat or sqlite): "), wizard::PromptType::Once);
+    let wres = fields.execute(&args, &mut inputs);
+    if wres.is_none() {return Err(CmdError::BadInput);}
+    let to = wres.unwrap().get_text().unwrap_or_default().to_string();
+    let to = match storage::Backend::from_str(to.trim()){
+        Option::Some(x) => x,
+        Option::None =>{
+            conz::print_type("Error: no such storage: ", conz::MsgType::Error);
+            conz::println_type(to, conz::MsgType::Highlight);
+            return Err(CmdError::BadInput);
+        }
+    };
+    if storage::Backend::current() == to{
+        conz::print_type("Error: the storage already is ", conz::MsgType::Error);
+        conz::println_type(to.name(), conz::MsgType::Highlight);
+        return Err(CmdError::BadInput);
+    }
+    if to == storage::Backend::Sqlite && sync::enabled(){
+        conz::println_type("Error: the sqlite storage can not be kept in git, put \"git = off\" in the config first.",
+            conz::MsgType::Error);
+        return Err(CmdError::BadInput);
+    }
+    if !state.flush_files() {return Err(CmdError::Io);}
+    if !storage::migrate(to) {return Err(CmdError::Io);}
+    state.reopen();
+    conz::print_type("Success: the storage now is ", conz::MsgType::Highlight);
+    conz::println_type(to.name(), conz::MsgType::Value);
+    Ok(())
+}
 
-pub fn flush_files(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn flush_files(state: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     support::warn_unused_inputs(&inputs);
     if state.is_clean() {
         conz::println_type("All files clean, nothing to do.", conz::MsgType::Highlight);
-        return;
+        return Ok(());
     }
     let res = state.flush_files();
     if res {
         conz::println_type("Success: Flushed all dirty files.", conz::MsgType::Highlight);
+        Ok(())
     }else{
         conz::println_type("Error: Could not flush all dirty files.", conz::MsgType::Error);
+        Err(CmdError::Io)
     }
 }
 
-pub fn test_keys(_: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>){
+pub fn test_keys(_: &mut state::State, args: astr::AstrVec, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
     support::warn_unused_arguments(&args);
     support::warn_unused_inputs(&inputs);
     conz::println_type("Testing keys, press any key to get id, exit program to stop.", conz::MsgType::Normal);
     tbl::test_chars();
+    Ok(())
 }
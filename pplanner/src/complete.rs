@@ -0,0 +1,119 @@
+use super::conz;
+use super::parser;
+
+const FLAGS: [&str; 4] = ["-e", "-i", "-f", "--help"];
+const SUBCOMMANDS: [&str; 3] = ["completions", "daemon", "merge"];
+const SHELLS: [&str; 3] = ["bash", "zsh", "fish"];
+
+//the output flags come from the parser, so a new one is completed too
+fn all_flags() -> Vec<&'static str>{
+    FLAGS.iter().chain(parser::OUTPUT_FLAGS.iter()).chain(SUBCOMMANDS.iter()).copied().collect()
+}
+
+fn describe(flag: &str) -> &'static str{
+    match flag{
+        "--json" => "print data as json lines",
+        "--porcelain" | "--tsv" => "print data as tab separated values",
+        "--csv" => "print data as comma separated values",
+        "--color" => "always use colour",
+        "--no-color" => "never use colour",
+        _ => "",
+    }
+}
+
+//commands contain spaces, every shell gets them single quoted
+fn quote(string: &str) -> String{
+    format!("'{}'", string.replace('\'', "'\\''"))
+}
+
+fn quoted_list(commands: &[String]) -> String{
+    commands.iter().map(|c| quote(c)).collect::<Vec<_>>().join(" ")
+}
+
+pub fn bash(commands: &[String]) -> String{
+    let mut res = String::new();
+    res.push_str("# pplanner completion for bash, source it or put it in your completions dir\n");
+    res.push_str("_pplanner(){\n");
+    res.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    res.push_str("    local prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    res.push_str(&format!("    local commands=( {} )\n", quoted_list(commands)));
+    res.push_str("    case \"$prev\" in\n");
+    res.push_str("        -e)\n");
+    res.push_str("            local IFS=$'\\n'\n");
+    res.push_str("            local word=\"${cur#[\\\"\\']}\"\n");
+    res.push_str("            COMPREPLY=( $(compgen -W \"${commands[*]}\" -- \"$word\") )\n");
+    res.push_str("            [ ${#COMPREPLY[@]} -gt 0 ] && COMPREPLY=( $(printf '%q\\n' \"${COMPREPLY[@]}\") )\n");
+    res.push_str("            return;;\n");
+    res.push_str("        -f)\n");
+    res.push_str("            COMPREPLY=( $(compgen -f -- \"$cur\") )\n");
+    res.push_str("            return;;\n");
+    res.push_str("        -i)\n");
+    res.push_str("            return;;\n");
+    res.push_str("        completions)\n");
+    res.push_str(&format!("            COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )\n", SHELLS.join(" ")));
+    res.push_str("            return;;\n");
+    res.push_str("    esac\n");
+    res.push_str(&format!("    COMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )\n", all_flags().join(" ")));
+    res.push_str("}\n");
+    res.push_str("complete -F _pplanner pplanner\n");
+    res
+}
+
+pub fn zsh(commands: &[String]) -> String{
+    let mut res = String::new();
+    res.push_str("#compdef pplanner\n");
+    res.push_str("# pplanner completion for zsh, put it in your $fpath as _pplanner\n");
+    res.push_str("_pplanner(){\n");
+    res.push_str(&format!("    local -a commands=( {} )\n", quoted_list(commands)));
+    res.push_str("    local state\n");
+    res.push_str("    _arguments \\\n");
+    res.push_str("        '-e[execute a command]:command:->command' \\\n");
+    res.push_str("        '-i[inputs for the command, comma separated]:inputs:' \\\n");
+    res.push_str("        '-f[run a script, - for stdin]:script:_files' \\\n");
+    res.push_str("        '--help[show help]' \\\n");
+    for flag in parser::OUTPUT_FLAGS.iter(){
+        res.push_str(&format!("        '{}[{}]' \\\n", flag, describe(flag)));
+    }
+    res.push_str(&format!("        '1:: :({})' '2:: :({})'\n", SUBCOMMANDS.join(" "), SHELLS.join(" ")));
+    res.push_str("    case $state in\n");
+    res.push_str("        command) compadd -a commands ;;\n");
+    res.push_str("    esac\n");
+    res.push_str("}\n");
+    res.push_str("_pplanner \"$@\"\n");
+    res
+}
+
+pub fn fish(commands: &[String]) -> String{
+    let mut res = String::new();
+    res.push_str("# pplanner completion for fish, put it in ~/.config/fish/completions/pplanner.fish\n");
+    res.push_str("complete -c pplanner -f\n");
+    res.push_str(&format!("complete -c pplanner -s e -x -d 'execute a command' -a \"{}\"\n", quoted_list(commands)));
+    res.push_str("complete -c pplanner -s i -x -d 'inputs for the command, comma separated'\n");
+    res.push_str("complete -c pplanner -s f -r -F -d 'run a script, - for stdin'\n");
+    res.push_str("complete -c pplanner -l help -d 'show help'\n");
+    for flag in parser::OUTPUT_FLAGS.iter(){
+        res.push_str(&format!("complete -c pplanner -l {} -d '{}'\n", flag.trim_start_matches('-'), describe(flag)));
+    }
+    res.push_str("complete -c pplanner -n '__fish_use_subcommand' -a completions -d 'print a shell completion script'\n");
+    res.push_str("complete -c pplanner -n '__fish_use_subcommand' -a daemon -d 'send reminders before points and slices'\n");
+    res.push_str("complete -c pplanner -n '__fish_use_subcommand' -a merge -d 'three way merge of a data file'\n");
+    res.push_str(&format!("complete -c pplanner -n '__fish_seen_subcommand_from completions' -a '{}'\n", SHELLS.join(" ")));
+    res
+}
+
+pub fn print_completions(shell: Option<&str>, commands: &[String]) -> i32{
+    //commands that start with _ are for testing pplanner itself
+    let commands: Vec<String> = commands.iter().filter(|c| !c.starts_with('_')).cloned().collect();
+    let script = match shell{
+        Option::Some("bash") => bash(&commands),
+        Option::Some("zsh") => zsh(&commands),
+        Option::Some("fish") => fish(&commands),
+        _ => {
+            conz::print_type("Error: completions needs a shell, one of: ", conz::MsgType::Error);
+            conz::println_type(SHELLS.join(", "), conz::MsgType::Highlight);
+            return 1;
+        }
+    };
+    print!("{}", script);
+    0
+}
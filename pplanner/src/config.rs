@@ -0,0 +1,85 @@
+use std::io::prelude::*;
+use std::fs::File;
+
+use super::conz;
+use super::save;
+
+pub const CONFIG_FILE: &str = "config";
+
+/*
+Plain text, one "key = value" per line.
+Lines starting with # are comments, keys can repeat.
+*/
+pub struct Config{
+    values: Vec<(String,String)>,
+}
+
+impl Default for Config{
+    fn default() -> Self{
+        Self::new()
+    }
+}
+
+impl Config{
+    pub fn new() -> Self{
+        Config{
+            values: Vec::new(),
+        }
+    }
+
+    pub fn load() -> Self{
+        let mut config = Config::new();
+        let path = save::get_data_dir_path(CONFIG_FILE);
+        if path.is_none() {return config;}
+        let path = path.unwrap();
+        if std::fs::metadata(path.as_path()).is_err() {return config;}
+        let mut string = String::new();
+        let ok = File::open(path.as_path()).and_then(|mut f| f.read_to_string(&mut string));
+        if ok.is_err(){
+            conz::println_type("Error: could not read config file.", conz::MsgType::Error);
+            return config;
+        }
+        config.parse(&string);
+        config
+    }
+
+    pub fn parse(&mut self, string: &str){
+        for (nr, line) in string.lines().enumerate(){
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {continue;}
+            let eq = line.find('=');
+            if eq.is_none(){
+                conz::print_type("Warning: config line without =, ignored: ", conz::MsgType::Error);
+                conz::println_type(nr + 1, conz::MsgType::Highlight);
+                continue;
+            }
+            let eq = eq.unwrap();
+            let key = line[..eq].trim().to_string();
+            let value = line[eq + 1..].trim().to_string();
+            self.values.push((key, value));
+        }
+    }
+
+    //all "prefix name = value" lines as (name, value), in file order
+    pub fn get_prefixed(&self, prefix: &str) -> Vec<(&str, &str)>{
+        self.values.iter()
+            .filter(|(k,_)| k.starts_with(prefix) && k[prefix.len()..].starts_with(char::is_whitespace))
+            .map(|(k,v)| (k[prefix.len()..].trim(), v.as_ref()))
+            .collect()
+    }
+
+    //every value of a key that can repeat, in file order
+    pub fn get_all(&self, key: &str) -> Vec<&str>{
+        self.values.iter()
+            .filter(|(k,_)| k == key)
+            .map(|(_,v)| v.as_ref())
+            .collect()
+    }
+
+    //last one wins, so later lines override earlier ones
+    pub fn get(&self, key: &str) -> Option<&str>{
+        self.values.iter().rev()
+            .find(|(k,_)| k == key)
+            .map(|(_,v)| v.as_ref())
+    }
+}
@@ -1,4 +1,7 @@
 use std::collections::VecDeque;
+use std::io::IsTerminal;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool,AtomicU8,Ordering};
 use term_basics_linux as tbl;
 
 use super::astr;
@@ -17,14 +20,80 @@ pub trait Printable{
     fn print(&self);
 }
 
+//a full, untruncated value for machine readable output
+pub enum Raw{
+    Text(astr::Astr),
+    Num(i64),
+    Bool(bool),
+}
+
 pub trait PrettyPrintable{
     type ArgType;
     fn pretty_print(&self, arg: &Self::ArgType) -> (astr::AstrVec,Vec<MsgType>);
     fn lengths(arg: &Self::ArgType) -> Vec<u16>;
     fn titles(arg: &Self::ArgType) -> Vec<astr::Astr>;
+    fn raw(&self, arg: &Self::ArgType) -> Vec<Raw>;
+    fn raw_keys(arg: &Self::ArgType) -> Vec<&'static str>;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode{
+    Pretty = 0,
+    Json = 1,
+    Tsv = 2,
+    Csv = 3,
+}
+
+impl OutputMode{
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(string: &str) -> Option<OutputMode>{
+        match string{
+            "pretty" => Option::Some(OutputMode::Pretty),
+            "json" => Option::Some(OutputMode::Json),
+            "tsv" | "porcelain" => Option::Some(OutputMode::Tsv),
+            "csv" => Option::Some(OutputMode::Csv),
+            _ => Option::None,
+        }
+    }
+}
+
+static OUTPUT_MODE: AtomicU8 = AtomicU8::new(OutputMode::Pretty as u8);
+static COLOUR: AtomicBool = AtomicBool::new(true);
+
+pub fn set_output_mode(mode: OutputMode){
+    OUTPUT_MODE.store(mode as u8, Ordering::Relaxed);
+}
+
+pub fn output_mode() -> OutputMode{
+    match OUTPUT_MODE.load(Ordering::Relaxed){
+        1 => OutputMode::Json,
+        2 => OutputMode::Tsv,
+        3 => OutputMode::Csv,
+        _ => OutputMode::Pretty,
+    }
+}
+
+//in machine mode stdout only carries data, messages go to stderr
+pub fn is_machine() -> bool{
+    output_mode() != OutputMode::Pretty
+}
+
+pub fn set_colour(on: bool){
+    COLOUR.store(on, Ordering::Relaxed);
+}
+
+//colour escapes are written to stdout, so only use them when it is a terminal
+pub fn setup_colour(setting: Option<&str>){
+    let on = match setting{
+        Option::Some("always") => true,
+        Option::Some("never") => false,
+        _ => std::io::stdout().is_terminal(),
+    };
+    set_colour(on && !is_machine());
 }
 
 fn set_style(msgtype: MsgType){
+    if !COLOUR.load(Ordering::Relaxed) {return;}
     let colorcode = match msgtype {
         MsgType::Normal => tbl::UserColour::Green,
         MsgType::Error => tbl::UserColour::Red,
@@ -43,41 +112,384 @@ fn set_style(msgtype: MsgType){
     tbl::set_style(typecode);
 }
 
+fn out<T: std::fmt::Display>(msg: T){
+    if is_machine() {eprint!("{}", msg);}
+    else {print!("{}", msg);}
+}
+
 pub fn print_type<T: std::fmt::Display>(msg: T, msgtype: MsgType){
     set_style(msgtype);
-    print!("{}", msg);
+    out(msg);
 }
 
 pub fn println_type<T: std::fmt::Display>(msg: T, msgtype: MsgType){
     set_style(msgtype);
-    println!("{}", msg);
+    out(msg);
+    out("\n");
 }
 
 pub fn println_error<T: std::fmt::Display>(pre: T, mid: T, pos: T){
     set_style(MsgType::Error);
-    print!("{}", pre);
+    out(pre);
     set_style(MsgType::Highlight);
-    print!("{}", mid);
+    out(mid);
     set_style(MsgType::Error);
-    println!("{}", pos);
+    out(pos);
+    out("\n");
+}
+
+//data, always to stdout and never styled
+pub fn println_data<T: std::fmt::Display>(msg: T){
+    println!("{}", msg);
+}
+
+//piped stdin, no line editing possible, None at EOF
+fn read_piped_line() -> Option<String>{
+    let mut line = String::new();
+    match std::io::stdin().read_line(&mut line){
+        Ok(0) | Err(_) => Option::None,
+        Ok(_) => Option::Some(line.trim_end_matches(&['\n', '\r'][..]).to_string()),
+    }
 }
 
-pub fn prompt(msg : &str) -> String{
+//None when there is nothing left to read
+pub fn prompt(msg : &str) -> Option<String>{
     print_type(msg, MsgType::Prompt);
     tbl::flush().expect("Error: stdout flush failed.");
     set_style(MsgType::Normal);
-    tbl::input_field()
+    if std::io::stdin().is_terminal(){
+        return Option::Some(tbl::input_field());
+    }
+    read_piped_line()
+}
+
+/*
+Like prompt, but what is typed is not shown.
+Raw bytes are read, so a passphrase can have any character in it.
+ctrl-c gives up, that is an empty answer.
+*/
+pub fn prompt_secret(msg : &str) -> String{
+    if !std::io::stdin().is_terminal(){
+        return prompt(msg).unwrap_or_default();
+    }
+    print_type(msg, MsgType::Prompt);
+    tbl::flush().expect("Error: stdout flush failed.");
+    let mut bytes: Vec<u8> = Vec::new();
+    loop{
+        match tbl::getch(){
+            10 | 13 => break,
+            3 =>{
+                bytes.clear();
+                break;
+            }
+            8 | 127 =>{
+                //a whole utf-8 character, continuation bytes first
+                while let Some(x) = bytes.pop(){
+                    if x & 0xC0 != 0x80 {break;}
+                }
+            }
+            x if x < 32 => {}
+            x => bytes.push(x),
+        }
+    }
+    out("\n");
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+const HISTORY_MAX: usize = 1000;
+
+/*
+Line editor for the command prompt.
+Arrows, home/end, delete and backspace edit the line.
+Up and down walk the history, ctrl-r searches it backwards.
+Tab completes the word under the cursor, twice lists the candidates.
+ctrl-a/ctrl-e jump to the begin/end, ctrl-u/ctrl-k cut before/after the cursor
+and ctrl-w cuts the word before the cursor.
+*/
+pub struct LineEditor{
+    history: Vec<String>,
+    path: Option<std::path::PathBuf>,
+}
+
+enum Key{
+    Char(char),
+    Enter,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Ctrl(u8),
+    Ignore,
+}
+
+fn read_key() -> Key{
+    match tbl::getch(){
+        10 | 13 => Key::Enter,
+        8 | 127 => Key::Backspace,
+        1 => Key::Home,
+        5 => Key::End,
+        27 => {
+            let second = tbl::getch();
+            if second != b'[' && second != b'O' {return Key::Ignore;}
+            match tbl::getch(){
+                b'A' => Key::Up,
+                b'B' => Key::Down,
+                b'C' => Key::Right,
+                b'D' => Key::Left,
+                b'H' => Key::Home,
+                b'F' => Key::End,
+                x if x.is_ascii_digit() => {
+                    let mut last = tbl::getch();
+                    while last.is_ascii_digit() || last == b';' {last = tbl::getch();}
+                    if last != b'~' {return Key::Ignore;}
+                    match x{
+                        b'1' | b'7' => Key::Home,
+                        b'4' | b'8' => Key::End,
+                        b'3' => Key::Delete,
+                        _ => Key::Ignore,
+                    }
+                }
+                _ => Key::Ignore,
+            }
+        }
+        x if x < 32 => Key::Ctrl(x),
+        x if x < 128 => Key::Char(x as char),
+        x => read_utf8(x),
+    }
+}
+
+//the rest of a character of more than one byte, the lead byte says how many follow
+fn read_utf8(lead: u8) -> Key{
+    let follow = match lead{
+        0xC0..=0xDF => 1,
+        0xE0..=0xEF => 2,
+        0xF0..=0xF7 => 3,
+        _ => return Key::Ignore,
+    };
+    let mut bytes = vec![lead];
+    for _ in 0..follow{
+        let x = tbl::getch();
+        if x & 0xC0 != 0x80 {return Key::Ignore;}
+        bytes.push(x);
+    }
+    match std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()){
+        Option::Some(ch) => Key::Char(ch),
+        Option::None => Key::Ignore,
+    }
+}
+
+impl LineEditor{
+    pub fn new(path: Option<std::path::PathBuf>) -> Self{
+        let mut history = Vec::new();
+        if let Some(p) = &path{
+            if let Ok(string) = std::fs::read_to_string(p){
+                history = string.lines().map(|l| l.to_string()).collect();
+            }
+        }
+        let mut editor = LineEditor{
+            history,
+            path,
+        };
+        if editor.history.len() > HISTORY_MAX{
+            let cut = editor.history.len() - HISTORY_MAX;
+            editor.history.drain(..cut);
+            editor.rewrite_file();
+        }
+        editor
+    }
+
+    pub fn last(&self) -> Option<&str>{
+        self.history.last().map(|x| x.as_ref())
+    }
+
+    pub fn add(&mut self, line: &str){
+        if line.trim().is_empty() || line.contains('\n') {return;}
+        if self.last() == Option::Some(line) {return;}
+        self.history.push(line.to_string());
+        if let Some(p) = &self.path{
+            let ok = std::fs::OpenOptions::new().create(true).append(true).open(p)
+                .and_then(|mut f| writeln!(f, "{}", line));
+            if ok.is_err(){
+                println_type("Error: could not write history file.", MsgType::Error);
+            }
+        }
+    }
+
+    fn rewrite_file(&self){
+        if let Some(p) = &self.path{
+            let mut string = self.history.join("\n");
+            string.push('\n');
+            if std::fs::write(p, string).is_err(){
+                println_type("Error: could not write history file.", MsgType::Error);
+            }
+        }
+    }
+
+    fn redraw(msg: &str, line: &[char], pos: usize){
+        out("\r");
+        print_type(msg, MsgType::Prompt);
+        set_style(MsgType::Normal);
+        out(line.iter().collect::<String>());
+        out("\x1B[K");
+        if pos < line.len(){
+            out(format!("\x1B[{}D", line.len() - pos));
+        }
+        tbl::flush().expect("Error: stdout flush failed.");
+    }
+
+    //newest first, starting below index start
+    fn search(&self, query: &str, start: usize) -> Option<usize>{
+        if query.is_empty() {return Option::None;}
+        (0..start).rev().find(|i| self.history[*i].contains(query))
+    }
+
+    fn reverse_search(&self, line: &mut Vec<char>) -> bool{
+        let mut query = String::new();
+        let mut found: Option<usize> = Option::None;
+        loop{
+            let shown = found.map(|i| self.history[i].as_ref()).unwrap_or("");
+            Self::redraw(&format!("(reverse-i-search)`{}': ", query), &shown.chars().collect::<Vec<char>>(), 0);
+            match read_key(){
+                Key::Char(ch) => {
+                    query.push(ch);
+                    found = self.search(&query, found.map(|i| i + 1).unwrap_or(self.history.len()));
+                }
+                Key::Backspace => {
+                    query.pop();
+                    found = self.search(&query, self.history.len());
+                }
+                Key::Ctrl(18) => {
+                    let start = found.unwrap_or(self.history.len());
+                    if let Some(x) = self.search(&query, start) {found = Option::Some(x);}
+                }
+                Key::Ctrl(7) | Key::Ctrl(3) => return false,
+                Key::Enter => {
+                    if let Some(i) = found {*line = self.history[i].chars().collect();}
+                    return true;
+                }
+                _ => {
+                    if let Some(i) = found {*line = self.history[i].chars().collect();}
+                    return false;
+                }
+            }
+        }
+    }
+
+    //in chars, like the cursor
+    fn common_prefix(cands: &[String]) -> usize{
+        let mut len = cands[0].chars().count();
+        for c in cands.iter().skip(1){
+            let same = cands[0].chars().zip(c.chars()).take_while(|(a,b)| a == b).count();
+            len = std::cmp::min(len, same);
+        }
+        len
+    }
+
+    //None when there is nothing left to read
+    pub fn read_line(&mut self, msg: &str, complete: &dyn Fn(&str) -> (usize, Vec<String>)) -> Option<String>{
+        if !std::io::stdin().is_terminal(){
+            return prompt(msg);
+        }
+        let mut line: Vec<char> = Vec::new();
+        let mut pos = 0;
+        let mut index = self.history.len();
+        let mut stash: Vec<char> = Vec::new();
+        loop{
+            Self::redraw(msg, &line, pos);
+            match read_key(){
+                Key::Enter => break,
+                Key::Char(ch) => {
+                    line.insert(pos, ch);
+                    pos += 1;
+                }
+                Key::Backspace => {
+                    if pos == 0 {continue;}
+                    pos -= 1;
+                    line.remove(pos);
+                }
+                //ctrl-d on an empty line is the end of input, like in a shell
+                Key::Ctrl(4) if line.is_empty() => {
+                    out("\n");
+                    return Option::None;
+                }
+                Key::Delete | Key::Ctrl(4) if pos < line.len() => {
+                    line.remove(pos);
+                }
+                Key::Left => pos = pos.saturating_sub(1),
+                Key::Right => pos = std::cmp::min(pos + 1, line.len()),
+                Key::Home => pos = 0,
+                Key::End => pos = line.len(),
+                Key::Up => {
+                    if index == 0 {continue;}
+                    if index == self.history.len() {stash = line.clone();}
+                    index -= 1;
+                    line = self.history[index].chars().collect();
+                    pos = line.len();
+                }
+                Key::Down => {
+                    if index >= self.history.len() {continue;}
+                    index += 1;
+                    line = if index == self.history.len() {stash.clone()}
+                    else {self.history[index].chars().collect()};
+                    pos = line.len();
+                }
+                Key::Ctrl(11) => line.truncate(pos),
+                Key::Ctrl(21) => {
+                    line.drain(..pos);
+                    pos = 0;
+                }
+                Key::Ctrl(23) => {
+                    let mut start = pos;
+                    while start > 0 && line[start - 1] == ' ' {start -= 1;}
+                    while start > 0 && line[start - 1] != ' ' {start -= 1;}
+                    line.drain(start..pos);
+                    pos = start;
+                }
+                Key::Ctrl(9) => {
+                    let before: String = line[..pos].iter().collect();
+                    let (start, cands) = complete(&before);
+                    if cands.is_empty() {continue;}
+                    //start is a byte offset in before, the cursor counts chars
+                    let typed = before.get(start..).map(|x| x.chars().count()).unwrap_or(0);
+                    let common = Self::common_prefix(&cands);
+                    if common <= typed{
+                        let names: Vec<&str> = cands.iter().map(|c| c.trim_end()).collect();
+                        out("\n");
+                        println_type(names.join("  "), MsgType::Normal);
+                        continue;
+                    }
+                    for ch in cands[0].chars().take(common).skip(typed){
+                        line.insert(pos, ch);
+                        pos += 1;
+                    }
+                }
+                Key::Ctrl(18) => {
+                    let run = self.reverse_search(&mut line);
+                    pos = line.len();
+                    if run {break;}
+                }
+                _ => {}
+            }
+        }
+        Self::redraw(msg, &line, line.len());
+        out("\n");
+        Option::Some(line.into_iter().collect())
+    }
 }
 
 pub fn read_bool(msg: &str, inputs: &mut Option<VecDeque<astr::Astr>>) -> bool{
     let line;
-    if inputs.is_none(){line = prompt(&msg);}
+    if inputs.is_none(){line = prompt(msg).unwrap_or_default();}
     else{
         let res = inputs.as_mut().unwrap().pop_front();
         if let Some(resv) = res {
             line = resv.to_string();
         } else{
-            line = prompt(&msg);
+            line = prompt(msg).unwrap_or_default();
         }
     }
     tbl::string_to_bool(&line)
@@ -0,0 +1,232 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, KeyInit, AeadCore};
+use chacha20poly1305::aead::{Aead, Payload, OsRng};
+use chacha20poly1305::aead::rand_core::RngCore;
+use argon2::Argon2;
+
+use super::conz;
+use super::save;
+
+//an encrypted file starts with this, files without it are only read while the data is not encrypted
+const MAGIC: &[u8] = b"PPLCRYPT";
+const KEY_MAGIC: &[u8] = b"PPLKEY01";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+//encrypted into the key file, to know a passphrase is right before reading anything with it
+const CHECK: &[u8] = b"pplanner";
+const PASSPHRASE_ENV: &str = "PPLANNER_PASSPHRASE";
+const TRIES: usize = 3;
+
+//the key of this session, asked for once
+static KEY: Mutex<Option<Key>> = Mutex::new(Option::None);
+//after the tries are used up, we do not ask again for every file
+static GAVE_UP: AtomicBool = AtomicBool::new(false);
+static CONVERTING: AtomicBool = AtomicBool::new(false);
+
+fn key_path() -> Option<std::path::PathBuf>{
+    save::get_data_dir_path(save::CRYPT_FILE)
+}
+
+//the data is encrypted when the key file is there
+pub fn enabled() -> bool{
+    key_path().map(|p| p.exists()).unwrap_or(false)
+}
+
+fn derive(passphrase: &str, salt: &[u8]) -> Option<Key>{
+    let mut key = Key::default();
+    match Argon2::default().hash_password_into(passphrase.as_bytes(), salt, &mut key){
+        Ok(_) => Option::Some(key),
+        Err(e) =>{
+            conz::print_type("Error: could not derive the key: ", conz::MsgType::Error);
+            conz::println_type(e, conz::MsgType::Highlight);
+            Option::None
+        }
+    }
+}
+
+fn encrypt_with(key: &Key, name: &str, plain: &[u8]) -> Option<save::Buffer>{
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let sealed = ChaCha20Poly1305::new(key).encrypt(&nonce, Payload{msg: plain, aad: name.as_bytes()}).ok()?;
+    let mut res = MAGIC.to_vec();
+    res.extend_from_slice(&nonce);
+    res.extend(sealed);
+    Option::Some(res)
+}
+
+fn decrypt_with(key: &Key, name: &str, raw: &[u8]) -> Option<save::Buffer>{
+    if raw.len() < MAGIC.len() + NONCE_LEN || !raw.starts_with(MAGIC) {return Option::None;}
+    let (nonce, sealed) = raw[MAGIC.len()..].split_at(NONCE_LEN);
+    ChaCha20Poly1305::new(key).decrypt(Nonce::from_slice(nonce), Payload{msg: sealed, aad: name.as_bytes()}).ok()
+}
+
+/*
+Reads the passphrase from PPLANNER_PASSPHRASE, for scripts and the daemon, or asks for it.
+The key file has the salt and the check, so a wrong passphrase is noticed right away.
+*/
+fn unlock() -> Option<Key>{
+    let path = key_path()?;
+    let raw = match std::fs::read(&path){
+        Ok(x) if x.len() > KEY_MAGIC.len() + SALT_LEN && x.starts_with(KEY_MAGIC) => x,
+        _ =>{
+            conz::println_error("", "Error: the key file can not be read: ", &path.to_string_lossy());
+            return Option::None;
+        }
+    };
+    let (salt, check) = raw[KEY_MAGIC.len()..].split_at(SALT_LEN);
+    let from_env = std::env::var(PASSPHRASE_ENV).ok();
+    let tries = if from_env.is_some() {1} else {TRIES};
+    for _ in 0..tries{
+        let passphrase = match &from_env{
+            Option::Some(x) => x.clone(),
+            Option::None => conz::prompt_secret("Passphrase: "),
+        };
+        if passphrase.is_empty() {break;}
+        let key = derive(&passphrase, salt)?;
+        if decrypt_with(&key, save::CRYPT_FILE, check).as_deref() == Option::Some(CHECK){
+            return Option::Some(key);
+        }
+        conz::println_type("Error: wrong passphrase.", conz::MsgType::Error);
+    }
+    Option::None
+}
+
+fn key() -> Option<Key>{
+    let mut guard = KEY.lock().ok()?;
+    if guard.is_none() && !GAVE_UP.load(Ordering::Relaxed){
+        *guard = unlock();
+        GAVE_UP.store(guard.is_none(), Ordering::Relaxed);
+    }
+    *guard
+}
+
+//asks for the key now, so it is not asked halfway through something
+pub fn unlocked() -> bool{
+    key().is_some()
+}
+
+pub fn encrypt(name: &str, plain: &[u8]) -> Option<save::Buffer>{
+    let key = key()?;
+    let res = encrypt_with(&key, name, plain);
+    if res.is_none(){
+        conz::print_type("Error: could not encrypt: ", conz::MsgType::Error);
+        conz::println_type(name, conz::MsgType::Highlight);
+    }
+    res
+}
+
+//encrypted when the data is, name is the name of the data file and is checked on opening
+pub fn seal(name: &str, plain: &[u8]) -> Option<save::Buffer>{
+    if enabled() {encrypt(name, plain)} else {Option::Some(plain.to_vec())}
+}
+
+//true when raw is encrypted, it can only be read whole then
+pub fn is_sealed(raw: &[u8]) -> bool{
+    raw.starts_with(MAGIC)
+}
+
+fn decrypt(name: &str, raw: &[u8]) -> Option<save::Buffer>{
+    let res = decrypt_with(&key()?, name, raw);
+    if res.is_none(){
+        conz::print_type("Error: could not decrypt, the file is damaged or not ours: ", conz::MsgType::Error);
+        conz::println_type(name, conz::MsgType::Highlight);
+    }
+    res
+}
+
+/*
+While the data is encrypted a plain file is not ours to trust, someone could have put it there.
+Empty files are new files and hold nothing.
+*/
+pub fn open(name: &str, raw: &[u8]) -> Option<save::Buffer>{
+    if is_sealed(raw) {return decrypt(name, raw);}
+    if enabled() && !raw.is_empty() && !CONVERTING.load(Ordering::Relaxed){
+        conz::print_type("Error: the data is encrypted but this file is not, run encrypt data to finish: ", conz::MsgType::Error);
+        conz::println_type(name, conz::MsgType::Highlight);
+        return Option::None;
+    }
+    Option::Some(raw.to_vec())
+}
+
+//encrypt and decrypt data go through the files one by one, meanwhile plain files are read as they are
+pub fn set_converting(on: bool){
+    CONVERTING.store(on, Ordering::Relaxed);
+}
+
+//a new salt and key, the key file is written before any data is encrypted with it
+pub fn create_key(passphrase: &str) -> bool{
+    let path = match key_path(){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let key = match derive(passphrase, &salt){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    let check = match encrypt_with(&key, save::CRYPT_FILE, CHECK){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    let mut raw = KEY_MAGIC.to_vec();
+    raw.extend_from_slice(&salt);
+    raw.extend(check);
+    if !save::replace_file(&path, &raw) {return false;}
+    if let Ok(mut guard) = KEY.lock(){
+        *guard = Option::Some(key);
+    }
+    true
+}
+
+//the key file goes last, so until then the data still counts as encrypted
+pub fn remove_key() -> bool{
+    let path = match key_path(){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    if std::fs::remove_file(&path).is_err(){
+        conz::println_error("", "Error: could not remove the key file: ", &path.to_string_lossy());
+        return false;
+    }
+    if let Ok(mut guard) = KEY.lock(){
+        *guard = Option::None;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn key(byte: u8) -> Key{
+        *Key::from_slice(&[byte; 32])
+    }
+
+    #[test]
+    fn sealed_opens_with_its_name(){
+        let sealed = encrypt_with(&key(1), "todos", b"milk").unwrap();
+        assert!(is_sealed(&sealed));
+        assert_eq!(decrypt_with(&key(1), "todos", &sealed), Option::Some(b"milk".to_vec()));
+    }
+
+    #[test]
+    fn another_name_or_key_does_not_open(){
+        let sealed = encrypt_with(&key(1), "todos", b"milk").unwrap();
+        assert_eq!(decrypt_with(&key(1), "plans", &sealed), Option::None);
+        assert_eq!(decrypt_with(&key(2), "todos", &sealed), Option::None);
+    }
+
+    #[test]
+    fn a_changed_byte_does_not_open(){
+        let sealed = encrypt_with(&key(1), "todos", b"milk").unwrap();
+        for i in MAGIC.len()..sealed.len(){
+            let mut bad = sealed.clone();
+            bad[i] ^= 1;
+            assert_eq!(decrypt_with(&key(1), "todos", &bad), Option::None, "byte {}", i);
+        }
+        assert_eq!(decrypt_with(&key(1), "todos", &sealed[..sealed.len() - 1]), Option::None);
+        assert_eq!(decrypt_with(&key(1), "todos", MAGIC), Option::None);
+    }
+}
@@ -0,0 +1,187 @@
+use std::process::Command;
+use std::time::Duration;
+
+use super::conz;
+use super::data;
+use super::astr;
+use super::astr::{AStr,ToAstr};
+use super::save;
+use super::storage::Storage;
+use super::state;
+use super::config;
+use super::error;
+
+const DEFAULT_INTERVAL: u64 = 60;
+const DEFAULT_LEADS: [(&str, &str); 2] = [("deadline", "1d, 1h"), ("slice", "10m")];
+
+enum Notifier{
+    Stdout,
+    NotifySend,
+    Hook(String),
+}
+
+impl Notifier{
+    fn from_config(config: &config::Config) -> Self{
+        match config.get("notify"){
+            Option::None | Option::Some("") | Option::Some("stdout") => Notifier::Stdout,
+            Option::Some("notify-send") => Notifier::NotifySend,
+            Option::Some(x) => Notifier::Hook(x.to_string()),
+        }
+    }
+
+    fn describe(&self) -> String{
+        match self{
+            Notifier::Stdout => String::from("stdout"),
+            Notifier::NotifySend => String::from("notify-send"),
+            Notifier::Hook(x) => format!("command: {}", x),
+        }
+    }
+
+    //the hook gets the summary as $1 and the body as $2, a failure is reported and not retried
+    fn send(&self, summary: &str, body: &str){
+        let status = match self{
+            Notifier::Stdout =>{
+                conz::println_data(format!("{}: {}", summary, body));
+                return;
+            }
+            Notifier::NotifySend => Command::new("notify-send").arg(summary).arg(body).status(),
+            Notifier::Hook(x) => Command::new("sh").arg("-c").arg(x).arg("pplanner").arg(summary).arg(body).status(),
+        };
+        match status{
+            Ok(x) if x.success() => {}
+            Ok(x) =>{
+                conz::print_type("Error: notify failed with: ", conz::MsgType::Error);
+                conz::println_type(x, conz::MsgType::Highlight);
+            }
+            Err(e) =>{
+                conz::print_type("Error: could not run notify: ", conz::MsgType::Error);
+                conz::println_type(e, conz::MsgType::Highlight);
+            }
+        }
+    }
+}
+
+/*
+Lead times in minutes, from "remind name = 1d, 1h" lines in the config.
+Names are point types and slice.
+An empty value turns the defaults off.
+*/
+fn leads(config: &config::Config, name: &str) -> Vec<u32>{
+    let configured = config.get_prefixed("remind").into_iter().rev()
+        .find(|(n,_)| n.eq_ignore_ascii_case(name))
+        .map(|(_,v)| v);
+    let value = match configured{
+        Option::Some(x) => x,
+        Option::None => match DEFAULT_LEADS.iter().find(|(n,_)| n.eq_ignore_ascii_case(name)){
+            Option::Some((_,v)) => v,
+            Option::None => return Vec::new(),
+        },
+    };
+    let mut res = Vec::new();
+    for part in value.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()){
+        match data::parse_duration(&astr::from_str(part)){
+            Option::Some(x) => res.push(x),
+            Option::None =>{
+                conz::print_type("Warning: config: could not parse lead time, ignored: ", conz::MsgType::Error);
+                conz::println_type(part, conz::MsgType::Highlight);
+            }
+        }
+    }
+    res
+}
+
+struct Upcoming{
+    kind: astr::Astr,
+    title: astr::Astr,
+    at: data::DT,
+    leads: Vec<u32>,
+}
+
+fn upcoming(state: &mut state::State, config: &config::Config) -> Vec<Upcoming>{
+    let mut res = Vec::new();
+    for point in state.points.get_items(){
+        let kind = point.ptype.to_astr();
+        let leads = leads(config, &kind.to_string());
+        if leads.is_empty() {continue;}
+        res.push(Upcoming{kind, title: point.title.clone(), at: point.dt.clone(), leads});
+    }
+    let slice_leads = leads(config, "slice");
+    if !slice_leads.is_empty(){
+        for slice in state.slices.get_items(){
+            res.push(Upcoming{kind: astr::from_str("Slice"), title: slice.title.clone(), at: slice.start.clone(),
+                leads: slice_leads.clone()});
+        }
+    }
+    res
+}
+
+/*
+All leads that are due are recorded, but only the closest one is sent.
+So after a while offline you get one reminder per item, not a burst.
+Reminders for things that already started are dropped.
+*/
+fn check(state: &mut state::State, config: &config::Config, notifier: &Notifier,
+    fired: &mut save::BufferFile<data::Reminder>) -> bool{
+    let now = data::DT::new();
+    let mut new = Vec::new();
+    for item in upcoming(state, config){
+        if item.at <= now {continue;}
+        let mut due: Vec<u32> = item.leads.iter().cloned()
+            .filter(|lead| item.at.dt - chrono::Duration::minutes(i64::from(*lead)) <= now.dt)
+            .filter(|lead| !fired.get_items().contains(&data::Reminder{title: item.title.clone(), at: item.at.clone(), lead: *lead}))
+            .collect();
+        if due.is_empty() {continue;}
+        due.sort();
+        let summary = format!("{}: {}", item.kind.disp(), item.title.disp());
+        let body = format!("{}, at {}", now.diff(&item.at).string_significant(false), item.at.str_datetime().disp());
+        notifier.send(&summary, &body);
+        for lead in due{
+            new.push(data::Reminder{title: item.title.clone(), at: item.at.clone(), lead});
+        }
+    }
+    let old: Vec<usize> = fired.get_items().iter().enumerate()
+        .filter(|(_,r)| r.at <= now)
+        .map(|(i,_)| i)
+        .collect();
+    if new.is_empty() && old.is_empty() {return true;}
+    if !old.is_empty() && !fired.remove_indices(old) {return false;}
+    for reminder in new{
+        if !fired.add_item(reminder) {return false;}
+    }
+    fired.write()
+}
+
+pub fn run(mut state: state::State, config: &config::Config, args: &[String]) -> i32{
+    let once = args.iter().any(|a| a == "--once");
+    let interval = match config.get("daemon interval").map(|x| x.parse::<u64>()){
+        Option::None => DEFAULT_INTERVAL,
+        Option::Some(Ok(x)) if x > 0 => x,
+        Option::Some(_) =>{
+            conz::println_type("Warning: config: daemon interval should be a number of seconds.", conz::MsgType::Error);
+            DEFAULT_INTERVAL
+        }
+    };
+    let path = save::get_data_dir_path(save::REMINDER_DIR);
+    if path.is_none(){
+        conz::println_type("Error: could not find the reminders file.", conz::MsgType::Error);
+        return error::CmdError::Io.exit_code();
+    }
+    let mut fired = save::BufferFile::new(path.unwrap());
+    let notifier = Notifier::from_config(config);
+    if !once{
+        conz::print_type("Daemon: checking every ", conz::MsgType::Normal);
+        conz::print_type(format!("{} secs", interval), conz::MsgType::Value);
+        conz::print_type(", reminders go to ", conz::MsgType::Normal);
+        conz::println_type(notifier.describe(), conz::MsgType::Highlight);
+    }
+    loop{
+        //pick up changes from other pplanner processes
+        state.refresh();
+        if !check(&mut state, config, &notifier, &mut fired){
+            conz::println_type("Error: could not write the reminders file.", conz::MsgType::Error);
+            if once {return error::CmdError::Io.exit_code();}
+        }
+        if once {return 0;}
+        std::thread::sleep(Duration::from_secs(interval));
+    }
+}
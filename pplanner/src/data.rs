@@ -4,7 +4,6 @@ use num_traits::ToPrimitive;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use std::convert::TryInto;
-use term_basics_linux as tbl;
 
 use super::conz;
 use super::astr;
@@ -13,8 +12,12 @@ use super::astr::{Astr,AStr,ToAstr};
 use super::misc::{UnwrapDefault};
 use super::support;
 use super::wizard;
+use super::merge;
+use super::storage;
 
+#[allow(clippy::upper_case_acronyms)]
 type DMY = (u32,u32,u32);
+#[allow(clippy::upper_case_acronyms)]
 type HMS = (u32,u32,u32);
 
 pub fn day_name(i: u8) -> astr::Astr{
@@ -98,6 +101,7 @@ pub fn month_short_to_uint(mon: &astr::Astr) -> Option<u32>{
     }
 }
 
+#[allow(dead_code)]
 pub struct Span {
     pub total_hours: u64,
     pub total_mins: u64,
@@ -114,6 +118,25 @@ impl Span {
     pub const SECS_HOUR: u64 = 3600;
     pub const SECS_DAY: u64 = 86400;
 
+    pub fn from_secs(secs_all: u64, neg: bool) -> Span{
+        let days = secs_all / Span::SECS_DAY;
+        let mut left = secs_all - (days * Span::SECS_DAY);
+        let hours = left / Span::SECS_HOUR;
+        left -= hours * Span::SECS_HOUR;
+        let mins = left / Span::SECS_MIN;
+        left -= mins * Span::SECS_MIN;
+        Span {
+            total_hours: secs_all / Span::SECS_HOUR,
+            total_mins: secs_all / Span::SECS_MIN,
+            total_secs: secs_all,
+            days,
+            hours,
+            mins,
+            secs: left,
+            neg,
+        }
+    }
+
     pub fn string_significant(&self, as_dur: bool) -> String{
         let prefix = if as_dur { "" }
         else if self.neg {"past "}
@@ -139,6 +162,11 @@ impl Span {
         format!("{}{} secs", prefix, self.total_secs)
     }
 
+    //hours and minutes like 12:05, for totals that can be long
+    pub fn string_hm(&self) -> String{
+        format!("{}:{:02}", self.total_hours, self.mins)
+    }
+
     pub fn print_as_duration(&self){
         conz::print_type("Significant: ", conz::MsgType::Normal);
         conz::println_type(self.string_significant(true), conz::MsgType::Value);
@@ -190,9 +218,9 @@ impl DT {
     }
 
     pub fn make_datetime(dmy: DMY, hms: HMS) -> Option<Self>{
-        let datetime = Local.ymd_opt(dmy.2 as i32, dmy.1, dmy.0).and_hms_opt(hms.0, hms.1, hms.2);
-        if datetime == chrono::LocalResult::None {return Option::None;}
-        Option::Some(DT{ dt: datetime.unwrap(), })
+        let naive = NaiveDate::from_ymd_opt(dmy.2 as i32, dmy.1, dmy.0)?.and_hms_opt(hms.0, hms.1, hms.2)?;
+        //a wall clock time that happens twice when the clocks go back is the first one
+        Option::Some(DT{ dt: Local.from_local_datetime(&naive).earliest()?, })
     }
 
     pub fn str_datetime(&self) -> astr::Astr{
@@ -207,6 +235,10 @@ impl DT {
         format!("{}", self.dt.format("%H:%M:%S")).to_astr()
     }
 
+    pub fn str_iso(&self) -> astr::Astr{
+        format!("{}", self.dt.format("%Y-%m-%dT%H:%M:%S%:z")).to_astr()
+    }
+
     pub fn weeknr(&self) -> u8{
         let datetime = Self::make_datetime((1, 1, self.dt.year() as u32), (0, 0, 0));//first day this year
         if datetime.is_none() { return 0; }
@@ -216,6 +248,26 @@ impl DT {
         (weeks + 1) as u8 //year starts with week 1 not week 0
     }
 
+    //ISO 8601 week like 2026-W42, the year is the one the week belongs to
+    pub fn str_iso_week(&self) -> astr::Astr{
+        format!("{}", self.dt.format("%G-W%V")).to_astr()
+    }
+
+    pub fn str_iso_date(&self) -> astr::Astr{
+        format!("{}", self.dt.format("%Y-%m-%d")).to_astr()
+    }
+
+    //days since the start of the common era, to key things by date
+    pub fn day_number(&self) -> u32{
+        self.dt.num_days_from_ce() as u32
+    }
+
+    //midnight at the start of the next day
+    pub fn next_midnight(&self) -> Option<DT>{
+        let dt = self.dt.date().succ().and_hms_opt(0, 0, 0)?;
+        Option::Some(DT{dt})
+    }
+
     pub fn str_weeknr(&self) -> astr::Astr{
         format!("{}", self.weeknr()).to_astr()
     }
@@ -249,28 +301,6 @@ impl DT {
     }
 
     pub fn diff(&self, other: &DT) -> Span{
-        fn _diff(secs_all: u64, neg: bool) -> Span{
-            let days = secs_all / Span::SECS_DAY;
-            let mut left = secs_all - (days * Span::SECS_DAY);
-            let hours = left / Span::SECS_HOUR;
-            left -= hours * Span::SECS_HOUR;
-            let mins = left / Span::SECS_MIN;
-            left -= mins * Span::SECS_MIN;
-
-            let total_hours = secs_all / Span::SECS_HOUR;
-            let total_mins = secs_all / Span::SECS_MIN;
-
-            Span {
-                total_hours,
-                total_mins,
-                total_secs: secs_all,
-                days,
-                hours,
-                mins,
-                secs: left,
-                neg,
-            }
-        }
         fn get_secs(me: &DT, other: &DT) -> u64{
             let d = other.dt - me.dt;
             let stdd = d.to_std();
@@ -282,7 +312,7 @@ impl DT {
         let mut secs = get_secs(self, other);
         let neg = if secs == 0 { secs = get_secs(other, self); true }
         else { false };
-        _diff(secs, neg)
+        Span::from_secs(secs, neg)
     }
 }
 
@@ -339,11 +369,10 @@ pub fn parse_dmy(string: &astr::Astr) -> Option<DMY>{
         let now = DT::new().dt;
         return Option::Some((now.day(), now.month(), now.year().try_into().unwrap()));
     }
-    // if &string.to_string() == "tomorrow"{
-    //     let now = DT::new().dt;
-    //
-    //     return Option::Some((tom.day(), tom.month(), tom.year().try_into().unwrap()));
-    // }
+    if &string.to_string() == "tomorrow"{
+        let tom = DT::new().dt + chrono::Duration::days(1);
+        return Option::Some((tom.day(), tom.month(), tom.year().try_into().unwrap()));
+    }
     let splitted = string.split_str(&astr::from_str(":;-_.,/\\"));
     if splitted.len() != 3 {return Option::None;}
     let mut triplet: Vec<Option<u32>> = splitted.iter().map(astr::to_u32_checked).collect();
@@ -378,19 +407,64 @@ pub fn parse_hms(string: &astr::Astr) -> Option<DMY>{
         return Option::Some((0,0,1));
     }
     let splitted = string.split_str(&astr::from_str(":;-_.,/\\"));
-    if splitted.len() != 3 {return Option::None;}
-    let triplet: Vec<Option<u32>> = splitted.iter().map(astr::to_u32_checked).collect();
+    //seconds can be left out: 9:00
+    if splitted.len() != 3 && splitted.len() != 2 {return Option::None;}
+    let mut triplet: Vec<Option<u32>> = splitted.iter().map(astr::to_u32_checked).collect();
+    if triplet.len() == 2 {triplet.push(Option::Some(0));}
     triplet[0]?;
     triplet[1]?;
     triplet[2]?;
     Option::Some((triplet[0].unwrap(),triplet[1].unwrap(),triplet[2].unwrap()))
 }
 
-#[derive(FromPrimitive,ToPrimitive,Eq,Clone)]
+//minutes from 90, 1:30, 1h30m, 2h, 45m or 1d
+pub fn parse_duration(string: &astr::Astr) -> Option<u32>{
+    let string = string.to_lower().to_string();
+    if let Ok(x) = string.parse::<u32>(){
+        return Option::Some(x);
+    }
+    if let Some(i) = string.find(':'){
+        let hours = string[..i].parse::<u32>().ok()?;
+        let mins = string[i + 1..].parse::<u32>().ok()?;
+        return hours.checked_mul(60)?.checked_add(mins);
+    }
+    let mut total: u32 = 0;
+    let mut number = String::new();
+    for ch in string.chars(){
+        match ch{
+            '0'..='9' => number.push(ch),
+            'd' | 'h' | 'm' => {
+                let x = number.parse::<u32>().ok()?;
+                let x = match ch{
+                    'd' => x.checked_mul(24 * 60)?,
+                    'h' => x.checked_mul(60)?,
+                    _ => x,
+                };
+                total = total.checked_add(x)?;
+                number.clear();
+            }
+            _ => return Option::None,
+        }
+    }
+    if !number.is_empty() {return Option::None;}
+    Option::Some(total)
+}
+
+//estimates in tables, empty when there is none
+pub fn str_minutes(mins: u32) -> astr::Astr{
+    if mins == 0 {return astr::Astr::new();}
+    Span::from_secs(u64::from(mins) * Span::SECS_MIN, false).string_significant(true).to_astr()
+}
+
+//the high bit of a type or done byte says an estimate in minutes follows
+const ESTIMATE_FLAG: u8 = 0x80;
+
+#[derive(FromPrimitive,ToPrimitive,Eq,Clone,Default)]
 pub enum PointType{
     None = 0,
     Deadline = 1,
     Event = 2,
+    #[default]
     DefaultValue = 255,
 }
 
@@ -430,12 +504,6 @@ impl astr::ToAstr for PointType{
     }
 }
 
-impl Default for PointType{
-    fn default() -> Self{
-        PointType::DefaultValue
-    }
-}
-
 #[derive(Eq,Clone)]
 pub struct Point{
     pub dt: DT,
@@ -462,7 +530,7 @@ impl save::Bufferable for Point{
             primtypev.into_buffer(vec);
         }else{
             conz::println_type("Error: Could not convert PointType to u8.", conz::MsgType::Error);
-            (0 as u8).into_buffer(vec);
+            0_u8.into_buffer(vec);
         }
     }
 
@@ -509,7 +577,7 @@ impl conz::Printable for Point{
         conz::println_type(self.ptype.to_astr().disp(), conz::MsgType::Highlight);
         conz::print_type("time date: ", conz::MsgType::Normal);
         conz::print_type(self.dt.str_datetime().disp(), conz::MsgType::Value);
-        tbl::print(" ");
+        conz::print_type(" ", conz::MsgType::Normal);
         conz::println_type(self.dt.str_dayname().disp(), conz::MsgType::Value);
     }
 }
@@ -543,9 +611,37 @@ impl conz::PrettyPrintable for Point{
             astr::from_str("Time Date:"),
             astr::from_str("Type:"),]
     }
+
+    fn raw(&self, _: &Self::ArgType) -> Vec<conz::Raw>{
+        vec![conz::Raw::Text(self.title.clone()),
+            conz::Raw::Text(self.dt.str_iso()),
+            conz::Raw::Text(self.ptype.to_astr()),]
+    }
+
+    fn raw_keys(_: &Self::ArgType) -> Vec<&'static str>{
+        vec!["title", "time", "type"]
+    }
+}
+
+impl merge::Mergeable for Point{
+    fn identity(&self) -> astr::Astr{
+        //titles like "Exam" come back, the moment tells them apart
+        self.title.concat(astr::from_str(" ")).concat(self.dt.str_datetime())
+    }
+}
+
+impl storage::Indexed for Point{
+    fn date(&self) -> Option<i64>{
+        Option::Some(self.dt.dt.timestamp())
+    }
+
+    fn kind(&self) -> Option<u8>{
+        ToPrimitive::to_u8(&self.ptype)
+    }
 }
 
 impl wizard::Wizardable for Point{
+    #[allow(clippy::never_loop)]
     fn extract(wres: &mut wizard::WizardRes) -> Option<Self>{
         loop{
             let dt_res = wres.get_dt();
@@ -564,13 +660,13 @@ impl wizard::Wizardable for Point{
     fn get_fields(partial: bool) -> wizard::FieldVec{
         let mut fields = wizard::FieldVec::new();
         if partial{
-            fields.add(wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Partial);
-            fields.add(wizard::InputType::Text, astr::from_str("Type: "), wizard::PromptType::Partial);
-            fields.add(wizard::InputType::DateTime, astr::from_str("Time date: "), wizard::PromptType::Partial);
+            fields.add("title", wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Partial);
+            fields.add("type", wizard::InputType::Text, astr::from_str("Type: "), wizard::PromptType::Partial);
+            fields.add("at", wizard::InputType::DateTime, astr::from_str("Time date: "), wizard::PromptType::Partial);
         }else{
-            fields.add(wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Once);
-            fields.add(wizard::InputType::Text, astr::from_str("Type: "), wizard::PromptType::Once);
-            fields.add(wizard::InputType::DateTime, astr::from_str("Time date: "), wizard::PromptType::Reprompt);
+            fields.add("title", wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Once);
+            fields.add("type", wizard::InputType::Text, astr::from_str("Type: "), wizard::PromptType::Once);
+            fields.add("at", wizard::InputType::DateTime, astr::from_str("Time date: "), wizard::PromptType::Reprompt);
         }
         fields
     }
@@ -611,12 +707,13 @@ impl wizard::Wizardable for Point{
     }
 }
 
-#[derive(FromPrimitive,ToPrimitive,Eq,Clone)]
+#[derive(FromPrimitive,ToPrimitive,Eq,Clone,Default)]
 pub enum PlanType{
     Short,
     Long,
     Idea,
     Current,
+    #[default]
     DefaultValue,
 }
 
@@ -657,12 +754,6 @@ impl astr::ToAstr for PlanType{
     }
 }
 
-impl Default for PlanType{
-    fn default() -> Self{
-        PlanType::DefaultValue
-    }
-}
-
 impl std::cmp::PartialOrd for PlanType{
     fn partial_cmp(&self, other: &PlanType) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -677,17 +768,19 @@ impl std::cmp::Ord for PlanType{
 
 #[derive(Eq,Clone)]
 pub struct Plan{
-    title: astr::Astr,
+    pub title: astr::Astr,
     urgency: u16,
     pub ttype: PlanType,
+    pub estimate: u32,
 }
 
 impl Plan{
-    pub fn new(title: astr::Astr, urgency: u16, strtype: astr::Astr) -> Plan{
+    pub fn new(title: astr::Astr, urgency: u16, strtype: astr::Astr, estimate: u32) -> Plan{
         Plan{
             title,
             urgency,
             ttype: PlanType::from_astr(&strtype, false),
+            estimate,
         }
     }
 }
@@ -696,12 +789,16 @@ impl save::Bufferable for Plan{
     fn into_buffer(&self, vec: &mut Vec<u8>){
         self.title.into_buffer(vec);
         self.urgency.into_buffer(vec);
+        let flag = if self.estimate > 0 {ESTIMATE_FLAG} else {0};
         let primtype = ToPrimitive::to_u8(&self.ttype);
         if let Some(primtypev) = primtype {
-            primtypev.into_buffer(vec);
+            (primtypev | flag).into_buffer(vec);
         }else{
             conz::println_type("Error: Could not convert PlanType to u8.", conz::MsgType::Error);
-            (0 as u8).into_buffer(vec);
+            flag.into_buffer(vec);
+        }
+        if self.estimate > 0{
+            self.estimate.into_buffer(vec);
         }
     }
 
@@ -712,12 +809,16 @@ impl save::Bufferable for Plan{
         res_urg.as_ref()?;
         let res_ttype = u8::from_buffer(vec, iter);
         res_ttype.as_ref()?;
-        let res_ttype = FromPrimitive::from_u8(res_ttype.unwrap());
+        let raw_ttype = res_ttype.unwrap();
+        let has_estimate = raw_ttype != 255 && raw_ttype & ESTIMATE_FLAG != 0;
+        let res_ttype = FromPrimitive::from_u8(if has_estimate {raw_ttype & !ESTIMATE_FLAG} else {raw_ttype});
         res_ttype.as_ref()?;
+        let estimate = if has_estimate {u32::from_buffer(vec, iter)?} else {0};
         Option::Some(Plan{
             title: res_title.unwrap(),
             urgency: res_urg.unwrap(),
             ttype: res_ttype.unwrap(),
+            estimate,
         })
     }
 }
@@ -729,8 +830,10 @@ impl conz::PrettyPrintable for Plan{
         let mut types = Vec::new();
         text.push(self.title.clone());
         text.push(self.urgency.to_string().to_astr());
+        text.push(str_minutes(self.estimate));
         types.push(conz::MsgType::Normal);
         types.push(conz::MsgType::Value);
+        types.push(conz::MsgType::Value);
         if *print_type{
             text.push(self.ttype.to_astr());
             types.push(conz::MsgType::Normal);
@@ -739,18 +842,30 @@ impl conz::PrettyPrintable for Plan{
     }
 
     fn lengths(print_type: &Self::ArgType) -> Vec<u16>{
-        if !*print_type {vec![48,8]}
-        else {vec![48,8,8]}
+        if !*print_type {vec![48,8,10]}
+        else {vec![48,8,10,8]}
     }
 
     fn titles(print_type: &Self::ArgType) -> Vec<astr::Astr>{
         let mut res = vec![astr::from_str("Title:"),
-            astr::from_str("Urgency:")];
+            astr::from_str("Urgency:"),
+            astr::from_str("Estimate:")];
         if *print_type {
             res.push(astr::from_str("Type:"));
         }
         res
     }
+
+    fn raw(&self, _: &Self::ArgType) -> Vec<conz::Raw>{
+        vec![conz::Raw::Text(self.title.clone()),
+            conz::Raw::Num(i64::from(self.urgency)),
+            conz::Raw::Text(self.ttype.to_astr()),
+            conz::Raw::Num(i64::from(self.estimate)),]
+    }
+
+    fn raw_keys(_: &Self::ArgType) -> Vec<&'static str>{
+        vec!["title", "urgency", "type", "estimate"]
+    }
 }
 
 impl std::cmp::Ord for Plan {
@@ -773,11 +888,29 @@ impl std::cmp::PartialEq for Plan {
     fn eq(&self, other: &Plan) -> bool {
         self.title == other.title &&
         self.urgency == other.urgency &&
-        self.ttype == other.ttype
+        self.ttype == other.ttype &&
+        self.estimate == other.estimate
+    }
+}
+
+impl merge::Mergeable for Plan{
+    fn identity(&self) -> astr::Astr{
+        self.title.clone()
+    }
+}
+
+impl storage::Indexed for Plan{
+    fn date(&self) -> Option<i64>{
+        Option::None
+    }
+
+    fn kind(&self) -> Option<u8>{
+        ToPrimitive::to_u8(&self.ttype)
     }
 }
 
 impl wizard::Wizardable for Plan{
+    #[allow(clippy::never_loop)]
     fn extract(wres: &mut wizard::WizardRes) -> Option<Plan>{
         loop{
             let title_res = wres.get_text();
@@ -786,7 +919,9 @@ impl wizard::Wizardable for Plan{
             if urgency.is_none() {break;}
             let ttype = wres.get_text();
             if ttype.is_none() {break;}
-            let ret = Plan::new(title_res.unwrap(), urgency.unwrap(), ttype.unwrap());
+            let estimate = wres.get_duration();
+            if estimate.is_none() {break;}
+            let ret = Plan::new(title_res.unwrap(), urgency.unwrap(), ttype.unwrap(), estimate.unwrap());
             return Option::Some(ret);
         }
         conz::println_type("Error: could not build todo.", conz::MsgType::Error);
@@ -796,13 +931,16 @@ impl wizard::Wizardable for Plan{
     fn get_fields(partial: bool) -> wizard::FieldVec{
         let mut fields = wizard::FieldVec::new();
         if partial{
-            fields.add(wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Partial);
-            fields.add(wizard::InputType::U16, astr::from_str("Urgency: "), wizard::PromptType::Partial);
-            fields.add(wizard::InputType::Text, astr::from_str("Type: "), wizard::PromptType::Partial);
+            fields.add("title", wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Partial);
+            fields.add("urgency", wizard::InputType::U16, astr::from_str("Urgency: "), wizard::PromptType::Partial);
+            fields.add("type", wizard::InputType::Text, astr::from_str("Type: "), wizard::PromptType::Partial);
+            fields.add("estimate", wizard::InputType::Duration, astr::from_str("Estimate: "), wizard::PromptType::Partial);
         }else{
-            fields.add(wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Once);
-            fields.add(wizard::InputType::U16, astr::from_str("Urgency: "), wizard::PromptType::Reprompt);
-            fields.add(wizard::InputType::Text, astr::from_str("Type: "), wizard::PromptType::Once);
+            fields.add("title", wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Once);
+            fields.add("urgency", wizard::InputType::U16, astr::from_str("Urgency: "), wizard::PromptType::Reprompt);
+            fields.add("type", wizard::InputType::Text, astr::from_str("Type: "), wizard::PromptType::Once);
+            fields.add("estimate", wizard::InputType::Duration, astr::from_str("Estimate(like 1h30m, empty for none): "),
+                wizard::PromptType::Partial);
         }
         fields
     }
@@ -812,10 +950,12 @@ impl wizard::Wizardable for Plan{
         let turgency = u16::unwrap_default(wres.get_u16());
         let x = wres.get_text();
         let ttype = PlanType::from_astr(&astr::Astr::unwrap_default(x), true);
+        let estimate = u32::unwrap_default(wres.get_duration());
         Plan{
             title: ttitle,
             urgency: turgency,
             ttype,
+            estimate,
         }
     }
 
@@ -823,6 +963,7 @@ impl wizard::Wizardable for Plan{
         self.title.replace_if_not_default(replacements.title.clone());
         self.urgency.replace_if_not_default(replacements.urgency);
         self.ttype.replace_if_not_default(replacements.ttype.clone());
+        self.estimate.replace_if_not_default(replacements.estimate);
     }
 
     fn score_againts(&self, other: &Self) -> i32{
@@ -852,15 +993,20 @@ impl conz::Printable for Plan{
         conz::println_type(format!("{}", self.urgency), conz::MsgType::Highlight);
         conz::print_type("Type: ", conz::MsgType::Normal);
         conz::println_type(self.ttype.to_astr().disp(), conz::MsgType::Highlight);
+        if self.estimate > 0{
+            conz::print_type("Estimate: ", conz::MsgType::Normal);
+            conz::println_type(str_minutes(self.estimate).disp(), conz::MsgType::Highlight);
+        }
     }
 }
 
-#[derive(FromPrimitive,ToPrimitive,Eq,Clone)]
+#[derive(FromPrimitive,ToPrimitive,Eq,Clone,Default)]
 pub enum SliceType{
     None = 0,
     Deadline = 1,
     Goto = 2,
     Activity = 3,
+    #[default]
     DefaultValue = 255,
 }
 
@@ -901,18 +1047,12 @@ impl astr::ToAstr for SliceType {
     }
 }
 
-impl Default for SliceType {
-    fn default() -> Self{
-        SliceType::DefaultValue
-    }
-}
-
 #[derive(Eq, Clone)]
 pub struct Slice {
     pub start: DT,
     pub end: DT,
-    title: astr::Astr,
-    stype: SliceType,
+    pub title: astr::Astr,
+    pub stype: SliceType,
 }
 
 impl Slice {
@@ -936,12 +1076,11 @@ impl save::Bufferable for Slice {
             primtypev.into_buffer(vec);
         }else{
             conz::println_type("Error: Could not convert SliceType to u8.", conz::MsgType::Error);
-            (0 as u8).into_buffer(vec);
+            0_u8.into_buffer(vec);
         }
     }
 
     fn from_buffer(vec: &Vec<u8>, iter: &mut u32) -> Option<Self>{
-        if (vec.len() as i32) - (*iter as i32) < 18 { return Option::None; }
         let start = DT::from_buffer(vec, iter);
         start.as_ref()?;
         let end = DT::from_buffer(vec, iter);
@@ -990,11 +1129,11 @@ impl conz::Printable for Slice{
         conz::println_type(self.stype.to_astr().disp(), conz::MsgType::Highlight);
         conz::print_type("Start: ", conz::MsgType::Normal);
         conz::print_type(self.start.str_datetime().disp(), conz::MsgType::Value);
-        tbl::print(" ");
+        conz::print_type(" ", conz::MsgType::Normal);
         conz::println_type(self.start.str_dayname().disp(), conz::MsgType::Value);
         conz::print_type("End: ", conz::MsgType::Normal);
         conz::print_type(self.end.str_datetime().disp(), conz::MsgType::Value);
-        tbl::print(" ");
+        conz::print_type(" ", conz::MsgType::Normal);
         conz::println_type(self.end.str_dayname().disp(), conz::MsgType::Value);
     }
 }
@@ -1029,9 +1168,38 @@ impl conz::PrettyPrintable for Slice{
             astr::from_str("End Time Date:"),
             astr::from_str("Type:"),]
     }
+
+    fn raw(&self, _: &Self::ArgType) -> Vec<conz::Raw>{
+        vec![conz::Raw::Text(self.title.clone()),
+            conz::Raw::Text(self.start.str_iso()),
+            conz::Raw::Text(self.end.str_iso()),
+            conz::Raw::Text(self.stype.to_astr()),]
+    }
+
+    fn raw_keys(_: &Self::ArgType) -> Vec<&'static str>{
+        vec!["title", "start", "end", "type"]
+    }
+}
+
+impl merge::Mergeable for Slice{
+    fn identity(&self) -> astr::Astr{
+        //there is a "Work" slice every day
+        self.title.concat(astr::from_str(" ")).concat(self.start.str_datetime())
+    }
+}
+
+impl storage::Indexed for Slice{
+    fn date(&self) -> Option<i64>{
+        Option::Some(self.start.dt.timestamp())
+    }
+
+    fn kind(&self) -> Option<u8>{
+        ToPrimitive::to_u8(&self.stype)
+    }
 }
 
 impl wizard::Wizardable for Slice{
+    #[allow(clippy::never_loop)]
     fn extract(wres: &mut wizard::WizardRes) -> Option<Self>{
         loop{
             let start_res = wres.get_dt();
@@ -1053,15 +1221,15 @@ impl wizard::Wizardable for Slice{
     fn get_fields(partial: bool) -> wizard::FieldVec{
         let mut fields = wizard::FieldVec::new();
         if partial{
-            fields.add(wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Partial);
-            fields.add(wizard::InputType::Text, astr::from_str("Type: "), wizard::PromptType::Partial);
-            fields.add(wizard::InputType::DateTime, astr::from_str("Start time date: "), wizard::PromptType::Partial);
-            fields.add(wizard::InputType::DateTime, astr::from_str("End time date: "), wizard::PromptType::Partial);
+            fields.add("title", wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Partial);
+            fields.add("type", wizard::InputType::Text, astr::from_str("Type: "), wizard::PromptType::Partial);
+            fields.add("start", wizard::InputType::DateTime, astr::from_str("Start time date: "), wizard::PromptType::Partial);
+            fields.add("end", wizard::InputType::DateTime, astr::from_str("End time date: "), wizard::PromptType::Partial);
         }else{
-            fields.add(wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Once);
-            fields.add(wizard::InputType::Text, astr::from_str("Type: "), wizard::PromptType::Once);
-            fields.add(wizard::InputType::DateTime, astr::from_str("Start time date: "), wizard::PromptType::Reprompt);
-            fields.add(wizard::InputType::DateTime, astr::from_str("End time date: "), wizard::PromptType::Reprompt);
+            fields.add("title", wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Once);
+            fields.add("type", wizard::InputType::Text, astr::from_str("Type: "), wizard::PromptType::Once);
+            fields.add("start", wizard::InputType::DateTime, astr::from_str("Start time date: "), wizard::PromptType::Reprompt);
+            fields.add("end", wizard::InputType::DateTime, astr::from_str("End time date: "), wizard::PromptType::Reprompt);
         }
         fields
     }
@@ -1108,25 +1276,29 @@ impl wizard::Wizardable for Slice{
     }
 }
 
-#[derive(Eq, Clone)]
+#[derive(Eq, Clone, Default)]
 pub struct Todo {
-    title: astr::Astr,
+    pub title: astr::Astr,
     pub done: bool,
+    pub estimate: u32,
 }
 
 impl save::Bufferable for Todo {
     fn into_buffer(&self, vec: &mut Vec<u8>){
         self.title.into_buffer(vec);
-        (self.done as u8).into_buffer(vec);
+        if self.estimate > 0{
+            (self.done as u8 | ESTIMATE_FLAG).into_buffer(vec);
+            self.estimate.into_buffer(vec);
+        }else{
+            (self.done as u8).into_buffer(vec);
+        }
     }
 
     fn from_buffer(vec: &Vec<u8>, iter: &mut u32) -> Option<Self>{
-        if (vec.len() as i32) - (*iter as i32) < 5 { return Option::None; }
-        let title = astr::Astr::from_buffer(vec, iter);
-        title.as_ref();
-        let done = u8::from_buffer(vec, iter);
-        done.as_ref();
-        Option::Some(Self{title: title.unwrap(),done: done.unwrap() != 0})
+        let title = astr::Astr::from_buffer(vec, iter)?;
+        let done = u8::from_buffer(vec, iter)?;
+        let estimate = if done & ESTIMATE_FLAG != 0 {u32::from_buffer(vec, iter)?} else {0};
+        Option::Some(Self{title, done: done & !ESTIMATE_FLAG != 0, estimate})
     }
 }
 
@@ -1148,18 +1320,16 @@ impl std::cmp::PartialEq for Todo {
     }
 }
 
-impl Default for Todo{
-    fn default() -> Self{
-        Self{title: Astr::new(), done: false}
-    }
-}
-
 impl conz::Printable for Todo{
     fn print(&self){
         conz::print_type("Title: ", conz::MsgType::Normal);
         conz::println_type(self.title.disp(), conz::MsgType::Highlight);
         conz::print_type("Done:", conz::MsgType::Normal);
         conz::println_type(format!("{}", self.done), conz::MsgType::Highlight);
+        if self.estimate > 0{
+            conz::print_type("Estimate: ", conz::MsgType::Normal);
+            conz::println_type(str_minutes(self.estimate).disp(), conz::MsgType::Highlight);
+        }
     }
 }
 
@@ -1174,29 +1344,61 @@ impl conz::PrettyPrintable for Todo{
         let mut types = Vec::new();
         text.push(bool_tickbox(self.done));
         text.push(self.title.clone());
+        text.push(str_minutes(self.estimate));
         types.push(conz::MsgType::Value);
         types.push(conz::MsgType::Normal);
+        types.push(conz::MsgType::Value);
         (text,types)
     }
 
     fn lengths(_: &Self::ArgType) -> Vec<u16>{
-        vec![5,48]
+        vec![5,48,10]
     }
 
     fn titles(_: &Self::ArgType) -> Vec<astr::Astr>{
         vec![astr::from_str("Done:"),
-            astr::from_str("Title:"),]
+            astr::from_str("Title:"),
+            astr::from_str("Estimate:"),]
+    }
+
+    fn raw(&self, _: &Self::ArgType) -> Vec<conz::Raw>{
+        vec![conz::Raw::Bool(self.done),
+            conz::Raw::Text(self.title.clone()),
+            conz::Raw::Num(i64::from(self.estimate)),]
+    }
+
+    fn raw_keys(_: &Self::ArgType) -> Vec<&'static str>{
+        vec!["done", "title", "estimate"]
+    }
+}
+
+impl merge::Mergeable for Todo{
+    fn identity(&self) -> astr::Astr{
+        self.title.clone()
+    }
+}
+
+impl storage::Indexed for Todo{
+    fn date(&self) -> Option<i64>{
+        Option::None
+    }
+
+    fn kind(&self) -> Option<u8>{
+        Option::None
     }
 }
 
 impl wizard::Wizardable for Todo{
+    #[allow(clippy::never_loop)]
     fn extract(wres: &mut wizard::WizardRes) -> Option<Self>{
         loop{
             let title_res = wres.get_text();
             if title_res.is_none() {break;}
             let done_res = wres.get_bool();
             if done_res.is_none() {break;}
-            return Option::Some(Self{title:title_res.unwrap(),done:done_res.unwrap()});
+            let estimate = wres.get_duration();
+            if estimate.is_none() {break;}
+            return Option::Some(Self{title:title_res.unwrap(),done:done_res.unwrap(),estimate:estimate.unwrap()});
         }
         conz::println_type("Error: could not build todo.", conz::MsgType::Error);
         Option::None
@@ -1205,11 +1407,14 @@ impl wizard::Wizardable for Todo{
     fn get_fields(partial: bool) -> wizard::FieldVec{
         let mut fields = wizard::FieldVec::new();
         if partial{
-            fields.add(wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Partial);
-            fields.add(wizard::InputType::Bool, astr::from_str("Done: "), wizard::PromptType::Partial);
+            fields.add("title", wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Partial);
+            fields.add("done", wizard::InputType::Bool, astr::from_str("Done: "), wizard::PromptType::Partial);
+            fields.add("estimate", wizard::InputType::Duration, astr::from_str("Estimate: "), wizard::PromptType::Partial);
         }else{
-            fields.add(wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Once);
-            fields.add(wizard::InputType::Bool, astr::from_str("Done: "), wizard::PromptType::Reprompt);
+            fields.add("title", wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Once);
+            fields.add("done", wizard::InputType::Bool, astr::from_str("Done: "), wizard::PromptType::Reprompt);
+            fields.add("estimate", wizard::InputType::Duration, astr::from_str("Estimate(like 1h30m, empty for none): "),
+                wizard::PromptType::Partial);
         }
         fields
     }
@@ -1217,15 +1422,18 @@ impl wizard::Wizardable for Todo{
     fn get_partial(wres: &mut wizard::WizardRes) -> Self{
         let ttitle = astr::Astr::unwrap_default(wres.get_text());
         let tdone = bool::unwrap_default(wres.get_bool());
+        let estimate = u32::unwrap_default(wres.get_duration());
         Todo{
             title: ttitle,
             done: tdone,
+            estimate,
         }
     }
 
     fn replace_parts(&mut self, replacements: &Self){
         self.title.replace_if_not_default(replacements.title.clone());
         self.done.replace_if_not_default(replacements.done);
+        self.estimate.replace_if_not_default(replacements.estimate);
     }
 
     fn score_againts(&self, other: &Self) -> i32{
@@ -1243,3 +1451,668 @@ impl wizard::Wizardable for Todo{
         astr::from_str("todo")
     }
 }
+
+#[derive(FromPrimitive,ToPrimitive,Eq,PartialEq,Clone,Copy)]
+pub enum SessionKind{
+    Plan = 0,
+    Todo = 1,
+}
+
+impl astr::ToAstr for SessionKind{
+    fn to_astr(&self) -> astr::Astr{
+        astr::from_str(match self{
+            SessionKind::Plan => "plan",
+            SessionKind::Todo => "todo",
+        })
+    }
+}
+
+//time spent on a plan or todo, running until stopped
+#[derive(Eq, Clone)]
+pub struct Session{
+    pub title: astr::Astr,
+    pub kind: SessionKind,
+    pub start: DT,
+    pub end: DT,
+    pub running: bool,
+}
+
+impl Session{
+    pub fn start(title: astr::Astr, kind: SessionKind) -> Self{
+        Self{
+            title,
+            kind,
+            start: DT::new(),
+            end: DT::new(),
+            running: true,
+        }
+    }
+
+    pub fn stop(&mut self){
+        self.end = DT::new();
+        self.running = false;
+    }
+
+    //a running session counts up to now
+    pub fn secs(&self) -> u64{
+        let span = if self.running {self.start.diff(&DT::new())}
+        else {self.start.diff(&self.end)};
+        if span.neg {0}
+        else {span.total_secs}
+    }
+}
+
+impl save::Bufferable for Session{
+    fn into_buffer(&self, vec: &mut Vec<u8>){
+        self.title.into_buffer(vec);
+        (self.kind as u8).into_buffer(vec);
+        (self.running as u8).into_buffer(vec);
+        self.start.into_buffer(vec);
+        self.end.into_buffer(vec);
+    }
+
+    fn from_buffer(vec: &Vec<u8>, iter: &mut u32) -> Option<Self>{
+        let title = astr::Astr::from_buffer(vec, iter)?;
+        let kind = FromPrimitive::from_u8(u8::from_buffer(vec, iter)?)?;
+        let running = u8::from_buffer(vec, iter)? != 0;
+        let start = DT::from_buffer(vec, iter)?;
+        let end = DT::from_buffer(vec, iter)?;
+        Option::Some(Self{title, kind, start, end, running})
+    }
+}
+
+impl merge::Mergeable for Session{
+    fn identity(&self) -> astr::Astr{
+        //a running session that is stopped is the same session
+        self.title.concat(astr::from_str(" ")).concat(self.start.str_datetime())
+    }
+}
+
+impl storage::Indexed for Session{
+    fn date(&self) -> Option<i64>{
+        Option::Some(self.start.dt.timestamp())
+    }
+
+    fn kind(&self) -> Option<u8>{
+        ToPrimitive::to_u8(&self.kind)
+    }
+}
+
+impl std::cmp::Ord for Session{
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering{
+        self.start.cmp(&other.start)
+    }
+}
+
+impl std::cmp::PartialOrd for Session{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering>{
+        Some(self.cmp(other))
+    }
+}
+
+impl std::cmp::PartialEq for Session{
+    fn eq(&self, other: &Self) -> bool{
+        self.title == other.title &&
+        self.kind == other.kind &&
+        self.start == other.start &&
+        self.end == other.end &&
+        self.running == other.running
+    }
+}
+
+impl conz::PrettyPrintable for Session{
+    type ArgType = u8;
+    fn pretty_print(&self, _: &Self::ArgType) -> (astr::AstrVec,Vec<conz::MsgType>){
+        let end = if self.running {astr::from_str("running")}
+        else {self.end.str_datetime()};
+        let spent = Span::from_secs(self.secs(), false).string_significant(true).to_astr();
+        (vec![self.title.clone(), self.kind.to_astr(), self.start.str_datetime(), end, spent],
+        vec![conz::MsgType::Normal, conz::MsgType::Normal, conz::MsgType::Value, conz::MsgType::Value,
+            conz::MsgType::Highlight])
+    }
+
+    fn lengths(_: &Self::ArgType) -> Vec<u16>{
+        vec![32,5,19,19,10]
+    }
+
+    fn titles(_: &Self::ArgType) -> Vec<astr::Astr>{
+        vec![astr::from_str("Title:"),
+            astr::from_str("Kind:"),
+            astr::from_str("Start:"),
+            astr::from_str("End:"),
+            astr::from_str("Spent:"),]
+    }
+
+    fn raw(&self, _: &Self::ArgType) -> Vec<conz::Raw>{
+        vec![conz::Raw::Text(self.title.clone()),
+            conz::Raw::Text(self.kind.to_astr()),
+            conz::Raw::Text(self.start.str_iso()),
+            conz::Raw::Text(if self.running {astr::Astr::new()} else {self.end.str_iso()}),
+            conz::Raw::Num(self.secs() as i64),]
+    }
+
+    fn raw_keys(_: &Self::ArgType) -> Vec<&'static str>{
+        vec!["title", "kind", "start", "end", "secs"]
+    }
+}
+
+//estimate of a plan or todo next to the time its sessions add up to
+pub struct Tracked{
+    pub title: astr::Astr,
+    pub kind: SessionKind,
+    pub estimate: u32,
+    pub secs: u64,
+}
+
+impl Tracked{
+    pub fn new(title: astr::Astr, kind: SessionKind, estimate: u32, sessions: &[Session]) -> Self{
+        let secs = sessions.iter()
+            .filter(|s| s.kind == kind && s.title == title)
+            .map(|s| s.secs())
+            .sum();
+        Self{title, kind, estimate, secs}
+    }
+
+    pub fn str_spent(&self) -> astr::Astr{
+        Span::from_secs(self.secs, false).string_significant(true).to_astr()
+    }
+
+    //percentage of the estimate used, none without an estimate
+    pub fn progress(&self) -> Option<u64>{
+        if self.estimate == 0 {return Option::None;}
+        Option::Some(self.secs * 100 / (u64::from(self.estimate) * Span::SECS_MIN))
+    }
+}
+
+impl conz::PrettyPrintable for Tracked{
+    type ArgType = u8;
+    fn pretty_print(&self, _: &Self::ArgType) -> (astr::AstrVec,Vec<conz::MsgType>){
+        let progress = self.progress();
+        let (ptext, ptype) = match progress{
+            Option::Some(x) if x > 100 => (format!("{}%", x).to_astr(), conz::MsgType::Error),
+            Option::Some(x) => (format!("{}%", x).to_astr(), conz::MsgType::Value),
+            Option::None => (astr::Astr::new(), conz::MsgType::Normal),
+        };
+        (vec![self.title.clone(), self.kind.to_astr(), str_minutes(self.estimate), self.str_spent(), ptext],
+        vec![conz::MsgType::Normal, conz::MsgType::Normal, conz::MsgType::Value, conz::MsgType::Highlight, ptype])
+    }
+
+    fn lengths(_: &Self::ArgType) -> Vec<u16>{
+        vec![32,5,10,10,9]
+    }
+
+    fn titles(_: &Self::ArgType) -> Vec<astr::Astr>{
+        vec![astr::from_str("Title:"),
+            astr::from_str("Kind:"),
+            astr::from_str("Estimate:"),
+            astr::from_str("Spent:"),
+            astr::from_str("Progress:"),]
+    }
+
+    fn raw(&self, _: &Self::ArgType) -> Vec<conz::Raw>{
+        vec![conz::Raw::Text(self.title.clone()),
+            conz::Raw::Text(self.kind.to_astr()),
+            conz::Raw::Num(i64::from(self.estimate)),
+            conz::Raw::Num(self.secs as i64),]
+    }
+
+    fn raw_keys(_: &Self::ArgType) -> Vec<&'static str>{
+        vec!["title", "kind", "estimate", "secs"]
+    }
+}
+
+//7 times a week is daily
+pub fn parse_schedule(string: &astr::Astr) -> Option<u8>{
+    let string = string.to_lower().to_string();
+    if string == "daily" || string == "day" {return Option::Some(7);}
+    let number = string.trim_end_matches("/week").trim_end_matches('x');
+    let per_week = number.parse::<u8>().ok()?;
+    if per_week == 0 || per_week > 7 {return Option::None;}
+    Option::Some(per_week)
+}
+
+pub fn str_schedule(per_week: u8) -> astr::Astr{
+    if per_week == 7 {astr::from_str("daily")}
+    else {format!("{}/week", per_week).to_astr()}
+}
+
+#[derive(Eq, Clone, Default)]
+pub struct Habit{
+    pub title: astr::Astr,
+    pub per_week: u8,
+    pub checks: Vec<u32>,
+}
+
+impl Habit{
+    pub fn is_daily(&self) -> bool{
+        self.per_week >= 7
+    }
+
+    pub fn is_checked(&self, day: u32) -> bool{
+        self.checks.binary_search(&day).is_ok()
+    }
+
+    //keeps the check-ins sorted and unique, false when it was checked already
+    pub fn check(&mut self, day: u32) -> bool{
+        match self.checks.binary_search(&day){
+            Ok(_) => false,
+            Err(i) => {
+                self.checks.insert(i, day);
+                true
+            }
+        }
+    }
+
+    //the date of a day number, none for days chrono can not hold
+    fn date(day: u32) -> Option<chrono::NaiveDate>{
+        chrono::NaiveDate::from_num_days_from_ce_opt(day.try_into().ok()?)
+    }
+
+    //monday of the ISO week the day is in
+    fn week_start(day: u32) -> u32{
+        Self::date(day).and_then(|date| day.checked_sub(date.weekday().num_days_from_monday())).unwrap_or(day)
+    }
+
+    /*
+    Current and longest streak, in days for daily habits and in weeks otherwise.
+    A week counts when it has enough check-ins.
+    Today or this week not done yet does not break the current streak.
+    */
+    pub fn streaks(&self, today: u32) -> (u32, u32){
+        let (units, step, now): (Vec<u32>, u32, u32) = if self.is_daily(){
+            (self.checks.clone(), 1, today)
+        }else{
+            let mut weeks: Vec<(u32, u8)> = Vec::new();
+            for day in &self.checks{
+                let week = Self::week_start(*day);
+                match weeks.last_mut(){
+                    Option::Some((w, count)) if *w == week => *count = count.saturating_add(1),
+                    _ => weeks.push((week, 1)),
+                }
+            }
+            let met = weeks.into_iter().filter(|(_,c)| *c >= self.per_week).map(|(w,_)| w).collect();
+            (met, 7, Self::week_start(today))
+        };
+        let mut longest = 0;
+        let mut run = 0;
+        let mut last: Option<u32> = Option::None;
+        for unit in &units{
+            run = match last{
+                Option::Some(l) if l.checked_add(step) == Option::Some(*unit) => run + 1,
+                _ => 1,
+            };
+            longest = std::cmp::max(longest, run);
+            last = Option::Some(*unit);
+        }
+        let current = match last{
+            Option::Some(l) if l == now || l.checked_add(step) == Option::Some(now) => run,
+            _ => 0,
+        };
+        (current, longest)
+    }
+
+    //one character per day, oldest first, today last
+    pub fn strip(&self, today: u32, days: u32) -> astr::Astr{
+        let mut res = String::new();
+        for day in (today + 1).saturating_sub(days)..=today{
+            res.push(if self.is_checked(day) {'#'} else {'.'});
+        }
+        res.to_astr()
+    }
+
+    fn str_streak(&self, streak: u32) -> astr::Astr{
+        let unit = if self.is_daily() {"days"} else {"weeks"};
+        format!("{} {}", streak, unit).to_astr()
+    }
+}
+
+impl save::Bufferable for Habit{
+    fn into_buffer(&self, vec: &mut Vec<u8>){
+        self.title.into_buffer(vec);
+        self.per_week.into_buffer(vec);
+        (self.checks.len() as u32).into_buffer(vec);
+        for day in &self.checks{
+            day.into_buffer(vec);
+        }
+    }
+
+    fn from_buffer(vec: &Vec<u8>, iter: &mut u32) -> Option<Self>{
+        let title = astr::Astr::from_buffer(vec, iter)?;
+        let per_week = u8::from_buffer(vec, iter)?;
+        let count = u32::from_buffer(vec, iter)?;
+        let mut checks: Vec<u32> = Vec::new();
+        for _ in 0..count{
+            let day = u32::from_buffer(vec, iter)?;
+            //check-ins are kept sorted and unique, and have to be real days
+            if checks.last().map(|last| *last >= day).unwrap_or(false) || Self::date(day).is_none(){
+                return Option::None;
+            }
+            checks.push(day);
+        }
+        Option::Some(Self{title, per_week, checks})
+    }
+}
+
+impl std::cmp::Ord for Habit{
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering{
+        self.title.cmp(&other.title)
+    }
+}
+
+impl std::cmp::PartialOrd for Habit{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering>{
+        Some(self.cmp(other))
+    }
+}
+
+impl std::cmp::PartialEq for Habit{
+    fn eq(&self, other: &Self) -> bool{
+        self.title == other.title
+    }
+}
+
+impl conz::Printable for Habit{
+    fn print(&self){
+        let today = DT::new().day_number();
+        let (current, longest) = self.streaks(today);
+        conz::print_type("Title: ", conz::MsgType::Normal);
+        conz::println_type(self.title.disp(), conz::MsgType::Highlight);
+        conz::print_type("Schedule: ", conz::MsgType::Normal);
+        conz::println_type(str_schedule(self.per_week).disp(), conz::MsgType::Highlight);
+        conz::print_type("Streak: ", conz::MsgType::Normal);
+        conz::print_type(self.str_streak(current).disp(), conz::MsgType::Value);
+        conz::print_type(", longest ", conz::MsgType::Normal);
+        conz::println_type(self.str_streak(longest).disp(), conz::MsgType::Value);
+        conz::print_type("Check-ins: ", conz::MsgType::Normal);
+        conz::println_type(self.checks.len(), conz::MsgType::Value);
+    }
+}
+
+impl conz::PrettyPrintable for Habit{
+    type ArgType = u8;
+    fn pretty_print(&self, _: &Self::ArgType) -> (astr::AstrVec,Vec<conz::MsgType>){
+        let today = DT::new().day_number();
+        let (current, longest) = self.streaks(today);
+        let ctype = if current == 0 {conz::MsgType::Error} else {conz::MsgType::Value};
+        (vec![self.title.clone(), str_schedule(self.per_week), self.str_streak(current), self.str_streak(longest),
+            self.strip(today, 30)],
+        vec![conz::MsgType::Normal, conz::MsgType::Normal, ctype, conz::MsgType::Value, conz::MsgType::Highlight])
+    }
+
+    fn lengths(_: &Self::ArgType) -> Vec<u16>{
+        vec![24,9,9,9,30]
+    }
+
+    fn titles(_: &Self::ArgType) -> Vec<astr::Astr>{
+        vec![astr::from_str("Title:"),
+            astr::from_str("Schedule:"),
+            astr::from_str("Streak:"),
+            astr::from_str("Longest:"),
+            astr::from_str("Last 30 days:"),]
+    }
+
+    fn raw(&self, _: &Self::ArgType) -> Vec<conz::Raw>{
+        let today = DT::new().day_number();
+        let (current, longest) = self.streaks(today);
+        vec![conz::Raw::Text(self.title.clone()),
+            conz::Raw::Text(str_schedule(self.per_week)),
+            conz::Raw::Num(i64::from(current)),
+            conz::Raw::Num(i64::from(longest)),
+            conz::Raw::Text(self.strip(today, 30)),]
+    }
+
+    fn raw_keys(_: &Self::ArgType) -> Vec<&'static str>{
+        vec!["title", "schedule", "streak", "longest", "last30"]
+    }
+}
+
+impl merge::Mergeable for Habit{
+    fn identity(&self) -> astr::Astr{
+        self.title.clone()
+    }
+}
+
+impl storage::Indexed for Habit{
+    fn date(&self) -> Option<i64>{
+        Option::None
+    }
+
+    fn kind(&self) -> Option<u8>{
+        Option::None
+    }
+}
+
+impl wizard::Wizardable for Habit{
+    #[allow(clippy::never_loop)]
+    fn extract(wres: &mut wizard::WizardRes) -> Option<Self>{
+        loop{
+            let title = wres.get_text();
+            if title.is_none() {break;}
+            let schedule = wres.get_text();
+            if schedule.is_none() {break;}
+            let per_week = parse_schedule(&schedule.unwrap());
+            if per_week.is_none(){
+                conz::println_type("Error: schedule should be daily or times per week like 3/week.", conz::MsgType::Error);
+                break;
+            }
+            return Option::Some(Self{title: title.unwrap(), per_week: per_week.unwrap(), checks: Vec::new()});
+        }
+        conz::println_type("Error: could not build habit.", conz::MsgType::Error);
+        Option::None
+    }
+
+    fn get_fields(partial: bool) -> wizard::FieldVec{
+        let mut fields = wizard::FieldVec::new();
+        if partial{
+            fields.add("title", wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Partial);
+            fields.add("schedule", wizard::InputType::Text, astr::from_str("Schedule: "), wizard::PromptType::Partial);
+        }else{
+            fields.add("title", wizard::InputType::Text, astr::from_str("Title: "), wizard::PromptType::Once);
+            fields.add("schedule", wizard::InputType::Text, astr::from_str("Schedule(daily or like 3/week): "),
+                wizard::PromptType::Reprompt);
+        }
+        fields
+    }
+
+    fn get_partial(wres: &mut wizard::WizardRes) -> Self{
+        let title = astr::Astr::unwrap_default(wres.get_text());
+        let per_week = parse_schedule(&astr::Astr::unwrap_default(wres.get_text())).unwrap_or(0);
+        Habit{
+            title,
+            per_week,
+            checks: Vec::new(),
+        }
+    }
+
+    fn replace_parts(&mut self, replacements: &Self){
+        self.title.replace_if_not_default(replacements.title.clone());
+        self.per_week.replace_if_not_default(replacements.per_week);
+    }
+
+    fn score_againts(&self, other: &Self) -> i32{
+        let mut curr_score = 0;
+        if self.title == other.title{
+            curr_score += 1;
+        }
+        if self.per_week == other.per_week{
+            curr_score += 1;
+        }
+        curr_score
+    }
+
+    fn get_name() -> astr::Astr{
+        astr::from_str("habit")
+    }
+}
+
+//a reminder the daemon fired, lead minutes before at
+#[derive(Eq, Clone)]
+pub struct Reminder{
+    pub title: astr::Astr,
+    pub at: DT,
+    pub lead: u32,
+}
+
+impl save::Bufferable for Reminder{
+    fn into_buffer(&self, vec: &mut Vec<u8>){
+        self.title.into_buffer(vec);
+        self.at.into_buffer(vec);
+        self.lead.into_buffer(vec);
+    }
+
+    fn from_buffer(vec: &Vec<u8>, iter: &mut u32) -> Option<Self>{
+        let title = astr::Astr::from_buffer(vec, iter)?;
+        let at = DT::from_buffer(vec, iter)?;
+        let lead = u32::from_buffer(vec, iter)?;
+        Option::Some(Self{title, at, lead})
+    }
+}
+
+impl std::cmp::Ord for Reminder{
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering{
+        self.at.cmp(&other.at)
+            .then_with(|| self.title.cmp(&other.title))
+            .then_with(|| self.lead.cmp(&other.lead))
+    }
+}
+
+impl std::cmp::PartialOrd for Reminder{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering>{
+        Some(self.cmp(other))
+    }
+}
+
+impl std::cmp::PartialEq for Reminder{
+    fn eq(&self, other: &Self) -> bool{
+        self.title == other.title &&
+        self.at == other.at &&
+        self.lead == other.lead
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn duration(s: &str) -> Option<u32>{
+        parse_duration(&astr::from_str(s))
+    }
+
+    fn hms(s: &str) -> Option<(u32, u32, u32)>{
+        parse_hms(&astr::from_str(s))
+    }
+
+    #[test]
+    fn duration_in_minutes(){
+        assert_eq!(duration("90"), Option::Some(90));
+        assert_eq!(duration("0"), Option::Some(0));
+    }
+
+    #[test]
+    fn duration_with_a_colon(){
+        assert_eq!(duration("1:30"), Option::Some(90));
+        assert_eq!(duration("0:05"), Option::Some(5));
+        assert_eq!(duration("1:"), Option::None);
+        assert_eq!(duration(":30"), Option::None);
+        assert_eq!(duration("1:30:00"), Option::None);
+    }
+
+    #[test]
+    fn duration_with_units(){
+        assert_eq!(duration("1h30m"), Option::Some(90));
+        assert_eq!(duration("2h"), Option::Some(120));
+        assert_eq!(duration("45m"), Option::Some(45));
+        assert_eq!(duration("1H30M"), Option::Some(90));
+        //the same unit twice adds up
+        assert_eq!(duration("1h1h"), Option::Some(120));
+    }
+
+    #[test]
+    fn duration_in_days(){
+        assert_eq!(duration("1d"), Option::Some(24 * 60));
+        assert_eq!(duration("1D2H"), Option::Some(26 * 60));
+        assert_eq!(duration("3000000d"), Option::None);
+    }
+
+    #[test]
+    fn duration_turns_down_the_rest(){
+        assert_eq!(duration("1h30"), Option::None);
+        assert_eq!(duration("h"), Option::None);
+        assert_eq!(duration("1 h"), Option::None);
+        assert_eq!(duration("1.5h"), Option::None);
+        assert_eq!(duration("-5"), Option::None);
+        assert_eq!(duration("1w"), Option::None);
+    }
+
+    #[test]
+    fn duration_does_not_overflow(){
+        assert_eq!(duration("4294967295"), Option::Some(u32::MAX));
+        assert_eq!(duration("4294967296"), Option::None);
+        assert_eq!(duration("71582789:00"), Option::None);
+        assert_eq!(duration("4294967295m1m"), Option::None);
+    }
+
+    #[test]
+    fn hms_with_and_without_seconds(){
+        assert_eq!(hms("9:00"), Option::Some((9, 0, 0)));
+        assert_eq!(hms("23:59:30"), Option::Some((23, 59, 30)));
+        assert_eq!(hms("12.15"), Option::Some((12, 15, 0)));
+        assert_eq!(hms("8-30-5"), Option::Some((8, 30, 5)));
+    }
+
+    #[test]
+    fn hms_words(){
+        assert_eq!(hms("dead"), Option::Some((23, 59, 59)));
+        assert_eq!(hms("idk"), Option::Some((0, 0, 1)));
+    }
+
+    #[test]
+    fn hms_turns_down_the_rest(){
+        assert_eq!(hms("9"), Option::None);
+        assert_eq!(hms("1:2:3:4"), Option::None);
+        assert_eq!(hms("9:xx"), Option::None);
+        assert_eq!(hms("noon"), Option::None);
+    }
+
+    //4 february 2030 is a monday
+    fn monday() -> u32{
+        NaiveDate::from_ymd_opt(2030, 2, 4).unwrap().num_days_from_ce() as u32
+    }
+
+    fn habit(per_week: u8, checks: &[u32]) -> Habit{
+        Habit{title: astr::from_str("run"), per_week, checks: checks.to_vec()}
+    }
+
+    fn read_habit(checks: &[u32]) -> Option<Habit>{
+        let mut buf = Vec::new();
+        save::Bufferable::into_buffer(&habit(7, checks), &mut buf);
+        <Habit as save::Bufferable>::from_buffer(&buf, &mut 0)
+    }
+
+    #[test]
+    fn habit_streaks(){
+        let m = monday();
+        assert_eq!(habit(7, &[m - 2, m, m + 1, m + 2]).streaks(m + 3), (3, 3));
+        assert_eq!(habit(7, &[m, m + 1]).streaks(m + 3), (0, 2));
+        assert_eq!(habit(2, &[m - 7, m - 6, m, m + 6]).streaks(m + 8), (2, 2));
+        assert_eq!(habit(2, &[m - 7, m]).streaks(m), (0, 0));
+    }
+
+    #[test]
+    fn habit_streaks_at_the_edges(){
+        assert_eq!(habit(7, &[0, 1, u32::MAX - 1, u32::MAX]).streaks(5), (0, 2));
+        assert_eq!(habit(1, &[u32::MAX]).streaks(5), (0, 1));
+        //more check-ins in a week than a count holds, only from a habit that was not read
+        let m = monday();
+        assert_eq!(habit(1, &[m; 300]).streaks(m), (1, 1));
+    }
+
+    #[test]
+    fn habit_checks_read_back_only_in_order(){
+        let m = monday();
+        assert_eq!(read_habit(&[m, m + 1]).map(|h| h.checks), Option::Some(vec![m, m + 1]));
+        assert!(read_habit(&[m + 1, m]).is_none());
+        assert!(read_habit(&[m, m]).is_none());
+        assert!(read_habit(&[m, u32::MAX]).is_none());
+    }
+}
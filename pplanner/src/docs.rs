@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+
+use super::astr;
+use super::astr::{AStr};
+use super::help;
+
+type Helps = BTreeMap<astr::Astr, help::CmdHelp>;
+
+const MAN_TEMPLATE: &str = include_str!("../pplanner.ms.in");
+const MAN_MARKER: &str = ".\\\" COMMANDS";
+
+//debug commands start with _, they stay out of the docs
+fn public(helps: &Helps) -> impl Iterator<Item = (String, &help::CmdHelp)>{
+    helps.iter()
+        .map(|(n,h)| (n.to_string(), h))
+        .filter(|(n,_)| !n.starts_with('_'))
+}
+
+//a line starting with . or ' would be a request, - should be \- and " would quote
+fn roff_escape(line: &str) -> String{
+    let line = line.replace('\\', "\\e").replace('-', "\\-").replace('"', "\\(dq");
+    if line.starts_with('.') || line.starts_with('\'') {format!("\\&{}", line)}
+    else {line}
+}
+
+pub fn man_commands(helps: &Helps) -> String{
+    let mut res = String::from(".SH COMMANDS\n");
+    res.push_str("Commands are typed in the prompt, given to -e or put in a script.\n");
+    for (name, help) in public(helps){
+        res.push_str(".TP\n");
+        res.push_str(&format!(".B {}\n", roff_escape(&name)));
+        res.push_str(&format!("{}\n", roff_escape(help.summary)));
+        for line in &help.text{
+            res.push_str(&format!("{}\n", roff_escape(line)));
+        }
+        res.push_str(".RS\n.PP\nUsage:\n");
+        res.push_str(&format!(".B {}\n", roff_escape(help.usage)));
+        for (arg, desc) in &help.args{
+            res.push_str(".br\n");
+            res.push_str(&format!("\\fI{}\\fR: {}\n", roff_escape(arg), roff_escape(desc)));
+        }
+        for example in &help.examples{
+            res.push_str(".br\n");
+            res.push_str(&format!("Example: {}\n", roff_escape(example)));
+        }
+        res.push_str(".RE\n");
+    }
+    res
+}
+
+pub fn man(helps: &Helps) -> String{
+    let mut res = String::new();
+    for line in MAN_TEMPLATE.lines(){
+        if line.starts_with(".\\\" Template") {continue;}
+        if line == MAN_MARKER{
+            res.push_str(&man_commands(helps));
+            continue;
+        }
+        res.push_str(line);
+        res.push('\n');
+    }
+    res
+}
+
+//markdown list for the README, one line per command
+pub fn markdown(helps: &Helps) -> String{
+    let mut res = String::from("### commands\n");
+    for (_, help) in public(helps){
+        res.push_str(&format!("- `{}`: {}\n", help.usage, help.summary));
+    }
+    res
+}
@@ -0,0 +1,30 @@
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CmdError{
+    NotFound,   //command, help or file does not exist
+    BadInput,   //inputs missing, unparsable or not supported
+    Io,         //reading or writing a file failed
+    NoMatch,    //a search found nothing or nothing unique
+    Conflict,   //a merge has conflicts that are not resolved
+}
+
+pub type CmdRes = Result<(), CmdError>;
+
+impl CmdError{
+    //0 is success, 1 is left for generic failures like a bad cli or script
+    pub fn exit_code(self) -> i32{
+        match self{
+            CmdError::NotFound => 2,
+            CmdError::BadInput => 3,
+            CmdError::Io => 4,
+            CmdError::NoMatch => 5,
+            CmdError::Conflict => 6,
+        }
+    }
+}
+
+pub fn exit_code(res: CmdRes) -> i32{
+    match res{
+        Ok(_) => 0,
+        Err(e) => e.exit_code(),
+    }
+}
@@ -0,0 +1,69 @@
+use super::conz;
+
+/*
+Help of a command, compiled in from its file in help/.
+The first line is the summary, then lines starting with
+"usage: ", "arg name: " and "example: " are picked out,
+all other lines are the description.
+*/
+pub struct CmdHelp{
+    pub summary: &'static str,
+    pub text: Vec<&'static str>,
+    pub usage: &'static str,
+    pub args: Vec<(&'static str, &'static str)>,
+    pub examples: Vec<&'static str>,
+}
+
+impl CmdHelp{
+    pub fn parse(name: &'static str, source: &'static str) -> Self{
+        let mut lines = source.lines();
+        let mut help = CmdHelp{
+            summary: lines.next().unwrap_or("").trim(),
+            text: Vec::new(),
+            usage: name,
+            args: Vec::new(),
+            examples: Vec::new(),
+        };
+        for line in lines{
+            if let Some(x) = line.strip_prefix("usage: "){
+                help.usage = x.trim();
+            }else if let Some(x) = line.strip_prefix("example: "){
+                help.examples.push(x.trim());
+            }else if let Some(x) = line.strip_prefix("arg "){
+                match x.find(": "){
+                    Option::Some(i) => help.args.push((x[..i].trim(), x[i + 2..].trim())),
+                    Option::None => help.text.push(line),
+                }
+            }else{
+                help.text.push(line);
+            }
+        }
+        help
+    }
+
+    pub fn print(&self, name: &str){
+        conz::print_type("Command: ", conz::MsgType::Normal);
+        conz::println_type(name, conz::MsgType::Highlight);
+        conz::println_type(self.summary, conz::MsgType::Normal);
+        for line in &self.text{
+            conz::println_type(line, conz::MsgType::Normal);
+        }
+        conz::print_type("Usage: ", conz::MsgType::Normal);
+        conz::println_type(self.usage, conz::MsgType::Value);
+        if !self.args.is_empty(){
+            conz::println_type("Arguments: ", conz::MsgType::Normal);
+            let width = self.args.iter().map(|(a,_)| a.len()).max().unwrap_or(0);
+            for (arg, desc) in &self.args{
+                conz::print_type(format!("  {:w$}  ", arg, w = width), conz::MsgType::Value);
+                conz::println_type(desc, conz::MsgType::Normal);
+            }
+        }
+        if !self.examples.is_empty(){
+            conz::println_type("Examples: ", conz::MsgType::Normal);
+            for example in &self.examples{
+                conz::print_type("  ", conz::MsgType::Normal);
+                conz::println_type(example, conz::MsgType::Highlight);
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+use super::conz;
+use super::config;
+use super::support;
+use super::sync;
+
+#[derive(Clone, Copy)]
+pub enum Event{
+    Add,
+    Edit,
+    Remove,
+    Tick,
+    Flush,
+}
+
+impl Event{
+    const ALL: [Event; 5] = [Event::Add, Event::Edit, Event::Remove, Event::Tick, Event::Flush];
+
+    pub fn key(self) -> &'static str{
+        match self{
+            Event::Add => "on_add",
+            Event::Edit => "on_edit",
+            Event::Remove => "on_remove",
+            Event::Tick => "on_tick",
+            Event::Flush => "on_flush",
+        }
+    }
+
+    fn verb(self) -> &'static str{
+        match self{
+            Event::Add => "add",
+            Event::Edit => "edit",
+            Event::Remove => "remove",
+            Event::Tick => "tick",
+            Event::Flush => "flush",
+        }
+    }
+}
+
+//commands per event, in the order of Event::ALL
+static HOOKS: OnceLock<Vec<Vec<String>>> = OnceLock::new();
+
+pub fn setup(config: &config::Config){
+    let hooks = Event::ALL.iter()
+        .map(|e| config.get_all(e.key()).into_iter().filter(|c| !c.is_empty()).map(String::from).collect())
+        .collect();
+    let _ = HOOKS.set(hooks);
+}
+
+fn commands(event: Event) -> &'static [String]{
+    match HOOKS.get(){
+        Option::Some(x) => &x[event as usize],
+        Option::None => &[],
+    }
+}
+
+/*
+Runs after the change is saved, so a failing hook can not undo or corrupt it.
+The hook gets one json object per item on stdin, and the event and the kind of item
+in PPLANNER_EVENT and PPLANNER_KIND. Its output goes to stderr, to keep stdout for data.
+*/
+fn run(event: Event, kind: &str, lines: &[String]){
+    for cmd in commands(event){
+        let child = Command::new("sh").arg("-c").arg(cmd)
+            .env("PPLANNER_EVENT", event.key())
+            .env("PPLANNER_KIND", kind)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::from(std::io::stderr()))
+            .spawn();
+        let mut child = match child{
+            Ok(x) => x,
+            Err(e) =>{
+                conz::print_type(format!("Warning: hook {} could not run: ", event.key()), conz::MsgType::Error);
+                conz::println_type(e, conz::MsgType::Highlight);
+                continue;
+            }
+        };
+        if let Some(mut stdin) = child.stdin.take(){
+            //a hook that does not read its input is fine
+            for line in lines{
+                if writeln!(stdin, "{}", line).is_err() {break;}
+            }
+        }
+        match child.wait(){
+            Ok(x) if x.success() => {}
+            Ok(x) =>{
+                conz::print_type(format!("Warning: hook {} failed with {}: ", event.key(), x), conz::MsgType::Error);
+                conz::println_type(cmd, conz::MsgType::Highlight);
+            }
+            Err(e) =>{
+                conz::print_type(format!("Warning: hook {} failed: ", event.key()), conz::MsgType::Error);
+                conz::println_type(e, conz::MsgType::Highlight);
+            }
+        }
+    }
+}
+
+//runs after the change is written, the change is noted to be committed when git is on, see sync
+pub fn fire<T: conz::PrettyPrintable>(event: Event, kind: &str, items: &[T]) where T::ArgType: Default{
+    if items.is_empty() {return;}
+    let arg = T::ArgType::default();
+    let keys = T::raw_keys(&arg);
+    let rows: Vec<Vec<conz::Raw>> = items.iter().map(|x| x.raw(&arg)).collect();
+    sync::note(sync::describe(event.verb(), kind, &keys, &rows));
+    if commands(event).is_empty() {return;}
+    let lines: Vec<String> = rows.iter().map(|row| support::raw_to_json(&keys, row)).collect();
+    run(event, kind, &lines);
+}
+
+pub fn fire_flush(){
+    run(Event::Flush, "", &[]);
+}
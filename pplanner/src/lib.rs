@@ -0,0 +1,30 @@
+extern crate chrono;
+extern crate num_derive;
+
+#[macro_use]
+pub mod conz;
+#[macro_use]
+pub mod support;
+pub mod parser;
+pub mod data;
+pub mod astr;
+pub mod save;
+pub mod wizard;
+pub mod state;
+pub mod commands;
+pub mod misc;
+pub mod script;
+pub mod error;
+pub mod config;
+pub mod complete;
+pub mod report;
+pub mod daemon;
+pub mod hooks;
+pub mod sync;
+pub mod merge;
+pub mod crypt;
+pub mod storage;
+pub mod sqlite;
+pub mod help;
+pub mod docs;
+pub mod synth;
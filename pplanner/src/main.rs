@@ -1,31 +1,35 @@
-extern crate chrono;
-extern crate num_derive;
-
-#[macro_use]
-mod conz;
-#[macro_use]
-mod support;
-mod parser;
-mod data;
-mod astr;
-mod save;
-mod wizard;
-mod state;
-mod commands;
-mod misc;
+use pplanner::{conz, parser, save, state, error, config, daemon, hooks, sync, merge, synth};
 
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let config = config::Config::load();
+    parser::setup_output(&args, &config);
+    hooks::setup(&config);
+    //a merge driver works on the files git gives it, not on our data
+    if args.len() > 1 && args[1] == "merge"{
+        std::process::exit(merge::run(&args[2..]));
+    }
+    //hidden, writes made up data to measure with into a directory of its own
+    if args.len() > 1 && args[1] == "gen-data"{
+        std::process::exit(synth::run(&args[2..]));
+    }
     let ok = save::setup_config_dir();
-    if !ok {return;}
+    if !ok {
+        std::process::exit(error::CmdError::Io.exit_code());
+    }
+    sync::setup(&config);
     let state = state::State::new();
     if state.is_none() {
         conz::println_type("Error: Could not create state.", conz::MsgType::Error);
-        return;
+        std::process::exit(error::CmdError::Io.exit_code());
     }
-    let mut parser = parser::Parser::new(state.unwrap());
-    let args: Vec<String> = std::env::args().collect();
-    if args.len() > 1 {
-        parser::process_cli_args(args, &mut parser);
+    if args.len() > 1 && args[1] == "daemon"{
+        std::process::exit(daemon::run(state.unwrap(), &config, &args[2..]));
+    }
+    let mut parser = parser::Parser::new(state.unwrap(), &config);
+    if parser::has_actions(&args) {
+        let code = parser::process_cli_args(args, &mut parser);
+        std::process::exit(code);
     }else{
         parser.start_loop();
     }
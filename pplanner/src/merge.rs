@@ -0,0 +1,511 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::conz;
+use super::data;
+use super::astr;
+use super::save;
+use super::support;
+use super::error;
+use super::crypt;
+
+//records with the same identity are versions of the same thing, like a point and its edit
+pub trait Mergeable{
+    fn identity(&self) -> astr::Astr;
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Choice{
+    Ours,
+    Theirs,
+    Both,
+}
+
+impl Choice{
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(string: &str) -> Option<Choice>{
+        match string{
+            "o" | "ours" | "--ours" => Option::Some(Choice::Ours),
+            "t" | "theirs" | "--theirs" => Option::Some(Choice::Theirs),
+            "b" | "both" | "--both" => Option::Some(Choice::Both),
+            _ => Option::None,
+        }
+    }
+}
+
+enum Change<T>{
+    Keep,
+    Delete,
+    Replace(T),
+}
+
+fn encode<T: save::Bufferable>(item: &T) -> save::Buffer{
+    let mut buf = Vec::new();
+    item.into_buffer(&mut buf);
+    buf
+}
+
+/*
+What one side did to each base record, and what it added.
+Records that are still there byte for byte are kept,
+the rest is paired on identity in order: paired is an edit, unpaired a delete or an add.
+*/
+fn diff<T: save::Bufferable + Mergeable + Clone>(base: &[T], side: &[T]) -> (Vec<Change<T>>, Vec<T>){
+    let mut unused: HashMap<save::Buffer, VecDeque<usize>> = HashMap::new();
+    for (i, x) in side.iter().enumerate(){
+        unused.entry(encode(x)).or_default().push_back(i);
+    }
+    let mut changes = Vec::new();
+    let mut left_base = Vec::new();
+    for (i, x) in base.iter().enumerate(){
+        match unused.get_mut(&encode(x)).and_then(|q| q.pop_front()){
+            Option::Some(_) => changes.push(Change::Keep),
+            Option::None =>{
+                changes.push(Change::Delete);
+                left_base.push(i);
+            }
+        }
+    }
+    let mut left_side: Vec<Option<usize>> = unused.into_values().flatten().map(Option::Some).collect();
+    left_side.sort();
+    for i in left_base{
+        let id = base[i].identity();
+        let pos = left_side.iter().position(|j| j.map(|j| side[j].identity() == id).unwrap_or(false));
+        if let Some(pos) = pos{
+            changes[i] = Change::Replace(side[left_side[pos].unwrap()].clone());
+            left_side[pos] = Option::None;
+        }
+    }
+    let added = left_side.into_iter().flatten().map(|j| side[j].clone()).collect();
+    (changes, added)
+}
+
+fn apply<T: Clone>(change: &Change<T>, base: &T, res: &mut Vec<T>){
+    match change{
+        Change::Keep => res.push(base.clone()),
+        Change::Delete => {}
+        Change::Replace(x) => res.push(x.clone()),
+    }
+}
+
+fn show<T: conz::PrettyPrintable>(label: &str, item: Option<&T>) where T::ArgType: Default{
+    conz::print_type(label, conz::MsgType::Highlight);
+    match item{
+        Option::Some(x) =>{
+            conz::println_type("", conz::MsgType::Normal);
+            support::pretty_print(std::slice::from_ref(x), &T::ArgType::default());
+        }
+        Option::None => conz::println_type(" removed", conz::MsgType::Error),
+    }
+}
+
+//the fixed choice, or ask; no answer means the conflict stays
+fn choose<T: conz::PrettyPrintable>(name: &str, fixed: Option<Choice>, base: Option<&T>, ours: Option<&T>,
+    theirs: Option<&T>) -> Option<Choice> where T::ArgType: Default{
+    if fixed.is_some() {return fixed;}
+    conz::print_type("Conflict in ", conz::MsgType::Error);
+    conz::println_type(name, conz::MsgType::Highlight);
+    if base.is_some() {show("base:", base);}
+    show("ours:", ours);
+    show("theirs:", theirs);
+    loop{
+        let answer = conz::prompt("Keep ours, theirs or both? o/t/b: ").unwrap_or_default();
+        if answer.is_empty() {return Option::None;}
+        if let Some(x) = Choice::from_str(answer.trim()) {return Option::Some(x);}
+    }
+}
+
+//archives are kept in the order things were archived in, the other files are sorted
+fn is_archive(name: &str) -> bool{
+    [save::POINT_ARCHIVE_DIR, save::PLAN_ARCHIVE_DIR, save::SLICE_ARCHIVE_DIR, save::TODO_ARCHIVE_DIR].contains(&name)
+}
+
+/*
+Three way merge on records.
+Changes made by one side are taken, the same change made by both sides is taken once.
+A record edited by both sides in different ways, or edited by one and removed by the other,
+and records with the same identity added by both are conflicts.
+Those are resolved with the fixed choice, or by asking.
+The result is sorted, an archive is in base order followed by what ours and then theirs added.
+None when a conflict is left unresolved.
+*/
+pub fn merge<T>(name: &str, base: &[T], ours: &[T], theirs: &[T], fixed: Option<Choice>) -> Option<Vec<T>>
+    where T: save::Bufferable + Mergeable + conz::PrettyPrintable + Ord + Clone, T::ArgType: Default{
+    let (ochanges, oadded) = diff(base, ours);
+    let (tchanges, tadded) = diff(base, theirs);
+    let mut res = Vec::new();
+    for (i, b) in base.iter().enumerate(){
+        match (&ochanges[i], &tchanges[i]){
+            (Change::Keep, x) | (x, Change::Keep) => apply(x, b, &mut res),
+            (Change::Delete, Change::Delete) => {}
+            (Change::Replace(o), Change::Replace(t)) if encode(o) == encode(t) => res.push(o.clone()),
+            (o, t) =>{
+                let as_item = |c: &Change<T>| match c{
+                    Change::Replace(x) => Option::Some(x.clone()),
+                    _ => Option::None,
+                };
+                let (oi, ti) = (as_item(o), as_item(t));
+                match choose(name, fixed, Option::Some(b), oi.as_ref(), ti.as_ref())?{
+                    Choice::Ours => apply(o, b, &mut res),
+                    Choice::Theirs => apply(t, b, &mut res),
+                    Choice::Both =>{
+                        apply(o, b, &mut res);
+                        apply(t, b, &mut res);
+                    }
+                }
+            }
+        }
+    }
+    //added by both, byte for byte, is added once
+    let mut tadded: Vec<Option<T>> = save::merge_records(&oadded, &[], &tadded).into_iter().map(Option::Some).collect();
+    for o in oadded{
+        let pos = tadded.iter().position(|t| t.as_ref().map(|t| t.identity() == o.identity()).unwrap_or(false));
+        let t = match pos{
+            Option::Some(p) => tadded[p].take().unwrap(),
+            Option::None =>{
+                res.push(o);
+                continue;
+            }
+        };
+        match choose(name, fixed, Option::None, Option::Some(&o), Option::Some(&t))?{
+            Choice::Ours => res.push(o),
+            Choice::Theirs => res.push(t),
+            Choice::Both =>{
+                res.push(o);
+                res.push(t);
+            }
+        }
+    }
+    res.extend(tadded.into_iter().flatten());
+    if !is_archive(name) {res.sort();}
+    Option::Some(res)
+}
+
+fn merge_buffers<T>(name: &str, base: &save::Buffer, ours: &save::Buffer, theirs: &save::Buffer,
+    fixed: Option<Choice>) -> Result<save::Buffer, error::CmdError>
+    where T: save::Bufferable + Mergeable + conz::PrettyPrintable + Ord + Clone, T::ArgType: Default{
+    let decode = |buf: &save::Buffer, side: &str| match save::decode_records::<T>(buf){
+        Option::Some(x) => Ok(x),
+        Option::None =>{
+            conz::print_type(format!("Error: the {} version can not be read: ", side), conz::MsgType::Error);
+            conz::println_type(name, conz::MsgType::Highlight);
+            Err(error::CmdError::BadInput)
+        }
+    };
+    let (base, ours, theirs) = (decode(base, "base")?, decode(ours, "our")?, decode(theirs, "their")?);
+    match merge(name, &base, &ours, &theirs, fixed){
+        Option::Some(x) => Ok(save::encode_records(&x)),
+        Option::None => Err(error::CmdError::Conflict),
+    }
+}
+
+//name is the name of the data file, it says what kind of records are in it
+pub fn merge_named(name: &str, base: &save::Buffer, ours: &save::Buffer, theirs: &save::Buffer,
+    fixed: Option<Choice>) -> Result<save::Buffer, error::CmdError>{
+    match name{
+        save::POINT_DIR | save::POINT_ARCHIVE_DIR => merge_buffers::<data::Point>(name, base, ours, theirs, fixed),
+        save::PLAN_DIR | save::PLAN_ARCHIVE_DIR => merge_buffers::<data::Plan>(name, base, ours, theirs, fixed),
+        save::SLICE_DIR | save::SLICE_ARCHIVE_DIR => merge_buffers::<data::Slice>(name, base, ours, theirs, fixed),
+        save::TODO_DIR | save::TODO_ARCHIVE_DIR => merge_buffers::<data::Todo>(name, base, ours, theirs, fixed),
+        save::SESSION_DIR => merge_buffers::<data::Session>(name, base, ours, theirs, fixed),
+        save::HABIT_DIR => merge_buffers::<data::Habit>(name, base, ours, theirs, fixed),
+        _ =>{
+            conz::print_type("Error: not a pplanner data file: ", conz::MsgType::Error);
+            conz::println_type(name, conz::MsgType::Highlight);
+            Err(error::CmdError::NotFound)
+        }
+    }
+}
+
+/*
+pplanner merge base ours theirs [name] [--ours|--theirs|--both]
+The result is written over ours, like git wants from a merge driver:
+merge.pplanner.driver = pplanner merge %O %A %B %P
+The name of the data file is the name of ours when it is not given.
+*/
+pub fn run(args: &[String]) -> i32{
+    let fixed = args.iter().filter_map(|a| if a.starts_with("--") {Choice::from_str(a)} else {Option::None}).next_back();
+    let paths: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    if paths.len() < 3 || paths.len() > 4{
+        conz::println_type("Error: usage: pplanner merge base ours theirs [name] [--ours|--theirs|--both]",
+            conz::MsgType::Error);
+        return 1;
+    }
+    let name = match paths.get(3){
+        Option::Some(x) => std::path::Path::new(x.as_str()),
+        Option::None => std::path::Path::new(paths[1].as_str()),
+    };
+    let name = name.file_name().and_then(|x| x.to_str()).unwrap_or("");
+    let mut buffers = Vec::new();
+    for path in &paths[..3]{
+        //the files git gives are copies, opened with the name of the data file
+        match std::fs::read(path.as_str()).ok().map(|x| crypt::open(name, &x)){
+            Option::Some(Option::Some(x)) => buffers.push(x),
+            Option::Some(Option::None) => return error::CmdError::BadInput.exit_code(),
+            Option::None =>{
+                conz::println_error("", "Error: Cannot read file: ", path);
+                return error::CmdError::Io.exit_code();
+            }
+        }
+    }
+    let merged = match merge_named(name, &buffers[0], &buffers[1], &buffers[2], fixed){
+        Ok(x) => x,
+        Err(e) =>{
+            if e == error::CmdError::Conflict{
+                conz::println_type("Conflicts are left, nothing is written.", conz::MsgType::Error);
+            }
+            return e.exit_code();
+        }
+    };
+    let sealed = match crypt::seal(name, &merged){
+        Option::Some(x) => x,
+        Option::None => return error::CmdError::Io.exit_code(),
+    };
+    if std::fs::write(paths[1].as_str(), sealed).is_err(){
+        conz::println_error("", "Error: Cannot write items to file: ", paths[1]);
+        return error::CmdError::Io.exit_code();
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    /*
+    The choices are fixed, so nothing is asked.
+    Where no conflict is expected the fixed choice is ours,
+    a conflict would then drop their record and show in the result.
+    */
+
+    fn todo(title: &str, done: bool) -> data::Todo{
+        data::Todo{title: astr::from_str(title), done, estimate: 0}
+    }
+
+    //K for kept, D for deleted, R and the title for replaced
+    fn show(changes: &[Change<data::Todo>]) -> Vec<String>{
+        changes.iter().map(|c| match c{
+            Change::Keep => String::from("K"),
+            Change::Delete => String::from("D"),
+            Change::Replace(x) => format!("R{}", String::from_utf8_lossy(&x.title.0)),
+        }).collect()
+    }
+
+    fn titles(xs: &[data::Todo]) -> Vec<String>{
+        xs.iter().map(|x| String::from_utf8_lossy(&x.title.0).into_owned()).collect()
+    }
+
+    fn todos(base: &[data::Todo], ours: &[data::Todo], theirs: &[data::Todo], fixed: Choice) -> Vec<(String, bool)>{
+        merge("todos", base, ours, theirs, Option::Some(fixed)).unwrap()
+            .into_iter().map(|x| (String::from_utf8_lossy(&x.title.0).into_owned(), x.done)).collect()
+    }
+
+    fn t(title: &str, done: bool) -> (String, bool){
+        (title.to_string(), done)
+    }
+
+    fn day(d: u32, h: u32) -> data::DT{
+        data::DT::make_datetime((d, 1, 2030), (h, 0, 0)).unwrap()
+    }
+
+    fn slice(title: &str, d: u32) -> data::Slice{
+        data::Slice::from(day(d, 9), day(d, 17), astr::from_str(title), data::SliceType::Activity)
+    }
+
+    fn point(title: &str, d: u32) -> data::Point{
+        data::Point::new(day(d, 12), astr::from_str(title), astr::from_str("deadline"))
+    }
+
+    fn identities<T: Mergeable>(xs: &[T]) -> Vec<astr::Astr>{
+        xs.iter().map(|x| x.identity()).collect()
+    }
+
+    #[test]
+    fn diff_keeps_what_is_there(){
+        let base = [todo("a", false), todo("b", false)];
+        let side = [todo("b", false), todo("a", false)];
+        let (changes, added) = diff(&base, &side);
+        assert_eq!(show(&changes), ["K", "K"]);
+        assert!(added.is_empty());
+    }
+
+    #[test]
+    fn diff_pairs_edits_on_identity(){
+        let base = [todo("a", false), todo("b", false), todo("c", false)];
+        let side = [todo("a", false), todo("b", true), todo("d", false)];
+        let (changes, added) = diff(&base, &side);
+        assert_eq!(show(&changes), ["K", "Rb", "D"]);
+        assert_eq!(titles(&added), ["d"]);
+    }
+
+    #[test]
+    fn diff_pairs_each_record_once(){
+        let base = [todo("a", false), todo("a", false)];
+        let side = [todo("a", true)];
+        let (changes, added) = diff(&base, &side);
+        assert_eq!(show(&changes), ["Ra", "D"]);
+        assert!(added.is_empty());
+    }
+
+    #[test]
+    fn diff_of_copies(){
+        let base = [todo("a", false), todo("a", false)];
+        let side = [todo("a", false), todo("a", false), todo("a", false)];
+        let (changes, added) = diff(&base, &side);
+        assert_eq!(show(&changes), ["K", "K"]);
+        assert_eq!(titles(&added), ["a"]);
+    }
+
+    #[test]
+    fn diff_from_nothing(){
+        let (changes, added) = diff(&[], &[todo("a", false)]);
+        assert!(changes.is_empty());
+        assert_eq!(titles(&added), ["a"]);
+        let (changes, added) = diff(&[todo("a", false)], &[]);
+        assert_eq!(show(&changes), ["D"]);
+        assert!(added.is_empty());
+    }
+
+    #[test]
+    fn choose_takes_the_fixed_choice(){
+        let x = todo("a", false);
+        assert!(choose("todos", Option::Some(Choice::Theirs), Option::None, Option::Some(&x), Option::None)
+            == Option::Some(Choice::Theirs));
+    }
+
+    #[test]
+    fn choices_from_words(){
+        assert!(Choice::from_str("o") == Option::Some(Choice::Ours));
+        assert!(Choice::from_str("--theirs") == Option::Some(Choice::Theirs));
+        assert!(Choice::from_str("both") == Option::Some(Choice::Both));
+        assert!(Choice::from_str("mine").is_none());
+    }
+
+    #[test]
+    fn nothing_changed(){
+        let base = [todo("a", false), todo("b", true)];
+        assert_eq!(todos(&base, &base, &base, Choice::Ours), [t("a", false), t("b", true)]);
+    }
+
+    //todos that are done sort last
+    #[test]
+    fn edit_on_one_side(){
+        let base = [todo("a", false), todo("b", false)];
+        let theirs = [todo("a", true), todo("b", false)];
+        assert_eq!(todos(&base, &base, &theirs, Choice::Ours), [t("b", false), t("a", true)]);
+        assert_eq!(todos(&base, &theirs, &base, Choice::Theirs), [t("b", false), t("a", true)]);
+    }
+
+    #[test]
+    fn the_same_edit_on_both_sides(){
+        let base = [todo("a", false)];
+        let edit = [todo("a", true)];
+        assert_eq!(todos(&base, &edit, &edit, Choice::Both), [t("a", true)]);
+    }
+
+    #[test]
+    fn adds_and_removes_on_both_sides(){
+        let base = [todo("a", false), todo("b", false), todo("c", false)];
+        let ours = [todo("a", false), todo("c", false), todo("d", false)];
+        let theirs = [todo("a", false), todo("b", false), todo("e", false)];
+        assert_eq!(todos(&base, &ours, &theirs, Choice::Theirs), [t("a", false), t("d", false), t("e", false)]);
+    }
+
+    #[test]
+    fn removed_on_both_sides(){
+        let base = [todo("a", false), todo("b", false)];
+        let rest = [todo("b", false)];
+        assert_eq!(todos(&base, &rest, &rest, Choice::Both), [t("b", false)]);
+    }
+
+    #[test]
+    fn added_on_both_sides_the_same(){
+        let base = [todo("a", false)];
+        let added = [todo("a", false), todo("b", false)];
+        assert_eq!(todos(&base, &added, &added, Choice::Both), [t("a", false), t("b", false)]);
+    }
+
+    #[test]
+    fn records_that_are_there_twice(){
+        let base = [todo("a", false), todo("a", false)];
+        let ours = [todo("a", false)];
+        assert_eq!(todos(&base, &ours, &base, Choice::Both), [t("a", false)]);
+    }
+
+    #[test]
+    fn edited_in_different_ways(){
+        let base = [data::Todo{title: astr::from_str("a"), done: false, estimate: 0}];
+        let ours = [data::Todo{title: astr::from_str("a"), done: false, estimate: 30}];
+        let theirs = [data::Todo{title: astr::from_str("a"), done: true, estimate: 0}];
+        let run = |fixed| merge("todos", &base, &ours, &theirs, Option::Some(fixed)).unwrap()
+            .into_iter().map(|x| (x.done, x.estimate)).collect::<Vec<_>>();
+        assert_eq!(run(Choice::Ours), [(false, 30)]);
+        assert_eq!(run(Choice::Theirs), [(true, 0)]);
+        assert_eq!(run(Choice::Both), [(false, 30), (true, 0)]);
+    }
+
+    #[test]
+    fn edited_and_removed(){
+        let base = [todo("a", false), todo("b", false)];
+        let ours = [todo("a", true), todo("b", false)];
+        let theirs = [todo("b", false)];
+        assert_eq!(todos(&base, &ours, &theirs, Choice::Ours), [t("b", false), t("a", true)]);
+        assert_eq!(todos(&base, &ours, &theirs, Choice::Theirs), [t("b", false)]);
+        assert_eq!(todos(&base, &ours, &theirs, Choice::Both), [t("b", false), t("a", true)]);
+    }
+
+    #[test]
+    fn added_on_both_sides_in_different_ways(){
+        let ours = [todo("a", false)];
+        let theirs = [todo("a", true)];
+        assert_eq!(todos(&[], &ours, &theirs, Choice::Ours), [t("a", false)]);
+        assert_eq!(todos(&[], &ours, &theirs, Choice::Theirs), [t("a", true)]);
+        assert_eq!(todos(&[], &ours, &theirs, Choice::Both).len(), 2);
+    }
+
+    #[test]
+    fn slices_with_the_same_title_on_other_days(){
+        let base = [slice("Work", 1)];
+        let ours = [slice("Work", 1), slice("Work", 2)];
+        let theirs = [slice("Work", 1), slice("Work", 3)];
+        let res = merge("slices", &base, &ours, &theirs, Option::Some(Choice::Ours)).unwrap();
+        assert_eq!(res.len(), 3);
+        assert!(identities(&res) == identities(&[slice("Work", 1), slice("Work", 2), slice("Work", 3)]));
+    }
+
+    #[test]
+    fn slices_with_the_same_title_on_the_same_day(){
+        let ours = [slice("Work", 1)];
+        let mut other = slice("Work", 1);
+        other.end = day(1, 18);
+        let res = merge("slices", &[], &ours, &[other], Option::Some(Choice::Theirs)).unwrap();
+        assert_eq!(res.len(), 1);
+        assert!(res[0].end.dt == day(1, 18).dt);
+    }
+
+    #[test]
+    fn points_with_the_same_title_on_other_days(){
+        let ours = [point("Exam", 1)];
+        let theirs = [point("Exam", 2)];
+        let res = merge("points", &[], &ours, &theirs, Option::Some(Choice::Ours)).unwrap();
+        assert!(identities(&res) == identities(&[point("Exam", 1), point("Exam", 2)]));
+    }
+
+    #[test]
+    fn a_point_moved_on_one_side(){
+        let base = [point("Exam", 1)];
+        let theirs = [point("Exam", 2)];
+        let res = merge("points", &base, &base, &theirs, Option::Some(Choice::Ours)).unwrap();
+        assert!(identities(&res) == identities(&theirs));
+    }
+
+    #[test]
+    fn archives_keep_their_order(){
+        let base = [point("z", 5), point("a", 1)];
+        let ours = [point("z", 5), point("a", 1), point("y", 4), point("c", 3)];
+        let theirs = [point("z", 5), point("b", 2), point("a", 1)];
+        let res = merge(save::POINT_ARCHIVE_DIR, &base, &ours, &theirs, Option::Some(Choice::Ours)).unwrap();
+        assert!(identities(&res) == identities(&[point("z", 5), point("a", 1), point("y", 4), point("c", 3), point("b", 2)]));
+        let res = merge(save::POINT_DIR, &base, &ours, &theirs, Option::Some(Choice::Ours)).unwrap();
+        assert!(identities(&res) == identities(&[point("a", 1), point("b", 2), point("c", 3), point("y", 4), point("z", 5)]));
+    }
+}
@@ -13,7 +13,7 @@ impl<T: Default + PartialEq> UnwrapDefault<T> for T{
 
     fn replace_if_not_default(&mut self, new: T){
         if new == Self::default() {return;}
-        std::mem::replace(self, new);
+        *self = new;
     }
 }
 
@@ -28,7 +28,7 @@ pub fn is_sorted<T: PartialOrd>(vec: &[T]) -> bool{
         if last > item {
             return false;
         }
-        last = &item;
+        last = item;
     }
     true
 }
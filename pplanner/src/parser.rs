@@ -1,18 +1,51 @@
-use std::collections::HashSet;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::VecDeque;
+use std::io::IsTerminal;
 
 use super::state;
 use super::conz;
 use super::astr;
-use super::astr::{Astr,AStr,ToAstr};
+use super::astr::{Astr,AStr};
 use super::commands;
+use super::config;
+use super::save;
+use super::complete;
+use super::help;
+use super::docs;
+use super::script;
+use super::sync;
+use super::error;
+use super::error::{CmdError};
+use super::crypt;
 
-type Func = fn(&mut state::State, astr::AstrVec, Option<VecDeque<astr::Astr>>);
+type Func = fn(&mut state::State, astr::AstrVec, Option<VecDeque<astr::Astr>>) -> error::CmdRes;
+
+//aliases and macros can expand into each other, this deep and no deeper
+const MAX_EXPANSION: usize = 16;
+//handled by the loop itself, not by the tree
+const RESERVED: [&str; 2] = ["q", "quit"];
+
+#[derive(Clone)]
+enum Leaf{
+    Builtin(Func),
+    Alias(String),  //the command it stands for, arguments are appended
+    Macro(String),  //commands separated by ;, with $1..$9 and $@
+}
+
+impl Leaf{
+    fn kind(&self) -> &'static str{
+        match self{
+            Leaf::Builtin(_) => "command",
+            Leaf::Alias(_) => "alias",
+            Leaf::Macro(_) => "macro",
+        }
+    }
+}
 
 pub struct FuncTree{
     tree: HashMap<astr::Astr, Box<FuncTree>>,
-    leaf: Option<Func>,
+    leaf: Option<Leaf>,
 }
 
 impl FuncTree{
@@ -25,130 +58,289 @@ impl FuncTree{
         )
     }
 
-    fn new_value(f: Func) -> Box<FuncTree>{
+    fn new_value(f: Leaf) -> Box<FuncTree>{
         Box::new(FuncTree{
             tree: HashMap::new(),
             leaf: Option::Some(f),
         })
     }
 
-    fn push(&mut self, key: &astr::AstrVec, f: Func){
-        fn _push(root: &mut FuncTree, key: &astr::AstrVec, index: usize, f: Func){
-            if index >= key.len() {return;}
+    //on a conflict the tree is left alone and the leaf already there is returned
+    fn push(&mut self, key: &astr::AstrVec, f: Leaf) -> Result<(), Leaf>{
+        fn _push(root: &mut FuncTree, key: &astr::AstrVec, index: usize, f: Leaf) -> Result<(), Leaf>{
+            if index >= key.len() {return Ok(());}
             let last = index == key.len() - 1;
             let res = root.tree.get_mut(&key[index]);
             match res{
                 Option::None =>{
                     if last{
                         root.tree.insert(key[index].copy_from_ref(), FuncTree::new_value(f));
+                        Ok(())
                     }else{
                         let mut subtree = FuncTree::new();
-                        _push(&mut subtree, key, index + 1, f);
+                        let res = _push(&mut subtree, key, index + 1, f);
                         root.tree.insert(key[index].copy_from_ref(), subtree);
+                        res
                     }
                 }
                 Option::Some(x) =>{
-                    if last{
-                        if x.leaf.is_none(){
-                            x.leaf.get_or_insert(f);
-                        }else{
-                            panic!("FuncTree: double element");
+                    if !last{
+                        return _push(x, key, index + 1, f);
+                    }
+                    match &x.leaf{
+                        Option::Some(old) => Err(old.clone()),
+                        Option::None => {
+                            x.leaf = Option::Some(f);
+                            Ok(())
                         }
-                    }else{
-                        _push(x, key, index + 1, f);
                     }
                 }
             }
         }
-        _push(self, key, 0, f);
+        _push(self, key, 0, f)
     }
 
-    fn find(&mut self, key: &astr::AstrVec) -> Option<Func>{
-        fn _find(root: &mut FuncTree, key: &astr::AstrVec, index: usize) -> Option<Func>{
+    fn find(&mut self, key: &astr::AstrVec) -> Option<Leaf>{
+        fn _find(root: &mut FuncTree, key: &astr::AstrVec, index: usize) -> Option<Leaf>{
             if index >= key.len() {return Option::None;}
             let last = index == key.len() - 1;
             let res = root.tree.get_mut(&key[index]);
             res.as_ref()?;
             if last{
-                res.unwrap().leaf
+                res.unwrap().leaf.clone()
             }else{
-                _find(&mut res.unwrap(), key, index + 1)
+                _find(res.unwrap(), key, index + 1)
             }
         }
         _find(self, key, 0)
     }
+
+    //all full commands in the tree, sorted
+    pub fn all_commands(&self) -> Vec<String>{
+        fn _all(root: &FuncTree, prefix: &str, res: &mut Vec<String>){
+            for (k,v) in &root.tree{
+                let name = if prefix.is_empty() {k.to_string()}
+                else {format!("{} {}", prefix, k.to_string())};
+                if v.leaf.is_some() {res.push(name.clone());}
+                _all(v, &name, res);
+            }
+        }
+        let mut res = Vec::new();
+        _all(self, "", &mut res);
+        res.sort();
+        res
+    }
+
+    /*
+    Completes the word under the cursor, before is the line up to the cursor.
+    After a ( the argument is completed as a command, for help(ls po.
+    Returns where the word starts and the candidates for it, with what should follow them.
+    */
+    pub fn complete(&self, before: &str) -> (usize, Vec<String>){
+        let base = before.rfind('(').map(|i| i + 1).unwrap_or(0);
+        let text = &before[base..];
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let ends_open = text.is_empty() || text.ends_with(char::is_whitespace);
+        let (done, partial) = if ends_open {(&words[..], "")}
+        else {(&words[..words.len() - 1], words[words.len() - 1])};
+        let mut node = self;
+        for word in done{
+            match node.tree.get(&astr::from_str(word)){
+                Option::None => return (before.len(), Vec::new()),
+                Option::Some(x) => node = x,
+            }
+        }
+        //inside the parens a full command is followed by ) not a space
+        let mut res: Vec<String> = node.tree.iter()
+            .map(|(k,v)| (k.to_string(), v))
+            .filter(|(k,_)| k.starts_with(partial))
+            .map(|(k,v)| if base > 0 && v.leaf.is_some() {k} else {format!("{} ", k)})
+            .collect();
+        res.sort();
+        (before.len() - partial.len(), res)
+    }
+
+    //length of the longest prefix of key that is a command
+    fn longest_match(&self, key: &[astr::Astr]) -> usize{
+        self.expand(key).0
+    }
+
+    /*
+    Like longest_match, but a word can also be the start of a unique command word: ls po.
+    Once the words make a command only whole words go on, the rest are its arguments.
+    Returns the length and the full command words.
+    */
+    fn expand(&self, key: &[astr::Astr]) -> (usize, astr::AstrVec){
+        let mut node = self;
+        let mut best = (0, Vec::new());
+        let mut full = Vec::new();
+        for (i, word) in key.iter().enumerate(){
+            let next = match node.tree.get_key_value(word){
+                Option::Some(x) => x,
+                Option::None if node.leaf.is_none() => {
+                    let mut starts = node.tree.iter().filter(|(k,_)| k.0.starts_with(&word.0));
+                    match (starts.next(), starts.next()){
+                        (Option::Some(x), Option::None) => x,
+                        _ => break,
+                    }
+                }
+                Option::None => break,
+            };
+            full.push(next.0.clone());
+            node = next.1;
+            if node.leaf.is_some() {best = (i + 1, full.clone());}
+        }
+        best
+    }
 }
 
 pub struct Parser{
     ftree: Box<FuncTree>,
     state: state::State,
+    editor: conz::LineEditor,
 }
 
 impl Parser {
-    pub fn new(mut state: state::State) -> Parser {
+    pub fn new(mut state: state::State, config: &config::Config) -> Parser {
         let mut ftree = FuncTree::new();
-        let mut fset = HashSet::new();
-        Parser::add("now", commands::now, &mut ftree, &mut fset);
-        Parser::add("help", commands::help, &mut ftree, &mut fset);
-        Parser::add("license", commands::license, &mut ftree, &mut fset);
-        Parser::add("ls commands", commands::ls_commands, &mut ftree, &mut fset);
-        Parser::add("ls days", commands::ls_days, &mut ftree, &mut fset);
-        Parser::add("ls months", commands::ls_months, &mut ftree, &mut fset);
-
-        Parser::add("mk point", commands::mk_point, &mut ftree, &mut fset);
-        Parser::add("ls points", commands::ls_points, &mut ftree, &mut fset);
-        Parser::add("ls points archive", commands::ls_points_archive, &mut ftree, &mut fset);
-        Parser::add("inspect point", commands::inspect_point, &mut ftree, &mut fset);
-        Parser::add("rm points", commands::rm_points, &mut ftree, &mut fset);
-        Parser::add("clean points", commands::clean_points, &mut ftree, &mut fset);
-        Parser::add("edit points", commands::edit_points, &mut ftree, &mut fset);
-
-        Parser::add("mk plan", commands::mk_plan, &mut ftree, &mut fset);
-        Parser::add("ls plans", commands::ls_plans, &mut ftree, &mut fset);
-        Parser::add("ls plans archive", commands::ls_plans_archive, &mut ftree, &mut fset);
-        Parser::add("rm plans", commands::rm_plans, &mut ftree, &mut fset);
-        Parser::add("edit plans", commands::edit_plans, &mut ftree, &mut fset);
-        Parser::add("mv plans", commands::mv_plans, &mut ftree, &mut fset);
-
-        Parser::add("mk slice", commands::mk_slice, &mut ftree, &mut fset);
-        Parser::add("ls slices", commands::ls_slices, &mut ftree, &mut fset);
-        Parser::add("ls slices archive", commands::ls_slices_archive, &mut ftree, &mut fset);
-        Parser::add("inspect slice", commands::inspect_slice, &mut ftree, &mut fset);
-        Parser::add("rm slices", commands::rm_slices, &mut ftree, &mut fset);
-        Parser::add("clean slices", commands::clean_slices, &mut ftree, &mut fset);
-        Parser::add("edit slices", commands::edit_slices, &mut ftree, &mut fset);
-
-        Parser::add("mk todo", commands::mk_todo, &mut ftree, &mut fset);
-        Parser::add("ls todos", commands::ls_todos, &mut ftree, &mut fset);
-        Parser::add("ls todos archive", commands::ls_todos_archive, &mut ftree, &mut fset);
-        Parser::add("rm todos", commands::rm_todos, &mut ftree, &mut fset);
-        Parser::add("clean todos", commands::clean_todos, &mut ftree, &mut fset);
-        Parser::add("tick todos", commands::tick_todos, &mut ftree, &mut fset);
-
-        Parser::add("status", commands::status, &mut ftree, &mut fset);
-        Parser::add("flush files", commands::flush_files, &mut ftree, &mut fset);
-        Parser::add("_test_keys", commands::test_keys, &mut ftree, &mut fset);
-        Parser::add("_missing_help", commands::missing_help, &mut ftree, &mut fset);
-        state.fset = fset;
+        let mut helps = BTreeMap::new();
+        Parser::add("now", commands::now, include_str!("../help/now"), &mut ftree, &mut helps);
+        Parser::add("help", commands::help, include_str!("../help/help"), &mut ftree, &mut helps);
+        Parser::add("license", commands::license, include_str!("../help/license"), &mut ftree, &mut helps);
+        Parser::add("ls commands", commands::ls_commands, include_str!("../help/ls_commands"), &mut ftree, &mut helps);
+        Parser::add("ls days", commands::ls_days, include_str!("../help/ls_days"), &mut ftree, &mut helps);
+        Parser::add("ls months", commands::ls_months, include_str!("../help/ls_months"), &mut ftree, &mut helps);
+
+        Parser::add("mk point", commands::mk_point, include_str!("../help/mk_point"), &mut ftree, &mut helps);
+        Parser::add("ls points", commands::ls_points, include_str!("../help/ls_points"), &mut ftree, &mut helps);
+        Parser::add("ls points archive", commands::ls_points_archive, include_str!("../help/ls_points_archive"), &mut ftree, &mut helps);
+        Parser::add("inspect point", commands::inspect_point, include_str!("../help/inspect_point"), &mut ftree, &mut helps);
+        Parser::add("rm points", commands::rm_points, include_str!("../help/rm_points"), &mut ftree, &mut helps);
+        Parser::add("clean points", commands::clean_points, include_str!("../help/clean_points"), &mut ftree, &mut helps);
+        Parser::add("edit points", commands::edit_points, include_str!("../help/edit_points"), &mut ftree, &mut helps);
+
+        Parser::add("mk plan", commands::mk_plan, include_str!("../help/mk_plan"), &mut ftree, &mut helps);
+        Parser::add("ls plans", commands::ls_plans, include_str!("../help/ls_plans"), &mut ftree, &mut helps);
+        Parser::add("ls plans archive", commands::ls_plans_archive, include_str!("../help/ls_plans_archive"), &mut ftree, &mut helps);
+        Parser::add("rm plans", commands::rm_plans, include_str!("../help/rm_plans"), &mut ftree, &mut helps);
+        Parser::add("edit plans", commands::edit_plans, include_str!("../help/edit_plans"), &mut ftree, &mut helps);
+        Parser::add("mv plans", commands::mv_plans, include_str!("../help/mv_plans"), &mut ftree, &mut helps);
+
+        Parser::add("mk slice", commands::mk_slice, include_str!("../help/mk_slice"), &mut ftree, &mut helps);
+        Parser::add("ls slices", commands::ls_slices, include_str!("../help/ls_slices"), &mut ftree, &mut helps);
+        Parser::add("ls slices archive", commands::ls_slices_archive, include_str!("../help/ls_slices_archive"), &mut ftree, &mut helps);
+        Parser::add("inspect slice", commands::inspect_slice, include_str!("../help/inspect_slice"), &mut ftree, &mut helps);
+        Parser::add("rm slices", commands::rm_slices, include_str!("../help/rm_slices"), &mut ftree, &mut helps);
+        Parser::add("clean slices", commands::clean_slices, include_str!("../help/clean_slices"), &mut ftree, &mut helps);
+        Parser::add("edit slices", commands::edit_slices, include_str!("../help/edit_slices"), &mut ftree, &mut helps);
+        Parser::add("report", commands::report, include_str!("../help/report"), &mut ftree, &mut helps);
+
+        Parser::add("mk todo", commands::mk_todo, include_str!("../help/mk_todo"), &mut ftree, &mut helps);
+        Parser::add("ls todos", commands::ls_todos, include_str!("../help/ls_todos"), &mut ftree, &mut helps);
+        Parser::add("ls todos archive", commands::ls_todos_archive, include_str!("../help/ls_todos_archive"), &mut ftree, &mut helps);
+        Parser::add("rm todos", commands::rm_todos, include_str!("../help/rm_todos"), &mut ftree, &mut helps);
+        Parser::add("clean todos", commands::clean_todos, include_str!("../help/clean_todos"), &mut ftree, &mut helps);
+        Parser::add("tick todos", commands::tick_todos, include_str!("../help/tick_todos"), &mut ftree, &mut helps);
+
+        Parser::add("start", commands::start, include_str!("../help/start"), &mut ftree, &mut helps);
+        Parser::add("stop", commands::stop, include_str!("../help/stop"), &mut ftree, &mut helps);
+        Parser::add("ls sessions", commands::ls_sessions, include_str!("../help/ls_sessions"), &mut ftree, &mut helps);
+        Parser::add("ls spent", commands::ls_spent, include_str!("../help/ls_spent"), &mut ftree, &mut helps);
+
+        Parser::add("mk habit", commands::mk_habit, include_str!("../help/mk_habit"), &mut ftree, &mut helps);
+        Parser::add("check habit", commands::check_habit, include_str!("../help/check_habit"), &mut ftree, &mut helps);
+        Parser::add("ls habits", commands::ls_habits, include_str!("../help/ls_habits"), &mut ftree, &mut helps);
+
+        Parser::add("sync", commands::sync, include_str!("../help/sync"), &mut ftree, &mut helps);
+        Parser::add("sync pull", commands::sync_pull, include_str!("../help/sync_pull"), &mut ftree, &mut helps);
+        Parser::add("sync push", commands::sync_push, include_str!("../help/sync_push"), &mut ftree, &mut helps);
+
+        Parser::add("encrypt data", commands::encrypt_data, include_str!("../help/encrypt_data"), &mut ftree, &mut helps);
+        Parser::add("decrypt data", commands::decrypt_data, include_str!("../help/decrypt_data"), &mut ftree, &mut helps);
+        Parser::add("migrate storage", commands::migrate_storage, include_str!("../help/migrate_storage"), &mut ftree, &mut helps);
+
+        Parser::add("status", commands::status, include_str!("../help/status"), &mut ftree, &mut helps);
+        Parser::add("flush files", commands::flush_files, include_str!("../help/flush_files"), &mut ftree, &mut helps);
+        Parser::add("_test_keys", commands::test_keys, include_str!("../help/_test_keys"), &mut ftree, &mut helps);
+        Parser::add("_missing_help", commands::missing_help, include_str!("../help/_missing_help"), &mut ftree, &mut helps);
+        state.commands = helps;
+        //reversed, so when a name is defined twice the last one wins like in the rest of the config
+        for (name, target) in config.get_prefixed("alias").into_iter().rev(){
+            Parser::add_user(name, Leaf::Alias(target.to_string()), &mut ftree);
+        }
+        for (name, body) in config.get_prefixed("macro").into_iter().rev(){
+            Parser::add_user(name, Leaf::Macro(body.to_string()), &mut ftree);
+        }
         Parser {
             ftree,
             state,
+            //typed commands can be as private as the data, with encrypted data they are not kept on disk
+            editor: conz::LineEditor::new(if crypt::enabled() {Option::None} else {save::get_data_dir_path(save::HISTORY_FILE)}),
+        }
+    }
+
+    //help is the source of the commands file in help/, see help::CmdHelp
+    fn add(name: &'static str, func: Func, help: &'static str, ftree: &mut FuncTree,
+        helps: &mut BTreeMap<astr::Astr, help::CmdHelp>){
+        let splitted = astr::from_str(name).split_str(&astr::astr_whitespace());
+        if ftree.push(&splitted, Leaf::Builtin(func)).is_err(){
+            conz::println_error("Error: command registered twice: \"", name, "\".");
         }
+        helps.insert(astr::from_str(name), help::CmdHelp::parse(name, help));
     }
 
-    fn add(name: &str, func: Func, ftree: &mut FuncTree, fset: &mut HashSet<astr::Astr>){
+    fn add_user(name: &str, leaf: Leaf, ftree: &mut FuncTree){
+        let warn = |msg: &str|{
+            conz::print_type(format!("Warning: config: {} ", leaf.kind()), conz::MsgType::Error);
+            conz::print_type(name, conz::MsgType::Highlight);
+            conz::println_type(format!(" {}", msg), conz::MsgType::Error);
+        };
+        if RESERVED.contains(&name){
+            warn("is ignored, the name is reserved.");
+            return;
+        }
+        let body = match &leaf{
+            Leaf::Alias(x) | Leaf::Macro(x) => x.clone(),
+            Leaf::Builtin(_) => return,
+        };
+        let ok = script::split_commands(&body).iter()
+            .all(|c| script::tokenize(c).map(|t| !t.is_empty()).unwrap_or(false));
+        if body.trim().is_empty() || !ok{
+            warn("is ignored, it does not expand into a valid command.");
+            return;
+        }
         let splitted = astr::from_str(name).split_str(&astr::astr_whitespace());
-        ftree.push(&splitted, func);
-        fset.insert(astr::from_str(name));
+        match ftree.push(&splitted, leaf.clone()){
+            Ok(_) => {},
+            Err(Leaf::Builtin(_)) => warn("is ignored, it conflicts with a built-in command."),
+            Err(old) => {
+                conz::print_type(format!("Warning: config: {} ", leaf.kind()), conz::MsgType::Error);
+                conz::print_type(name, conz::MsgType::Highlight);
+                conz::println_type(format!(" is defined more than once, the last {} is used.", old.kind()),
+                    conz::MsgType::Error);
+            }
+        }
+    }
+
+    pub fn command_length(&self, words: &[astr::Astr]) -> usize{
+        self.ftree.longest_match(words)
+    }
+
+    pub fn all_commands(&self) -> Vec<String>{
+        self.ftree.all_commands()
+    }
+
+    pub fn helps(&self) -> &BTreeMap<astr::Astr, help::CmdHelp>{
+        &self.state.commands
     }
 
     fn do_quit(&self) -> bool{
         if self.state.is_clean() {return true;}
         conz::println_type("Unsaved files! Do you really want to quit?\nYou can say no and try \"flush files\"", conz::MsgType::Highlight);
-        let x = conz::prompt("Quit? y/*: ");
-        match x.as_ref(){
-            "y" => true,
-            _ => false,
+        //nothing left to read, nobody to ask
+        match conz::prompt("Quit? y/*: "){
+            Option::Some(x) => x == "y",
+            Option::None => true,
         }
     }
 
@@ -176,69 +368,217 @@ impl Parser {
         conz::println_type("Made by Cody Bloemhard.", conz::MsgType::Prompt);
         conz::println_type("Type help for help on commands.", conz::MsgType::Prompt);
         loop{
-            let x = conz::prompt("cmd > ");
+            let ftree = &self.ftree;
+            let mut x = match self.editor.read_line("cmd > ", &|before| ftree.complete(before)){
+                Option::Some(x) => x,
+                //EOF, like quit but there is nobody left to ask
+                Option::None =>{
+                    if !self.state.is_clean(){
+                        conz::println_type("Warning: Quitting with unsaved files.", conz::MsgType::Error);
+                    }
+                    break;
+                }
+            };
+            if script::expand_last(&x, "").is_some(){
+                match self.editor.last().and_then(|last| script::expand_last(&x, last)){
+                    Option::None => {
+                        conz::println_type("Fail: no last command to repeat.", conz::MsgType::Error);
+                        continue;
+                    }
+                    Option::Some(expanded) => {
+                        x = expanded;
+                        conz::println_type(&x, conz::MsgType::Highlight);
+                    }
+                }
+            }
+            self.editor.add(&x);
             let y = x.as_ref();
             match y{
                 "q" => if self.do_quit() {break;},
                 "quit" => if self.do_quit() {break;},
                 _ => {
-                    if self.parse_and_run(y, Option::None) {continue;}
+                    self.state.refresh();
+                    let _ = self.parse_and_run(y, Option::None);
                 }
             }
         }
         conz::println_type("Bye!", conz::MsgType::Prompt);
     }
 
-    pub fn parse_and_run(&mut self, rawstr: &str, inputs: Option<VecDeque<astr::Astr>>) -> bool{
-        let (com,arg) = Parser::extract_args(astr::from_str(rawstr));
-        let command = com.split_str(&astr::astr_whitespace());
-        let args = arg.split_str(&astr::from_str(","));
+    pub fn parse_and_run(&mut self, rawstr: &str, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+        //words after the command are arguments too: mk point Exam --type deadline
+        let tokens = script::tokenize_typed(rawstr);
+        self.run_line(rawstr, tokens, inputs)
+    }
+
+    //a line that is already split, script lines are split more strictly than typed ones
+    pub fn run_line(&mut self, rawstr: &str, tokens: Vec<script::Token>, inputs: Option<VecDeque<astr::Astr>>) -> error::CmdRes{
+        let res = self.run_tokens(rawstr, tokens, inputs, 0);
+        //the command saved its changes, flushing commits them when git is on
+        if sync::pending() && !self.state.flush_files() && res.is_ok() {return Err(CmdError::Io);}
+        res
+    }
+
+    /*
+    Commands close to the typed words, best first, as (distance, command, words used).
+    Commands are compared word by word, so the words after them can be arguments,
+    and as a whole, so a misplaced space is one edit.
+    */
+    fn suggestions(&self, words: &[astr::Astr]) -> Vec<(usize, String, usize)>{
+        if words.is_empty() {return Vec::new();}
+        let joined = astr::unsplit(&words.to_vec(), b' ');
+        let mut res = Vec::new();
+        for name in self.ftree.all_commands(){
+            if name.starts_with('_') {continue;}
+            let cwords = astr::from_str(&name).split_str(&astr::astr_whitespace());
+            let mut best = (joined.distance(&astr::from_str(&name)), words.len());
+            if words.len() >= cwords.len(){
+                let dist = words.iter().zip(&cwords).map(|(w,c)| w.distance(c)).sum();
+                if dist < best.0 {best = (dist, cwords.len());}
+            }
+            //an ambiguous abbreviation, ls p, is close to all it could mean
+            if words.len() == cwords.len() && words.iter().zip(&cwords).all(|(w,c)| c.0.starts_with(&w.0)){
+                best = (1, words.len());
+            }
+            if best.0 <= std::cmp::max(1, name.len() / 3){
+                res.push((best.0, name, best.1));
+            }
+        }
+        res.sort();
+        res
+    }
+
+    fn run_tokens(&mut self, rawstr: &str, tokens: Vec<script::Token>, inputs: Option<VecDeque<astr::Astr>>,
+        depth: usize) -> error::CmdRes{
+        let (comstr, rest) = script::split_command(self, &tokens);
+        let rest_tokens = tokens[tokens.len() - rest.len()..].to_vec();
+        let (com,arg) = Parser::extract_args(astr::from_str(&comstr));
+        let mut command = com.split_str(&astr::astr_whitespace());
+        let (len, full) = self.ftree.expand(&command);
+        if len > 0 && len == command.len(){
+            command = full;
+        }
+        let mut args = arg.split_str(&astr::from_str(","));
+        args.extend(rest);
         let search = self.ftree.find(&command);
+        if search.is_some() && depth >= MAX_EXPANSION{
+            conz::println_error("Error: aliases and macros expand too deep, is there a loop at \"", rawstr, "\"?");
+            return Err(CmdError::BadInput);
+        }
         match search {
             Option::None => {
-                conz::println_error("Fail: Command not found: \"", &rawstr, "\"!");
-                let words = astr::from_str(rawstr).split_str(&astr::astr_whitespace());
-                let mut maxcount = 0.0;
-                let mut best = Vec::new();
-                for f in &self.state.fset{
-                    let mut count = 0.0;
-                    let splitted = f.split_str(&astr::astr_whitespace());
-                    for w in &words{
-                        for s in &splitted{
-                            if w == s {
-                                count += 4.0;
-                            }else{
-                                count += w.sameness(s);
-                            }
-                        }
-                    }
-                    count /= (words.len() * splitted.len()) as f32;
-                    if count > maxcount + 1.0{
-                        best.clear();
-                        best.push(f.clone());
-                        maxcount = count;
-                    }else if count >= maxcount{
-                        best.push(f.clone());
-                    }
+                conz::println_error("Fail: Command not found: \"", rawstr, "\"!");
+                let best = self.suggestions(&command);
+                let sure = !best.is_empty() && best[0].0 <= 2 && (best.len() == 1 || best[1].0 > best[0].0);
+                let can_ask = inputs.is_none() && !conz::is_machine() && std::io::stdin().is_terminal();
+                if sure && can_ask{
+                    let (_, name, used) = &best[0];
+                    let question = format!("Run `{}` instead? y/n: ", name);
+                    if conz::prompt(&question).as_deref() != Option::Some("y") {return Err(CmdError::NotFound);}
+                    //the words the suggestion did not replace are arguments again
+                    let mut fixed = script::tokenize(name).unwrap_or_default();
+                    fixed.extend(command[*used..].iter()
+                        .map(|w| script::Token{ text: w.to_string(), quoted: false, }));
+                    let arg_count = args.len() - rest_tokens.len();
+                    fixed.extend(args.iter().take(arg_count)
+                        .map(|a| script::Token{ text: a.to_string(), quoted: true, }));
+                    fixed.extend(rest_tokens);
+                    return self.run_tokens(name, fixed, inputs, depth + 1);
                 }
                 if !best.is_empty(){
-                    conz::println_type("Best matches: ", conz::MsgType::Normal);
-                    for b in best{
-                        conz::println_type(b.disp(), conz::MsgType::Highlight);
+                    conz::println_type("Did you mean: ", conz::MsgType::Normal);
+                    for (_, name, _) in best.iter().take(5){
+                        conz::println_type(name, conz::MsgType::Highlight);
                     }
                 }
-                return false;
+                Err(CmdError::NotFound)
             },
-            Option::Some(x) => x(&mut self.state, args, inputs),
+            Option::Some(Leaf::Builtin(x)) => x(&mut self.state, args, inputs),
+            Option::Some(Leaf::Alias(target)) => {
+                //plain words stay plain, so an alias can also stand for the start of a command
+                let mut expanded = script::tokenize(&target).unwrap_or_default();
+                let arg_count = args.len() - rest_tokens.len();
+                expanded.extend(args.iter().take(arg_count)
+                    .map(|a| script::Token{ text: a.to_string(), quoted: true, }));
+                expanded.extend(rest_tokens);
+                self.run_tokens(&target, expanded, inputs, depth + 1)
+            }
+            Option::Some(Leaf::Macro(body)) => {
+                let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+                let mut inputs = inputs;
+                for line in script::split_commands(&body){
+                    let tokens = script::substitute(script::tokenize(&line).unwrap_or_default(), &args);
+                    let tokens = match tokens{
+                        Ok(x) => x,
+                        Err(n) => {
+                            conz::print_type("Fail: macro needs more arguments, $", conz::MsgType::Error);
+                            conz::print_type(n, conz::MsgType::Highlight);
+                            conz::println_type(" is missing.", conz::MsgType::Error);
+                            return Err(CmdError::BadInput);
+                        }
+                    };
+                    //given inputs go to the first command, the rest gets none but still may not prompt
+                    let these = inputs.take();
+                    inputs = these.as_ref().map(|_| VecDeque::new());
+                    self.run_tokens(&line, tokens, these, depth + 1)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+//first argument modes that print a file or reminders instead of running commands
+const DATA_MODES: [&str; 4] = ["completions", "gen-man", "gen-docs", "daemon"];
+pub const OUTPUT_FLAGS: [&str; 6] = ["--json", "--porcelain", "--tsv", "--csv", "--color", "--no-color"];
+
+//output flags go before everything else, so even the first messages obey them
+pub fn setup_output(args: &[String], config: &config::Config){
+    let mut mode = config.get("output").and_then(conz::OutputMode::from_str);
+    if mode.is_none() && config.get("output").is_some(){
+        conz::println_type("Warning: config: output should be pretty, json, tsv or csv.", conz::MsgType::Error);
+    }
+    let mut colour = config.get("color");
+    for arg in args.iter().skip(1){
+        match arg.as_ref(){
+            "--json" => mode = Option::Some(conz::OutputMode::Json),
+            "--porcelain" | "--tsv" => mode = Option::Some(conz::OutputMode::Tsv),
+            "--csv" => mode = Option::Some(conz::OutputMode::Csv),
+            "--color" => colour = Option::Some("always"),
+            "--no-color" => colour = Option::Some("never"),
+            _ => {}
         }
-        true
     }
+    //the script, docs or reminders are the data, keep the chatter off stdout
+    if args.len() > 1 && DATA_MODES.contains(&args[1].as_ref()){
+        mode = Option::Some(conz::OutputMode::Tsv);
+    }
+    conz::set_output_mode(mode.unwrap_or(conz::OutputMode::Pretty));
+    conz::setup_colour(colour);
 }
 
-pub fn process_cli_args(args: Vec<String>, parser: &mut Parser){
+//only output flags means interactive use
+pub fn has_actions(args: &[String]) -> bool{
+    args.iter().skip(1).any(|a| !OUTPUT_FLAGS.contains(&a.as_ref()))
+}
+
+pub fn process_cli_args(args: Vec<String>, parser: &mut Parser) -> i32{
     let mut i = 1;
     let mut to_exec = "";
+    let mut script = "";
     let mut inputs = Option::None;
+    if args.len() > 1 && args[1] == "completions"{
+        return complete::print_completions(args.get(2).map(|x| x.as_ref()), &parser.all_commands());
+    }
+    //hidden, for keeping pplanner.ms and the README in sync with the commands
+    if args.len() > 1 && args[1] == "gen-man"{
+        print!("{}", docs::man(parser.helps()));
+        return 0;
+    }
+    if args.len() > 1 && args[1] == "gen-docs"{
+        print!("{}", docs::markdown(parser.helps()));
+        return 0;
+    }
     while i < args.len(){
         let arg: &str = args[i].as_ref();
         let last = i == args.len() - 1;
@@ -251,7 +591,7 @@ pub fn process_cli_args(args: Vec<String>, parser: &mut Parser){
             if last{
                 conz::println_type("Error: -e is the last argument, it needs a follow up argument with the command to execute.",
                     conz::MsgType::Error);
-                return;
+                return 1;
             }
             to_exec = args[i + 1].as_ref();
             i += 2;
@@ -260,28 +600,152 @@ pub fn process_cli_args(args: Vec<String>, parser: &mut Parser){
             if last{
                 conz::println_type("Error: -i is the last argument, it needs a follow up argument with the inputs to the command.",
                     conz::MsgType::Error);
-                return;
+                return 1;
             }
-            let splitted = args[i + 1].to_astr().split_str(&astr::from_str(","));
-            let mut res = VecDeque::new();
-            for s in splitted{
-                res.push_back(s);
+            inputs = Option::Some(script::split_inputs(&args[i + 1]));
+            i += 2;
+        }
+        else if arg == "-f"{
+            if last{
+                conz::println_type("Error: -f is the last argument, it needs a follow up argument with the script to run.",
+                    conz::MsgType::Error);
+                return 1;
             }
-            inputs = Option::Some(res);
+            script = args[i + 1].as_ref();
             i += 2;
+        }
+        else if OUTPUT_FLAGS.contains(&arg){
+            i += 1;
         }else{
             conz::print_type("Warning: redundant/unused argument: ", conz::MsgType::Error);
             conz::println_type(arg, conz::MsgType::Highlight);
             i += 1;
         }
     }
-    if to_exec != ""{
-        parser.parse_and_run(to_exec, inputs);
+    if !script.is_empty() && !to_exec.is_empty(){
+        conz::print_type("Error: use either ", conz::MsgType::Error);
+        conz::print_type("-e", conz::MsgType::Highlight);
+        conz::print_type(" or ", conz::MsgType::Error);
+        conz::print_type("-f", conz::MsgType::Highlight);
+        conz::println_type(", not both.", conz::MsgType::Error);
+        return 1;
     }
-    else if inputs.is_some(){
-        conz::print_type("Warning: There were inputs provided using flag ", conz::MsgType::Error);
-        conz::print_type("-i", conz::MsgType::Highlight);
-        conz::print_type(" while there was no command given to execute using ", conz::MsgType::Error);
-        conz::println_type("-e", conz::MsgType::Highlight);
+    if !script.is_empty(){
+        if inputs.is_some(){
+            conz::println_type("Warning: -i is ignored when running a script, put the inputs in the script.", conz::MsgType::Error);
+        }
+        return script::run_script_file(parser, script);
+    }
+    if !to_exec.is_empty(){
+        error::exit_code(parser.parse_and_run(to_exec, inputs))
+    }
+    else{
+        if inputs.is_some(){
+            conz::print_type("Warning: There were inputs provided using flag ", conz::MsgType::Error);
+            conz::print_type("-i", conz::MsgType::Highlight);
+            conz::print_type(" while there was no command given to execute using ", conz::MsgType::Error);
+            conz::println_type("-e", conz::MsgType::Highlight);
+        }
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn words(s: &str) -> astr::AstrVec{
+        astr::from_str(s).split_str(&astr::astr_whitespace())
+    }
+
+    fn tree(names: &[&str]) -> Box<FuncTree>{
+        let mut tree = FuncTree::new();
+        for name in names{
+            assert!(tree.push(&words(name), Leaf::Alias(name.to_string())).is_ok());
+        }
+        tree
+    }
+
+    fn expand(tree: &FuncTree, s: &str) -> (usize, String){
+        let (len, full) = tree.expand(&words(s));
+        (len, full.iter().map(|w| w.to_string()).collect::<Vec<_>>().join(" "))
+    }
+
+    fn found(tree: &mut FuncTree, s: &str) -> Option<String>{
+        match tree.find(&words(s)){
+            Option::Some(Leaf::Alias(x)) => Option::Some(x),
+            _ => Option::None,
+        }
+    }
+
+    const NAMES: [&str; 6] = ["ls points", "ls points archive", "ls plans", "mk point", "status", "sync"];
+
+    #[test]
+    fn expand_whole_words(){
+        let tree = tree(&NAMES);
+        assert_eq!(expand(&tree, "ls points"), (2, String::from("ls points")));
+        assert_eq!(expand(&tree, "ls points archive 5"), (3, String::from("ls points archive")));
+        assert_eq!(expand(&tree, "mk point Exam"), (2, String::from("mk point")));
+    }
+
+    #[test]
+    fn expand_unique_starts(){
+        let tree = tree(&NAMES);
+        assert_eq!(expand(&tree, "l poi"), (2, String::from("ls points")));
+        assert_eq!(expand(&tree, "ls poi archive"), (3, String::from("ls points archive")));
+        assert_eq!(expand(&tree, "m p"), (2, String::from("mk point")));
+        assert_eq!(expand(&tree, "st"), (1, String::from("status")));
+    }
+
+    #[test]
+    fn expand_stops_at_ambiguous_words(){
+        let tree = tree(&NAMES);
+        //points or plans
+        assert_eq!(expand(&tree, "ls p"), (0, String::new()));
+        //status or sync
+        assert_eq!(expand(&tree, "s"), (0, String::new()));
+        //after a whole command a short word is an argument
+        assert_eq!(expand(&tree, "ls points a"), (2, String::from("ls points")));
+        assert_eq!(expand(&tree, "ls poi ar"), (2, String::from("ls points")));
+        assert_eq!(expand(&tree, "ls points x"), (2, String::from("ls points")));
+    }
+
+    #[test]
+    fn expand_nothing(){
+        let tree = tree(&NAMES);
+        assert_eq!(expand(&tree, ""), (0, String::new()));
+        assert_eq!(expand(&tree, "ls"), (0, String::new()));
+        assert_eq!(expand(&tree, "rm points"), (0, String::new()));
+        assert_eq!(tree.longest_match(&words("ls plans archive")), 2);
+    }
+
+    #[test]
+    fn push_and_find(){
+        let mut tree = tree(&NAMES);
+        assert_eq!(found(&mut tree, "ls points archive"), Option::Some(String::from("ls points archive")));
+        assert_eq!(found(&mut tree, "ls"), Option::None);
+        assert_eq!(found(&mut tree, "ls poi"), Option::None);
+        match tree.push(&words("ls plans"), Leaf::Alias(String::from("other"))){
+            Err(Leaf::Alias(x)) => assert_eq!(x, "ls plans"),
+            _ => panic!("a command that is there is pushed again"),
+        }
+        assert_eq!(found(&mut tree, "ls plans"), Option::Some(String::from("ls plans")));
+    }
+
+    #[test]
+    fn all_commands_sorted(){
+        let tree = tree(&NAMES);
+        let mut names = NAMES.to_vec();
+        names.sort();
+        assert_eq!(tree.all_commands(), names);
+    }
+
+    #[test]
+    fn complete_words(){
+        let tree = tree(&NAMES);
+        assert_eq!(tree.complete("ls p"), (3, vec![String::from("plans "), String::from("points ")]));
+        assert_eq!(tree.complete("s"), (0, vec![String::from("status "), String::from("sync ")]));
+        assert_eq!(tree.complete("help(ls pl"), (8, vec![String::from("plans")]));
+        assert_eq!(tree.complete("rm "), (3, Vec::new()));
     }
 }
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+
+use super::conz;
+use super::data;
+use super::astr;
+use super::astr::{AStr,ToAstr};
+
+//time spent in one group, like one slice type or one week
+pub struct ReportRow{
+    pub group: &'static str,
+    pub key: astr::Astr,
+    pub secs: u64,
+}
+
+impl ReportRow{
+    fn span(&self) -> data::Span{
+        data::Span::from_secs(self.secs, false)
+    }
+
+    fn str_hours(&self) -> astr::Astr{
+        format!("{:.2}", self.secs as f64 / data::Span::SECS_HOUR as f64).to_astr()
+    }
+}
+
+impl conz::PrettyPrintable for ReportRow{
+    type ArgType = u8;
+    fn pretty_print(&self, _: &Self::ArgType) -> (astr::AstrVec,Vec<conz::MsgType>){
+        (vec![astr::from_str(self.group), self.key.clone(), self.span().string_hm().to_astr(), self.str_hours()],
+        vec![conz::MsgType::Normal, conz::MsgType::Highlight, conz::MsgType::Value, conz::MsgType::Value])
+    }
+
+    fn lengths(_: &Self::ArgType) -> Vec<u16>{
+        vec![6,32,9,8]
+    }
+
+    fn titles(_: &Self::ArgType) -> Vec<astr::Astr>{
+        vec![astr::from_str("Group:"),
+            astr::from_str("Key:"),
+            astr::from_str("Time:"),
+            astr::from_str("Hours:"),]
+    }
+
+    fn raw(&self, _: &Self::ArgType) -> Vec<conz::Raw>{
+        vec![conz::Raw::Text(astr::from_str(self.group)),
+            conz::Raw::Text(self.key.clone()),
+            conz::Raw::Num(self.secs as i64),
+            conz::Raw::Text(self.str_hours()),]
+    }
+
+    fn raw_keys(_: &Self::ArgType) -> Vec<&'static str>{
+        vec!["group", "key", "secs", "hours"]
+    }
+}
+
+/*
+Sums the time of the slices between from and to.
+Slices are cut to the range and at midnight,
+so a night shift counts for both days it is in.
+*/
+pub fn build(slices: &[data::Slice], from: &data::DT, to: &data::DT) -> Vec<ReportRow>{
+    let mut types: BTreeMap<astr::Astr, u64> = BTreeMap::new();
+    let mut titles: BTreeMap<astr::Astr, u64> = BTreeMap::new();
+    let mut days: BTreeMap<astr::Astr, u64> = BTreeMap::new();
+    let mut weeks: BTreeMap<astr::Astr, u64> = BTreeMap::new();
+    let mut total = 0;
+    for slice in slices{
+        let mut start = std::cmp::max(&slice.start, from).clone();
+        let end = std::cmp::min(&slice.end, to);
+        while start < *end{
+            let next = match start.next_midnight(){
+                Option::Some(x) => std::cmp::min(x, end.clone()),
+                Option::None => end.clone(),
+            };
+            let span = start.diff(&next);
+            let secs = if span.neg {0} else {span.total_secs};
+            let day = start.str_iso_date().concat(astr::from_str(" ")).concat(start.str_dayname_short());
+            *types.entry(slice.stype.to_astr()).or_insert(0) += secs;
+            *titles.entry(slice.title.clone()).or_insert(0) += secs;
+            *days.entry(day).or_insert(0) += secs;
+            *weeks.entry(start.str_iso_week()).or_insert(0) += secs;
+            total += secs;
+            start = next;
+        }
+    }
+    let mut rows = Vec::new();
+    for (group, map) in [("type", types), ("title", titles), ("day", days), ("week", weeks)]{
+        for (key, secs) in map{
+            rows.push(ReportRow{group, key, secs});
+        }
+    }
+    rows.push(ReportRow{group: "total", key: astr::Astr::new(), secs: total});
+    rows
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //3 february 2030 is a sunday, the last day of week 5
+    fn at(d: u32, h: u32) -> data::DT{
+        data::DT::make_datetime((d, 2, 2030), (h, 0, 0)).unwrap()
+    }
+
+    fn slice(title: &str, from: (u32, u32), to: (u32, u32)) -> data::Slice{
+        data::Slice::from(at(from.0, from.1), at(to.0, to.1), astr::from_str(title), data::SliceType::Activity)
+    }
+
+    fn hours(rows: &[ReportRow], group: &str) -> Vec<(String, u64)>{
+        rows.iter().filter(|r| r.group == group)
+            .map(|r| (String::from_utf8_lossy(&r.key.0).into_owned(), r.secs / 3600)).collect()
+    }
+
+    fn h(key: &str, hours: u64) -> (String, u64){
+        (key.to_string(), hours)
+    }
+
+    #[test]
+    fn night_shift_counts_for_both_days(){
+        let rows = build(&[slice("shift", (3, 22), (4, 6))], &at(1, 0), &at(8, 0));
+        assert_eq!(hours(&rows, "day"), vec![h("2030-02-03 Sun", 2), h("2030-02-04 Mon", 6)]);
+        assert_eq!(hours(&rows, "week"), vec![h("2030-W05", 2), h("2030-W06", 6)]);
+        assert_eq!(hours(&rows, "title"), vec![h("shift", 8)]);
+        assert_eq!(hours(&rows, "type"), vec![h("Activity", 8)]);
+        assert_eq!(hours(&rows, "total"), vec![h("", 8)]);
+    }
+
+    #[test]
+    fn slices_are_cut_to_the_range(){
+        let slices = [
+            slice("early", (2, 23), (3, 1)),
+            slice("shift", (3, 22), (4, 6)),
+            slice("late", (4, 23), (5, 2)),
+            slice("outside", (6, 9), (6, 17)),
+        ];
+        let rows = build(&slices, &at(3, 0), &at(5, 0));
+        assert_eq!(hours(&rows, "day"), vec![h("2030-02-03 Sun", 3), h("2030-02-04 Mon", 7)]);
+        assert_eq!(hours(&rows, "title"), vec![h("early", 1), h("late", 1), h("shift", 8)]);
+        assert_eq!(hours(&rows, "total"), vec![h("", 10)]);
+    }
+
+    #[test]
+    fn a_range_inside_a_slice(){
+        let rows = build(&[slice("shift", (3, 22), (4, 6))], &at(3, 23), &at(4, 1));
+        assert_eq!(hours(&rows, "day"), vec![h("2030-02-03 Sun", 1), h("2030-02-04 Mon", 1)]);
+        assert_eq!(hours(&rows, "total"), vec![h("", 2)]);
+    }
+}
@@ -1,9 +1,13 @@
 use std::io::prelude::*;
+use std::io::SeekFrom;
 use std::fs::OpenOptions;
+use std::collections::HashMap;
 use simpleio as sio;
 
 use super::conz;
 use super::misc;
+use super::crypt;
+use super::storage;
 
 pub const DATA_DIR: &str = "pplanner";
 pub const POINT_DIR: &str = "points";
@@ -14,6 +18,15 @@ pub const SLICE_DIR: &str = "slices";
 pub const SLICE_ARCHIVE_DIR: &str = "slices_archive";
 pub const TODO_DIR: &str = "todos";
 pub const TODO_ARCHIVE_DIR: &str = "todos_archive";
+pub const SESSION_DIR: &str = "sessions";
+pub const HABIT_DIR: &str = "habits";
+pub const REMINDER_DIR: &str = "reminders";
+pub const HISTORY_FILE: &str = "history";
+pub const CRYPT_FILE: &str = "crypt";
+pub const DATABASE_FILE: &str = "pplanner.db";
+//all files with records, the ones that are encrypted
+pub const DATA_FILES: [&str; 11] = [POINT_DIR, POINT_ARCHIVE_DIR, PLAN_DIR, PLAN_ARCHIVE_DIR, SLICE_DIR,
+    SLICE_ARCHIVE_DIR, TODO_DIR, TODO_ARCHIVE_DIR, SESSION_DIR, HABIT_DIR, REMINDER_DIR];
 
 pub fn get_data_dir_path(relative: &str) -> Option<std::path::PathBuf>{
     let confd = sio::get_config();
@@ -40,7 +53,7 @@ fn setup_file(p: &str){
         conz::println_type(pathstr, conz::MsgType::Value);
     }
     else {
-        conz::println_error("", "Error: Could not create file: ", &pathstr);
+        conz::println_error("", "Error: Could not create file: ", pathstr);
         conz::println_type(pathstr, conz::MsgType::Value);
     }
 }
@@ -66,27 +79,26 @@ pub fn setup_config_dir() -> bool{
             conz::println_type(pathstr, conz::MsgType::Value);
         },
         sio::DirStatus::Error =>{
-            conz::println_error("", "Error: Could not create path: ", &pathstr);
+            conz::println_error("", "Error: Could not create path: ", pathstr);
             return false;
         },
         _ =>{
-            println!("{}", path.display());
+            conz::println_type(pathstr, conz::MsgType::Normal);
         },
     }
-    setup_file(POINT_DIR);
-    setup_file(POINT_ARCHIVE_DIR);
-    setup_file(PLAN_DIR);
-    setup_file(PLAN_ARCHIVE_DIR);
-    setup_file(SLICE_DIR);
-    setup_file(SLICE_ARCHIVE_DIR);
-    setup_file(TODO_DIR);
-    setup_file(TODO_ARCHIVE_DIR);
+    //in the database the records do not have files
+    let sqlite = storage::Backend::current() == storage::Backend::Sqlite;
+    for name in DATA_FILES.iter(){
+        if sqlite && storage::STORED_FILES.contains(name) {continue;}
+        setup_file(name);
+    }
     true
 }
 
 pub type Buffer = Vec<u8>;
 
 pub trait Bufferable where Self: std::marker::Sized{
+    #[allow(clippy::wrong_self_convention)]
     fn into_buffer(&self, vec: &mut Buffer);
     fn from_buffer(vec: &Buffer, iter: &mut u32) -> Option<Self>;
 }
@@ -102,7 +114,7 @@ impl Bufferable for u32{
     fn from_buffer(vec: &Buffer, iter: &mut u32) -> Option<Self>{
         if (vec.len() as i32) - (*iter as i32) < 4 {return Option::None;}
         let mut val: u32 = 0;
-        val += u32::from(vec[(*iter + 0) as usize]) << 24;
+        val += u32::from(vec[*iter as usize]) << 24;
         val += u32::from(vec[(*iter + 1) as usize]) << 16;
         val += u32::from(vec[(*iter + 2) as usize]) << 8;
         val += u32::from(vec[(*iter + 3) as usize]);
@@ -111,6 +123,19 @@ impl Bufferable for u32{
     }
 }
 
+impl Bufferable for u64{
+    fn into_buffer(&self, vec: &mut Buffer){
+        ((*self >> 32) as u32).into_buffer(vec);
+        (*self as u32).into_buffer(vec);
+    }
+
+    fn from_buffer(vec: &Buffer, iter: &mut u32) -> Option<Self>{
+        let high = u32::from_buffer(vec, iter)?;
+        let low = u32::from_buffer(vec, iter)?;
+        Option::Some(u64::from(high) << 32 | u64::from(low))
+    }
+}
+
 impl Bufferable for u16{
     fn into_buffer(&self, vec: &mut Buffer){
         vec.push(((*self >> 8) & 0xff) as u8);
@@ -120,7 +145,7 @@ impl Bufferable for u16{
     fn from_buffer(vec: &Buffer, iter: &mut u32) -> Option<Self>{
         if (vec.len() as i32) - (*iter as i32) < 2 {return Option::None;}
         let mut val: u16 = 0;
-        val += u16::from(vec[(*iter + 0) as usize]) << 8;
+        val += u16::from(vec[*iter as usize]) << 8;
         val += u16::from(vec[(*iter + 1) as usize]);
         *iter += 2;
         Option::Some(val)
@@ -146,34 +171,380 @@ pub fn buffer_append_buffer(vec: &mut Buffer, string: &Buffer){
     }
 }
 
+//the name of a data file, encrypted files are bound to it
+fn file_name(path: &std::path::Path) -> &str{
+    path.file_name().and_then(|x| x.to_str()).unwrap_or("")
+}
+
 pub fn buffer_write_file(path: &std::path::Path, vec: &Buffer) -> bool{
+    let sealed = match crypt::seal(file_name(path), vec){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
     let file = OpenOptions::new().write(true).create(true).truncate(true).open(path);
     if file.is_err() { return false; }
     let mut opened = file.unwrap();
-    if opened.write_all(&vec).is_err() {return false;}
+    if opened.write_all(&sealed).is_err() {return false;}
     true
 }
 
-pub fn buffer_write_file_append(path: &std::path::Path, vec: &Buffer) -> bool{
-    let file = OpenOptions::new().write(true).create(true).append(true).open(path);
-    if file.is_err() { return false; }
-    let mut opened = file.unwrap();
-    if opened.write_all(&vec).is_err() {return false;}
+//an encrypted file can not be appended to, it is read and written again with vec after it
+fn rewrite_appended(opened: &mut std::fs::File, path: &std::path::Path, vec: &Buffer) -> bool{
+    let mut raw: Buffer = Vec::new();
+    if opened.seek(SeekFrom::Start(0)).is_err() || opened.read_to_end(&mut raw).is_err() {return false;}
+    let mut plain = match crypt::open(file_name(path), &raw){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    plain.extend_from_slice(vec);
+    //in append mode the write goes to the end, which is the start after truncating
+    match crypt::seal(file_name(path), &plain){
+        Option::Some(x) => opened.set_len(0).is_ok() && opened.write_all(&x).is_ok(),
+        Option::None => false,
+    }
+}
+
+/*
+Writes next to the file and renames it over, so the file is always whole,
+the old or the new version, even when we are stopped halfway.
+*/
+pub fn replace_file(path: &std::path::Path, raw: &Buffer) -> bool{
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = std::path::PathBuf::from(tmp);
+    let ok = OpenOptions::new().write(true).create(true).truncate(true).open(&tmp)
+        .and_then(|mut f| f.write_all(raw).and_then(|_| f.sync_all()))
+        .and_then(|_| std::fs::rename(&tmp, path));
+    if ok.is_err(){
+        let _ = std::fs::remove_file(&tmp);
+        conz::println_error("", "Error: Cannot write file: ", &path.to_string_lossy());
+        return false;
+    }
     true
 }
 
-pub fn buffer_read_file(path: &std::path::Path) -> Option<Buffer>{
-    let file = OpenOptions::new().read(true).open(path);
-    if file.is_err() {return Option::None;}
+//encrypts or decrypts a data file in place, files that already are are left alone
+pub fn convert_file(name: &str, encrypt: bool) -> bool{
+    let path = match get_data_dir_path(name){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    if !path.exists() {return true;}
+    let file = OpenOptions::new().read(true).open(&path);
+    if file.is_err() {return false;}
     let mut opened = file.unwrap();
-    let mut vec: Buffer = Vec::new();
-    if opened.read_to_end(&mut vec).is_err() {return Option::None;}
-    Option::Some(vec)
+    if opened.lock().is_err() {return false;}
+    let mut raw: Buffer = Vec::new();
+    if opened.read_to_end(&mut raw).is_err() {return false;}
+    //the index of an archive tells where its records are and their dates, it is not kept while encrypted
+    if encrypt{
+        let index = sidecar_path(&path, INDEX_EXT);
+        if index.exists() && std::fs::remove_file(&index).is_err() {return false;}
+    }
+    let plain = match crypt::open(name, &raw){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    let res = if encrypt {crypt::encrypt(name, &plain)} else {Option::Some(plain)};
+    match res{
+        Option::Some(x) => replace_file(&path, &x),
+        Option::None => false,
+    }
+}
+
+fn encode<T: Bufferable>(item: &T) -> Buffer{
+    let mut buf = Vec::new();
+    item.into_buffer(&mut buf);
+    buf
+}
+
+//all records in the buffer, or none when it does not end on a whole record
+pub fn decode_records<T: Bufferable>(vec: &Buffer) -> Option<Vec<T>>{
+    let mut iter: u32 = 0;
+    let mut res = Vec::new();
+    while let Some(x) = T::from_buffer(vec, &mut iter){
+        res.push(x);
+    }
+    if iter as usize != vec.len() {return Option::None;}
+    Option::Some(res)
+}
+
+pub fn encode_records<T: Bufferable>(vec: &[T]) -> Buffer{
+    let mut buf = Vec::new();
+    for x in vec{
+        x.into_buffer(&mut buf);
+    }
+    buf
+}
+
+/*
+Three way merge of records, per record like diff3: when one side did not change
+how often a record is there, the other side wins.
+Records are compared on their bytes, Eq on some types only looks at the sort key.
+*/
+pub fn merge_records<T: Bufferable + Clone>(base: &[T], ours: &[T], theirs: &[T]) -> Vec<T>{
+    //how often each record is in base, ours and theirs
+    let mut counts: HashMap<Buffer, (i64, i64, i64)> = HashMap::new();
+    for x in base{
+        counts.entry(encode(x)).or_insert((0, 0, 0)).0 += 1;
+    }
+    for x in ours{
+        counts.entry(encode(x)).or_insert((0, 0, 0)).1 += 1;
+    }
+    for x in theirs{
+        counts.entry(encode(x)).or_insert((0, 0, 0)).2 += 1;
+    }
+    let mut left: HashMap<Buffer, i64> = counts.into_iter().map(|(k, (b, o, t))|{
+        let n = if o == b {t} else if t == b || o == t {o} else {std::cmp::max(0, o + t - b)};
+        (k, n)
+    }).collect();
+    let mut merged = Vec::new();
+    for x in theirs.iter().chain(ours.iter()){
+        if let Some(n) = left.get_mut(&encode(x)){
+            if *n > 0{
+                *n -= 1;
+                merged.push(x.clone());
+            }
+        }
+    }
+    merged
+}
+
+/*
+Next to each file of records is a log of the changes since the file was last written whole.
+The log starts with LOG_MAGIC and a hash of the file as it is on disk, a log with another hash
+belongs to an older version of the file and is not used.
+After that come the changes, each as a u32 length and the change, encrypted when the data is.
+A change that was not written completely, when we were stopped halfway, is dropped.
+*/
+const LOG_EXT: &str = ".log";
+const LOG_MAGIC: &[u8] = b"PPLLOG01";
+const LOG_HEADER_LEN: u64 = 16;
+//the file is written whole again when the log gets bigger than it, and this
+const LOG_MIN_COMPACT: u64 = 1 << 16;
+const OP_ADD: u8 = 1;
+const OP_REMOVE: u8 = 2;
+const OP_REPLACE: u8 = 3;
+
+//a record is known by its bytes, a remove takes away one record with the same bytes
+pub enum Op<T>{
+    Add(T),
+    Remove(T),
+    Replace(T, T),
+}
+
+impl<T: Bufferable> Op<T>{
+    fn to_buffer(&self, vec: &mut Buffer){
+        fn put<T: Bufferable>(x: &T, vec: &mut Buffer){
+            let buf = encode(x);
+            (buf.len() as u32).into_buffer(vec);
+            vec.extend(buf);
+        }
+        match self{
+            Op::Add(x) =>{
+                vec.push(OP_ADD);
+                put(x, vec);
+            }
+            Op::Remove(x) =>{
+                vec.push(OP_REMOVE);
+                put(x, vec);
+            }
+            Op::Replace(old, new) =>{
+                vec.push(OP_REPLACE);
+                put(old, vec);
+                put(new, vec);
+            }
+        }
+    }
+
+    fn from_buffer(vec: &Buffer) -> Option<Op<T>>{
+        let mut iter: u32 = 1;
+        let mut get = ||{
+            let len = u32::from_buffer(vec, &mut iter)? as usize;
+            let start = iter as usize;
+            let end = start.checked_add(len).filter(|end| *end <= vec.len())?;
+            let record = vec[start..end].to_vec();
+            iter = end as u32;
+            decode_records::<T>(&record).filter(|x| x.len() == 1)?.pop()
+        };
+        let op = match vec.first(){
+            Option::Some(&OP_ADD) => Op::Add(get()?),
+            Option::Some(&OP_REMOVE) => Op::Remove(get()?),
+            Option::Some(&OP_REPLACE) =>{
+                let old = get()?;
+                Op::Replace(old, get()?)
+            }
+            _ => return Option::None,
+        };
+        if iter as usize != vec.len() {return Option::None;}
+        Option::Some(op)
+    }
+}
+
+impl<T: Clone> Clone for Op<T>{
+    fn clone(&self) -> Self{
+        match self{
+            Op::Add(x) => Op::Add(x.clone()),
+            Op::Remove(x) => Op::Remove(x.clone()),
+            Op::Replace(old, new) => Op::Replace(old.clone(), new.clone()),
+        }
+    }
+}
+
+//fnv-1a, it is stored in the log so it has to stay the same between versions
+fn stable_hash(vec: &[u8]) -> u64{
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in vec{
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+//a file next to a data file, like its log
+fn sidecar_path(path: &std::path::Path, ext: &str) -> std::path::PathBuf{
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(ext);
+    std::path::PathBuf::from(sidecar)
+}
+
+//applies the changes to the records, an edit of a record that is not there adds it
+fn replay<T: Bufferable>(records: Vec<T>, ops: Vec<Op<T>>) -> Vec<T>{
+    let mut slots: Vec<Option<T>> = Vec::with_capacity(records.len() + ops.len());
+    let mut index: HashMap<Buffer, Vec<usize>> = HashMap::new();
+    let add = |x: T, slots: &mut Vec<Option<T>>, index: &mut HashMap<Buffer, Vec<usize>>|{
+        index.entry(encode(&x)).or_default().push(slots.len());
+        slots.push(Option::Some(x));
+    };
+    for x in records{
+        add(x, &mut slots, &mut index);
+    }
+    for op in ops{
+        let (old, new) = match op{
+            Op::Add(x) => (Option::None, Option::Some(x)),
+            Op::Remove(x) => (Option::Some(x), Option::None),
+            Op::Replace(old, new) => (Option::Some(old), Option::Some(new)),
+        };
+        if let Some(old) = old{
+            if let Some(i) = index.get_mut(&encode(&old)).and_then(|v| v.pop()){
+                slots[i] = Option::None;
+            }
+        }
+        if let Some(new) = new{
+            add(new, &mut slots, &mut index);
+        }
+    }
+    slots.into_iter().flatten().collect()
+}
+
+//modification time and length of the file and its log, to see if someone else wrote them
+type Stamp = Option<(u64, u64, std::time::SystemTime, u64, std::time::SystemTime)>;
+
+fn file_stamp(path: &std::path::Path) -> Stamp{
+    use std::os::unix::fs::MetadataExt;
+    let meta = std::fs::metadata(path).ok()?;
+    let log = std::fs::metadata(sidecar_path(path, LOG_EXT)).ok();
+    let (log_len, log_time) = match log{
+        Option::Some(x) => (x.len(), x.modified().ok()?),
+        Option::None => (0, std::time::UNIX_EPOCH),
+    };
+    //the file is replaced when it is written whole, then it is another inode
+    Option::Some((meta.ino(), meta.len(), meta.modified().ok()?, log_len, log_time))
+}
+
+//the records on disk, with where the log stands
+struct Disk<T>{
+    records: Vec<T>,
+    //the hash of the file as it is on disk, and if the log belongs to it
+    file_hash: u64,
+    file_len: u64,
+    log_ok: bool,
+    //the length of the log without a half written change at the end
+    log_len: u64,
+    stamp: Stamp,
+}
+
+/*
+Reads the file and its log, the caller has the log locked.
+Strict fails on records that can not be read completely, else they are dropped like before there was a log.
+*/
+fn read_disk<T: Bufferable>(path: &std::path::Path, log: &mut std::fs::File, strict: bool) -> Option<Disk<T>>{
+    let name = file_name(path);
+    let raw = std::fs::read(path).ok()?;
+    let plain = crypt::open(name, &raw)?;
+    let records = match decode_records(&plain){
+        Option::Some(x) => x,
+        Option::None if strict => return Option::None,
+        Option::None =>{
+            let mut iter: u32 = 0;
+            std::iter::from_fn(|| T::from_buffer(&plain, &mut iter)).collect()
+        }
+    };
+    let mut log_raw: Buffer = Vec::new();
+    if log.seek(SeekFrom::Start(0)).is_err() || log.read_to_end(&mut log_raw).is_err() {return Option::None;}
+    let file_hash = stable_hash(&raw);
+    let mut iter = LOG_HEADER_LEN as u32;
+    let log_ok = log_raw.starts_with(LOG_MAGIC) && log_raw.len() as u64 >= LOG_HEADER_LEN
+        && u64::from_buffer(&log_raw, &mut (LOG_MAGIC.len() as u32)) == Option::Some(file_hash);
+    let mut ops = Vec::new();
+    if log_ok{
+        let log_name = format!("{}{}", name, LOG_EXT);
+        while let Some(len) = u32::from_buffer(&log_raw, &mut iter.clone()){
+            let start = iter as usize + 4;
+            let end = match start.checked_add(len as usize).filter(|end| *end <= log_raw.len()){
+                Option::Some(x) => x,
+                Option::None =>{
+                    conz::println_error("", "Warning: A change was not written completely, it is dropped: ", name);
+                    break;
+                }
+            };
+            let op = crypt::open(&log_name, &log_raw[start..end]).and_then(|x| Op::from_buffer(&x));
+            match op{
+                Option::Some(x) => ops.push(x),
+                Option::None =>{
+                    conz::println_error("", "Error: The log can not be read: ", &log_name);
+                    return Option::None;
+                }
+            }
+            iter = end as u32;
+        }
+    }
+    Option::Some(Disk{
+        records: replay(records, ops),
+        file_hash,
+        file_len: raw.len() as u64,
+        log_ok,
+        log_len: if log_ok {u64::from(iter)} else {0},
+        stamp: file_stamp(path),
+    })
+}
+
+//opens the log to lock it, the file itself is replaced when written whole so it can not hold the lock
+fn open_log(path: &std::path::Path) -> Option<std::fs::File>{
+    OpenOptions::new().read(true).write(true).create(true).truncate(false).open(sidecar_path(path, LOG_EXT)).ok()
+}
+
+//the records of a file with the changes in its log
+pub fn read_records<T: Bufferable>(path: &std::path::Path) -> Option<Vec<T>>{
+    let mut log = open_log(path)?;
+    if log.lock_shared().is_err() {return Option::None;}
+    read_disk(path, &mut log, true).map(|x| x.records)
 }
 
 pub struct BufferFile<T: Bufferable + std::cmp::Ord>{
     path: std::path::PathBuf,
     content: Vec<T>,
+    //the changes to content that are not in the log yet
+    pending: Vec<Op<T>>,
+    //write it whole instead of logging the changes
+    compact: bool,
+    file_hash: u64,
+    file_len: u64,
+    log_ok: bool,
+    log_len: u64,
+    //the stamp of our last read or write, and of the last change we noticed
+    stamp: Stamp,
+    seen: Stamp,
     dirty: bool,
     loaded: bool,
     sorted: bool,
@@ -184,139 +555,273 @@ impl<T: Bufferable + std::cmp::Ord + Clone> BufferFile<T>{
         BufferFile{
             path,
             content: Vec::new(),
+            pending: Vec::new(),
+            compact: false,
+            file_hash: 0,
+            file_len: 0,
+            log_ok: false,
+            log_len: 0,
+            stamp: Option::None,
+            seen: Option::None,
             dirty: false,
             loaded: false,
             sorted: false,
         }
     }
 
-    fn content_to_buffer(vec: &[T]) -> Buffer{
+    fn take_disk(&mut self, disk: &Disk<T>){
+        self.file_hash = disk.file_hash;
+        self.file_len = disk.file_len;
+        self.log_ok = disk.log_ok;
+        self.log_len = disk.log_len;
+        self.stamp = disk.stamp;
+        self.seen = disk.stamp;
+    }
+
+    /*
+    The log is locked while we write, and we look if someone else wrote since we read.
+    If so our changes are applied to theirs, else they are appended to the log.
+    */
+    fn write_locked(&mut self) -> bool{
+        let mut log = match open_log(self.path.as_path()){
+            Option::Some(x) => x,
+            Option::None => return false,
+        };
+        if log.lock().is_err() {return false;}
+        if file_stamp(self.path.as_path()) != self.stamp && !self.merge(&mut log) {return false;}
+        let mut frames: Buffer = Vec::new();
+        let log_name = format!("{}{}", file_name(self.path.as_path()), LOG_EXT);
+        for op in &self.pending{
+            let mut buf = Vec::new();
+            op.to_buffer(&mut buf);
+            let sealed = match crypt::seal(&log_name, &buf){
+                Option::Some(x) => x,
+                Option::None => return false,
+            };
+            (sealed.len() as u32).into_buffer(&mut frames);
+            frames.extend(sealed);
+        }
+        let too_big = self.log_len + frames.len() as u64 > std::cmp::max(LOG_MIN_COMPACT, self.file_len);
+        let ok = if self.compact || too_big {self.write_whole(&mut log)} else {self.append(&mut log, &frames)};
+        if !ok {return false;}
+        drop(log);
+        self.stamp = file_stamp(self.path.as_path());
+        self.seen = self.stamp;
+        true
+    }
+
+    fn append(&mut self, log: &mut std::fs::File, frames: &Buffer) -> bool{
+        if frames.is_empty() {return true;}
         let mut buf = Vec::new();
-        for x in vec{
-            x.into_buffer(&mut buf);
+        //a log of an older file is started over
+        if !self.log_ok || self.log_len < LOG_HEADER_LEN{
+            self.log_len = 0;
+            buf.extend_from_slice(LOG_MAGIC);
+            self.file_hash.into_buffer(&mut buf);
         }
-        buf
+        buf.extend_from_slice(frames);
+        //a half written change at the end is cut off first
+        let ok = log.set_len(self.log_len).is_ok() && log.seek(SeekFrom::Start(self.log_len)).is_ok()
+            && log.write_all(&buf).is_ok() && log.sync_data().is_ok();
+        if !ok {return false;}
+        self.log_ok = true;
+        self.log_len += buf.len() as u64;
+        self.pending.clear();
+        true
+    }
+
+    /*
+    The file is replaced first, then the log is emptied.
+    Stopped in between, the log has the hash of the old file and is not used.
+    */
+    fn write_whole(&mut self, log: &mut std::fs::File) -> bool{
+        let name = file_name(self.path.as_path()).to_string();
+        let sealed = match crypt::seal(&name, &encode_records(&self.content)){
+            Option::Some(x) => x,
+            Option::None => return false,
+        };
+        if !replace_file(self.path.as_path(), &sealed) {return false;}
+        self.file_hash = stable_hash(&sealed);
+        self.file_len = sealed.len() as u64;
+        self.log_ok = false;
+        self.log_len = 0;
+        self.pending.clear();
+        self.compact = false;
+        log.set_len(0).is_ok()
+    }
+
+    //when their file can not be read completely we stop, so nothing of theirs is lost
+    fn merge(&mut self, log: &mut std::fs::File) -> bool{
+        let pathstr = self.path.to_string_lossy().to_string();
+        let disk = match read_disk::<T>(self.path.as_path(), log, true){
+            Option::Some(x) => x,
+            Option::None =>{
+                conz::println_error("", "Error: File changed on disk and can not be read, not overwriting it: ", &pathstr);
+                return false;
+            }
+        };
+        self.take_disk(&disk);
+        let mut merged = replay(disk.records, self.pending.clone());
+        merged.sort();
+        if encode_records(&merged) != encode_records(&self.content){
+            conz::println_error("", "Warning: File was changed by another process, merged the changes: ", &pathstr);
+        }
+        self.content = merged;
+        true
+    }
+
+    pub fn sort(&mut self, check: bool){
+        /*
+        Rust docs:
+        The current algorithm is an adaptive, iterative merge sort inspired by timsort.
+        It is designed to be very fast in cases where the slice is nearly sorted, or consists of two or more sorted sequences concatenated one after another.
+        *//*
+        Items get added incrementally, written sorted, when first read there sorted.
+        Should be ok-ish for our usecase.
+        */
+        if check && misc::is_sorted(&self.content){
+            self.sorted = true;
+            return;
+        }
+        self.content.sort();
+        self.sorted = true;
     }
 
-    pub fn write(&mut self) -> bool{
+    //writes the file whole and empties its log, when the log has changes
+    pub fn compact(&mut self) -> bool{
+        use storage::Storage;
+        if !self.read(false) {return false;}
+        if self.log_len <= LOG_HEADER_LEN {return true;}
+        self.compact = true;
+        self.dirty = true;
+        self.write()
+    }
+}
+
+impl<T: Bufferable + std::cmp::Ord + Clone> storage::Storage<T> for BufferFile<T>{
+    fn write(&mut self) -> bool{
         if !self.dirty{return true;}
         if !self.loaded{
             conz::println_type("Error: Nothing to write, content was never initialized.", conz::MsgType::Error);
             return false;
         }
         if !self.sorted {self.sort(false);}
-        self.dirty = !buffer_write_file(self.path.as_path(), &BufferFile::content_to_buffer(&self.content));
+        self.dirty = !self.write_locked();
         if !self.dirty {return true;}
         let pathstr = self.path.to_str();
         if let Some(pathstrv) = pathstr{
-            conz::println_error("", "Error: Cannot write items to file: ", &pathstrv);
+            conz::println_error("", "Error: Cannot write items to file: ", pathstrv);
         }else{
             conz::println_type("Error: Cannot get string from path.", conz::MsgType::Error);
         }
         false
     }
 
-    fn buffer_to_content(&mut self, vec: &Buffer){
-        let mut iter: u32 = 0;
-        self.content.clear();
-        loop{
-            let res = T::from_buffer(vec, &mut iter);
-            if res.is_none() {break;}
-            self.content.push(res.unwrap());
-        }
-        self.loaded = true;
-    }
-
-    pub fn read(&mut self, force: bool) -> bool{
-        fn _read<T: Bufferable + std::cmp::Ord + Clone>(bf: &mut BufferFile<T>) -> bool{
-            let res = buffer_read_file(bf.path.as_path());
-            match res{
-                Option::None => {
-                    let pathstr = bf.path.to_str();
-                    if let Some(pathstrv) = pathstr{
-                        conz::println_error("", "Error: Cannot read file: ", &pathstrv);
-                    }else{
-                        conz::println_type("Error: Cannot get string from path.", conz::MsgType::Error);
-                    }
-                    false
-                }
-                Option::Some(x) => {
-                    bf.buffer_to_content(&x);
-                    bf.dirty = false;
-                    true
+    //the file with the changes in its log applied
+    fn read(&mut self, force: bool) -> bool{
+        if self.loaded && !force {return true;}
+        let disk = open_log(self.path.as_path())
+            .filter(|log| log.lock_shared().is_ok())
+            .and_then(|mut log| read_disk::<T>(self.path.as_path(), &mut log, false));
+        let disk = match disk{
+            Option::Some(x) => x,
+            Option::None =>{
+                let pathstr = self.path.to_str();
+                if let Some(pathstrv) = pathstr{
+                    conz::println_error("", "Error: Cannot read file: ", pathstrv);
+                }else{
+                    conz::println_type("Error: Cannot get string from path.", conz::MsgType::Error);
                 }
+                return false;
             }
-        }
-        if !self.loaded || force {
-            if !_read(self) {return false;}
-            let sorted = misc::is_sorted(&self.content);
-            if !sorted {
-                conz::println_type("Warning: data was not stored sorted!", conz::MsgType::Error);
-                self.sort(false);
-                self.dirty = true;
-            }
+        };
+        self.take_disk(&disk);
+        self.content = disk.records;
+        self.pending.clear();
+        self.compact = false;
+        self.dirty = false;
+        self.loaded = true;
+        //a file is stored sorted, the changes in the log are in the order they were made
+        if self.log_len > LOG_HEADER_LEN {self.sort(true);}
+        else if !misc::is_sorted(&self.content){
+            conz::println_type("Warning: data was not stored sorted!", conz::MsgType::Error);
+            self.sort(false);
+            self.compact = true;
+            self.dirty = true;
         }
         true
     }
 
-    pub fn add_item(&mut self, item: T) -> bool{
+    /*
+    True when another process wrote the file or its log since we last read or wrote it.
+    Files that were never read are not checked, they are read fresh when needed.
+    */
+    fn changed_on_disk(&mut self) -> bool{
+        if !self.loaded {return false;}
+        file_stamp(self.path.as_path()) != self.seen
+    }
+
+    //applies our unsaved changes to the file on disk, they stay unsaved
+    fn merge_from_disk(&mut self) -> bool{
+        let mut log = match open_log(self.path.as_path()){
+            Option::Some(x) => x,
+            Option::None => return false,
+        };
+        if log.lock_shared().is_err() {return false;}
+        if !self.merge(&mut log) {return false;}
+        self.sorted = true;
+        true
+    }
+
+    //stop noticing the current change on disk, it is still merged when we write
+    fn ignore_disk(&mut self){
+        self.seen = file_stamp(self.path.as_path());
+    }
+
+    fn add_item(&mut self, item: T) -> bool{
         if !self.loaded && !self.read(false) {
             conz::println_type("Error: Cannot add item.", conz::MsgType::Error);
             return false;
         }
+        self.pending.push(Op::Add(item.clone()));
         self.content.push(item);
         self.dirty = true;
         self.sorted = false;
         true
     }
 
-    pub fn get_items(&mut self) -> &Vec<T>{
+    fn get_items(&mut self) -> &Vec<T>{
         if !self.loaded {self.read(false);}
         self.sort(true);
         &self.content
     }
 
-    pub fn sort(&mut self, check: bool){
-        /*
-        Rust docs:
-        The current algorithm is an adaptive, iterative merge sort inspired by timsort.
-        It is designed to be very fast in cases where the slice is nearly sorted, or consists of two or more sorted sequences concatenated one after another.
-        *//*
-        Items get added incrementally, written sorted, when first read there sorted.
-        Should be ok-ish for our usecase.
-        */
-        if check && misc::is_sorted(&self.content){
-            self.sorted = true;
-            return;
-        }
-        self.content.sort();
-        self.sorted = true;
-    }
-
-    pub fn is_clean(&self) -> bool{
+    fn is_clean(&self) -> bool{
         !self.dirty
     }
 
-    pub fn remove_indices(&mut self, mut indices: Vec<usize>) -> bool{
+    fn remove_indices(&mut self, mut indices: Vec<usize>) -> bool{
         if !misc::is_sorted(&indices){
             conz::println_type("Warning: remove_indices, should be sorted, is not.", conz::MsgType::Error);
             indices.sort();
         }
         let mut index = 0;
-        let mut vec = Vec::new();
-        for i in 0..self.content.len(){
-            if index < indices.len() && indices[index] == i {
+        let mut i = 0;
+        let pending = &mut self.pending;
+        self.content.retain(|x|{
+            let remove = index < indices.len() && indices[index] == i;
+            i += 1;
+            if remove{
                 index += 1;
-                continue;
+                pending.push(Op::Remove(x.clone()));
             }
-            vec.push(self.content[i].clone());
-        }
-        self.content = vec;
+            !remove
+        });
         self.dirty = true;
         self.write()
     }
 
-    pub fn replace(&mut self, indices: Vec<usize>, replacements: Vec<T>) -> bool{
+    fn replace(&mut self, indices: Vec<usize>, replacements: Vec<T>) -> bool{
         if !misc::is_sorted(&indices){
             conz::println_type("Error: replace, indices should be sorted, is not.", conz::MsgType::Error);
             return false;
@@ -325,13 +830,10 @@ impl<T: Bufferable + std::cmp::Ord + Clone> BufferFile<T>{
             conz::println_type("Error: save::replace: indices.len() != replacements.len().", conz::MsgType::Error);
             return false;
         }
-        let mut index = 0;
-        for i in 0..self.content.len(){
-            if index >= indices.len(){break;}
-            if indices[index] == i {
-                self.content[i] = replacements[index].clone();
-                index += 1;
-            }
+        for (i, new) in indices.into_iter().zip(replacements){
+            if i >= self.content.len() {break;}
+            let old = std::mem::replace(&mut self.content[i], new.clone());
+            self.pending.push(Op::Replace(old, new));
         }
         self.sort(true);
         self.dirty = true;
@@ -339,13 +841,163 @@ impl<T: Bufferable + std::cmp::Ord + Clone> BufferFile<T>{
     }
 }
 
+/*
+Next to each archive is an index of where its records start, how long they are and their date,
+so a part of the archive is read without decoding the rest.
+The index has the inode, length and modification time of the archive it was made for,
+when the archive was changed without it, by git or an older pplanner, it is made again.
+While the data is encrypted there is no index, an encrypted archive is decrypted whole.
+*/
+const INDEX_EXT: &str = ".idx";
+const INDEX_MAGIC: &[u8] = b"PPLIDX02";
+//magic, the stamp of the archive, the length of its records and how many there are
+const INDEX_HEADER_LEN: u64 = 48;
+const INDEX_ENTRY_LEN: u64 = 21;
+
+//a record is decoded with a u32 position so its length fits in one, the archive can be longer
+#[derive(Clone, Copy)]
+struct Entry{
+    offset: u64,
+    len: u32,
+    date: Option<i64>,
+}
+
+impl Bufferable for Entry{
+    fn into_buffer(&self, vec: &mut Buffer){
+        self.offset.into_buffer(vec);
+        self.len.into_buffer(vec);
+        u8::from(self.date.is_some()).into_buffer(vec);
+        (self.date.unwrap_or(0) as u64).into_buffer(vec);
+    }
+
+    fn from_buffer(vec: &Buffer, iter: &mut u32) -> Option<Self>{
+        let offset = u64::from_buffer(vec, iter)?;
+        let len = u32::from_buffer(vec, iter)?;
+        let dated = u8::from_buffer(vec, iter)? != 0;
+        let date = u64::from_buffer(vec, iter)? as i64;
+        Option::Some(Entry{
+            offset,
+            len,
+            date: if dated {Option::Some(date)} else {Option::None},
+        })
+    }
+}
+
+//inode, length and modification time, an archive gets another one with every write
+type ArchiveStamp = (u64, u64, u64);
+
+fn archive_stamp(file: &std::fs::File) -> Option<ArchiveStamp>{
+    use std::os::unix::fs::MetadataExt;
+    let meta = file.metadata().ok()?;
+    let time = meta.modified().ok()?.duration_since(std::time::UNIX_EPOCH).ok()?;
+    Option::Some((meta.ino(), meta.len(), time.as_nanos() as u64))
+}
+
+fn index_header(stamp: ArchiveStamp, plain_len: u64, count: u64) -> Buffer{
+    let mut buf = INDEX_MAGIC.to_vec();
+    stamp.0.into_buffer(&mut buf);
+    stamp.1.into_buffer(&mut buf);
+    stamp.2.into_buffer(&mut buf);
+    plain_len.into_buffer(&mut buf);
+    count.into_buffer(&mut buf);
+    buf
+}
+
+//the length of the records and how many there are, None when the index is not for the archive as it is
+fn read_index_header(path: &std::path::Path, archive: &std::fs::File) -> Option<(u64, u64)>{
+    let stamp = archive_stamp(archive)?;
+    let mut index = std::fs::File::open(sidecar_path(path, INDEX_EXT)).ok()?;
+    let mut head = vec![0; INDEX_HEADER_LEN as usize];
+    index.read_exact(&mut head).ok()?;
+    if !head.starts_with(INDEX_MAGIC) {return Option::None;}
+    let mut iter = INDEX_MAGIC.len() as u32;
+    let mut next = || u64::from_buffer(&head, &mut iter);
+    if (next()?, next()?, next()?) != stamp {return Option::None;}
+    Option::Some((next()?, next()?))
+}
+
+//count entries from entry start on
+fn read_entries(path: &std::path::Path, start: u64, count: u64) -> Option<Vec<Entry>>{
+    let mut index = std::fs::File::open(sidecar_path(path, INDEX_EXT)).ok()?;
+    let mut buf = vec![0; (count * INDEX_ENTRY_LEN) as usize];
+    index.seek(SeekFrom::Start(INDEX_HEADER_LEN + start * INDEX_ENTRY_LEN)).ok()?;
+    index.read_exact(&mut buf).ok()?;
+    let mut iter: u32 = 0;
+    (0..count).map(|_| Entry::from_buffer(&buf, &mut iter)).collect()
+}
+
+//the entries are written before the header, stopped in between the header is not for the archive
+fn write_index_tail(path: &std::path::Path, archive: &std::fs::File, start: u64, entries: &Buffer,
+    plain_len: u64, count: u64) -> bool{
+    let stamp = match archive_stamp(archive){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    let index = OpenOptions::new().write(true).create(true).truncate(false).open(sidecar_path(path, INDEX_EXT));
+    let mut index = match index{
+        Ok(x) => x,
+        Err(_) => return false,
+    };
+    let at = INDEX_HEADER_LEN + start * INDEX_ENTRY_LEN;
+    index.set_len(at).is_ok() && index.seek(SeekFrom::Start(at)).is_ok() && index.write_all(entries).is_ok()
+        && index.seek(SeekFrom::Start(0)).is_ok() && index.write_all(&index_header(stamp, plain_len, count)).is_ok()
+}
+
+//where the records are read from
+enum Source{
+    //a plain archive is read at the offsets, it stays locked while it is read
+    File(std::fs::File),
+    //an encrypted archive is decrypted whole
+    Plain(Buffer),
+}
+
+//the records of an archive one by one, only the ones the index entries point to are decoded
+pub struct Records<T>{
+    path: std::path::PathBuf,
+    source: Source,
+    entries: std::vec::IntoIter<Entry>,
+    marker: std::marker::PhantomData<T>,
+}
+
+impl<T: Bufferable> Records<T>{
+    fn record(&mut self, entry: Entry) -> Option<T>{
+        let end = entry.offset.checked_add(u64::from(entry.len))? as usize;
+        let buf = match &mut self.source{
+            Source::File(file) =>{
+                let mut buf = vec![0; entry.len as usize];
+                file.seek(SeekFrom::Start(entry.offset)).ok()?;
+                file.read_exact(&mut buf).ok()?;
+                buf
+            }
+            Source::Plain(plain) => plain.get(entry.offset as usize..end)?.to_vec(),
+        };
+        decode_records(&buf).filter(|x| x.len() == 1)?.pop()
+    }
+}
+
+impl<T: Bufferable> Iterator for Records<T>{
+    type Item = T;
+
+    fn next(&mut self) -> Option<T>{
+        let entry = self.entries.next()?;
+        let res = self.record(entry);
+        if res.is_none(){
+            //it is made again on the next read
+            let _ = std::fs::remove_file(sidecar_path(self.path.as_path(), INDEX_EXT));
+            conz::println_error("", "Error: The archive does not match its index: ", &self.path.to_string_lossy());
+            self.entries = Vec::new().into_iter();
+        }
+        res
+    }
+}
+
 pub struct ArchiveFile<T: Bufferable>{
     path: std::path::PathBuf,
     content: Vec<T>,
     dirty: bool,
 }
 
-impl<T: Bufferable> ArchiveFile<T>{
+impl<T: Bufferable + storage::Indexed> ArchiveFile<T>{
     pub fn new(path: std::path::PathBuf) -> ArchiveFile<T>{
         ArchiveFile{
             path,
@@ -354,65 +1006,416 @@ impl<T: Bufferable> ArchiveFile<T>{
         }
     }
 
-    fn content_to_buffer(vec: &[T]) -> Buffer{
+    /*
+    The entries of all records, and them as they go in the index.
+    Records are decoded with a u32 position, None for an archive longer than that,
+    it can only be appended to with the index it has.
+    */
+    fn entries(plain: &Buffer) -> Option<(Vec<Entry>, Buffer)>{
+        if plain.len() > u32::MAX as usize{
+            conz::println_type("Error: the archive is too long to read whole.", conz::MsgType::Error);
+            return Option::None;
+        }
+        let mut entries = Vec::new();
         let mut buf = Vec::new();
-        for x in vec{
-            x.into_buffer(&mut buf);
+        let mut iter: u32 = 0;
+        let mut offset: u32 = 0;
+        while let Some(x) = T::from_buffer(plain, &mut iter){
+            let entry = Entry{offset: u64::from(offset), len: iter - offset, date: x.date()};
+            entry.into_buffer(&mut buf);
+            entries.push(entry);
+            offset = iter;
         }
-        buf
+        Option::Some((entries, buf))
+    }
+
+    //reads the whole archive to index it, the caller has it locked exclusive
+    fn rebuild_index(&self, archive: &mut std::fs::File) -> Option<(Vec<Entry>, u64)>{
+        let mut raw: Buffer = Vec::new();
+        archive.seek(SeekFrom::Start(0)).ok()?;
+        archive.read_to_end(&mut raw).ok()?;
+        let plain = crypt::open(file_name(self.path.as_path()), &raw)?;
+        let (entries, buf) = Self::entries(&plain)?;
+        let plain_len = plain.len() as u64;
+        let mut index = index_header(archive_stamp(archive)?, plain_len, entries.len() as u64);
+        index.extend(buf);
+        //without an index the archive can still be read, it is tried again next time
+        let _ = replace_file(&sidecar_path(self.path.as_path(), INDEX_EXT), &index);
+        Option::Some((entries, plain_len))
     }
 
-    pub fn write(&mut self) -> bool{
+    //the entries a query needs, without a date range only the last ones are read from the index
+    fn read_index(&self, archive: &std::fs::File, query: &storage::Query) -> Option<Vec<Entry>>{
+        let (_, count) = read_index_header(self.path.as_path(), archive)?;
+        let n = match query.last{
+            Option::Some(n) if !query.dated() => std::cmp::min(n as u64, count),
+            _ => count,
+        };
+        read_entries(self.path.as_path(), count - n, n)
+    }
+
+    fn index_entries(&self, archive: &mut std::fs::File, query: &storage::Query) -> Option<Vec<Entry>>{
+        if let Some(x) = self.read_index(archive, query) {return Option::Some(x);}
+        //exclusive while the index is made again, another process may have done it in the meantime
+        archive.lock().ok()?;
+        let res = match self.read_index(archive, query){
+            Option::Some(x) => Option::Some(x),
+            Option::None => self.rebuild_index(archive).map(|(x, _)| x),
+        };
+        archive.lock_shared().ok()?;
+        res
+    }
+
+    fn open_records(&self, query: &storage::Query) -> Option<Records<T>>{
+        let mut archive = std::fs::File::open(self.path.as_path()).ok()?;
+        archive.lock_shared().ok()?;
+        let (entries, source) = if crypt::enabled(){
+            //no index is kept, it would give away the dates, the archive is decrypted whole anyway
+            let mut raw: Buffer = Vec::new();
+            archive.read_to_end(&mut raw).ok()?;
+            let plain = crypt::open(file_name(self.path.as_path()), &raw)?;
+            (Self::entries(&plain)?.0, Source::Plain(plain))
+        }else{
+            let entries = self.index_entries(&mut archive, query)?;
+            let mut head = Vec::new();
+            archive.seek(SeekFrom::Start(0)).ok()?;
+            (&mut archive).take(INDEX_MAGIC.len() as u64).read_to_end(&mut head).ok()?;
+            let source = if crypt::is_sealed(&head){
+                let mut raw = head;
+                archive.read_to_end(&mut raw).ok()?;
+                Source::Plain(crypt::open(file_name(self.path.as_path()), &raw)?)
+            }else{
+                Source::File(archive)
+            };
+            (entries, source)
+        };
+        let mut entries: Vec<Entry> = entries.into_iter().filter(|x| query.matches(x.date)).collect();
+        if let Some(n) = query.last{
+            let skip = entries.len().saturating_sub(n);
+            entries.drain(..skip);
+        }
+        Option::Some(Records{
+            path: self.path.clone(),
+            source,
+            entries: entries.into_iter(),
+            marker: std::marker::PhantomData,
+        })
+    }
+
+    /*
+    The locks are advisory, only other pplanner processes respect them.
+    Writers hold an exclusive lock, readers a shared one, so no one reads a half written file.
+    The index is only written with the archive locked, the lock is released when the file is dropped.
+    */
+    fn append(&self) -> bool{
+        //read too, an encrypted file is read before it is appended to
+        let file = OpenOptions::new().create(true).read(true).append(true).open(self.path.as_path());
+        let mut archive = match file{
+            Ok(x) => x,
+            Err(_) => return false,
+        };
+        if archive.lock().is_err() {return false;}
+        let buf = encode_records(&self.content);
+        if crypt::enabled() {return rewrite_appended(&mut archive, self.path.as_path(), &buf);}
+        let (plain_len, count) = match read_index_header(self.path.as_path(), &archive){
+            Option::Some(x) => x,
+            Option::None => match self.rebuild_index(&mut archive){
+                Option::Some((entries, len)) => (len, entries.len() as u64),
+                Option::None => return false,
+            },
+        };
+        if archive.write_all(&buf).is_err() {return false;}
+        let mut entries = Vec::new();
+        let mut offset = plain_len;
+        for x in &self.content{
+            let len = encode(x).len() as u64;
+            Entry{offset, len: len as u32, date: x.date()}.into_buffer(&mut entries);
+            offset += len;
+        }
+        //the records are written, a failed index is made again on the next read
+        let _ = write_index_tail(self.path.as_path(), &archive, count, &entries, offset,
+            count + self.content.len() as u64);
+        true
+    }
+}
+
+impl<T: Bufferable + storage::Indexed + 'static> storage::Archive<T> for ArchiveFile<T>{
+    fn write(&mut self) -> bool{
         if !self.dirty{return true;}
-        self.dirty = !buffer_write_file_append(self.path.as_path(), &ArchiveFile::content_to_buffer(&self.content));
+        self.dirty = !self.append();
         if !self.dirty {
             self.content.clear();
             return true;
         }
         let pathstr = self.path.to_str();
         if let Some(pathstrv) = pathstr {
-            conz::println_error("", "Error: Cannot write items to file: ", &pathstrv);
+            conz::println_error("", "Error: Cannot write items to file: ", pathstrv);
         }else{
             conz::println_type("Error: Cannot get string from path.", conz::MsgType::Error);
         }
         false
     }
 
-    fn buffer_to_content(&mut self, vec: &Buffer) -> Vec<T>{
-        let mut iter: u32 = 0;
-        let mut ret = Vec::new();
-        loop{
-            let res = T::from_buffer(vec, &mut iter);
-            if res.is_none() {break;}
-            ret.push(res.unwrap());
-        }
-        ret
-    }
-
-    pub fn read(&mut self) -> Vec<T>{
-        let res = buffer_read_file(self.path.as_path());
-        match res{
+    fn select(&mut self, query: &storage::Query) -> Box<dyn Iterator<Item = T>>{
+        match self.open_records(query){
+            Option::Some(x) => Box::new(x),
             Option::None => {
                 let pathstr = self.path.to_str();
                 if let Some(pathstrv) = pathstr{
-                    conz::println_error("", "Error: Cannot read file: ", &pathstrv);
+                    conz::println_error("", "Error: Cannot read file: ", pathstrv);
                 }else{
                     conz::println_type("Error: Cannot get string from path.", conz::MsgType::Error);
                 }
-                Vec::new()
-            }
-            Option::Some(x) => {
-                self.buffer_to_content(&x)
+                Box::new(std::iter::empty())
             }
         }
     }
 
-    pub fn add_item(&mut self, item: T){
+    fn add_item(&mut self, item: T){
         self.content.push(item);
         self.dirty = true;
     }
 
-    pub fn is_clean(&self) -> bool{
+    fn is_clean(&self) -> bool{
         !self.dirty
     }
 }
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::astr::{self, Astr};
+    use crate::data;
+    use storage::Storage;
+
+    fn a(s: &str) -> Astr{
+        astr::from_str(s)
+    }
+
+    fn strings(xs: &[Astr]) -> Vec<String>{
+        xs.iter().map(|x| String::from_utf8_lossy(&x.0).into_owned()).collect()
+    }
+
+    fn op_bytes(op: &Op<Astr>) -> Buffer{
+        let mut buf = Vec::new();
+        op.to_buffer(&mut buf);
+        buf
+    }
+
+    //an empty data file in a directory of its own, HOME goes there too so no key file is found
+    fn data_file(name: &str) -> std::path::PathBuf{
+        static HOME: std::sync::Once = std::sync::Once::new();
+        let root = std::env::temp_dir().join(format!("pplanner-test-{}", std::process::id()));
+        HOME.call_once(|| std::env::set_var("HOME", &root));
+        let dir = root.join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("todos");
+        std::fs::write(&path, b"").unwrap();
+        path
+    }
+
+    fn file_len(path: &std::path::Path) -> u64{
+        std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+    }
+
+    fn opened(path: &std::path::Path) -> BufferFile<Astr>{
+        let mut bf = BufferFile::new(path.to_path_buf());
+        assert!(bf.read(false));
+        bf
+    }
+
+    #[test]
+    fn replay_changes(){
+        let ops = vec![Op::Add(a("d")), Op::Remove(a("b")), Op::Replace(a("c"), a("e"))];
+        assert_eq!(strings(&replay(vec![a("a"), a("b"), a("c")], ops)), ["a", "d", "e"]);
+    }
+
+    #[test]
+    fn replay_removes_one_copy(){
+        let ops = vec![Op::Remove(a("a"))];
+        assert_eq!(strings(&replay(vec![a("a"), a("a")], ops)), ["a"]);
+    }
+
+    #[test]
+    fn replay_of_records_that_are_not_there(){
+        //a remove does nothing, an edit adds
+        let ops = vec![Op::Remove(a("x")), Op::Replace(a("y"), a("z"))];
+        assert_eq!(strings(&replay(vec![a("a")], ops)), ["a", "z"]);
+    }
+
+    #[test]
+    fn replay_in_order(){
+        let ops = vec![Op::Add(a("b")), Op::Replace(a("b"), a("c")), Op::Remove(a("c")), Op::Add(a("c"))];
+        assert_eq!(strings(&replay(vec![a("a")], ops)), ["a", "c"]);
+    }
+
+    #[test]
+    fn ops_read_back(){
+        for op in [Op::Add(a("a")), Op::Remove(a("")), Op::Replace(a("old"), a("new"))]{
+            let buf = op_bytes(&op);
+            let back = Op::<Astr>::from_buffer(&buf).unwrap();
+            assert_eq!(op_bytes(&back), buf);
+        }
+    }
+
+    #[test]
+    fn ops_that_do_not_read(){
+        let buf = op_bytes(&Op::Replace(a("old"), a("new")));
+        for len in 0..buf.len(){
+            assert!(Op::<Astr>::from_buffer(&buf[..len].to_vec()).is_none());
+        }
+        let mut longer = buf.clone();
+        longer.push(0);
+        assert!(Op::<Astr>::from_buffer(&longer).is_none());
+        let mut unknown = buf;
+        unknown[0] = 9;
+        assert!(Op::<Astr>::from_buffer(&unknown).is_none());
+    }
+
+    #[test]
+    fn changes_go_to_the_log(){
+        let path = data_file("log");
+        let mut bf = opened(&path);
+        assert!(bf.add_item(a("b")) && bf.add_item(a("a")) && bf.write());
+        assert_eq!(file_len(&path), 0);
+        assert!(file_len(&sidecar_path(&path, LOG_EXT)) > LOG_HEADER_LEN);
+        assert!(bf.remove_indices(vec![1]));
+        assert!(bf.replace(vec![0], vec![a("c")]));
+        assert_eq!(strings(&read_records::<Astr>(&path).unwrap()), ["c"]);
+        assert_eq!(strings(opened(&path).get_items()), ["c"]);
+    }
+
+    #[test]
+    fn compact_writes_the_file_whole(){
+        let path = data_file("compact");
+        let mut bf = opened(&path);
+        assert!(bf.add_item(a("b")) && bf.add_item(a("a")) && bf.write());
+        assert!(bf.compact());
+        assert_eq!(file_len(&sidecar_path(&path, LOG_EXT)), 0);
+        assert_eq!(strings(&decode_records::<Astr>(&std::fs::read(&path).unwrap()).unwrap()), ["a", "b"]);
+        assert_eq!(strings(opened(&path).get_items()), ["a", "b"]);
+        //nothing in the log, nothing to do
+        let stamp = file_stamp(&path);
+        assert!(opened(&path).compact());
+        assert!(file_stamp(&path) == stamp);
+    }
+
+    #[test]
+    fn a_half_written_change_is_dropped(){
+        let path = data_file("torn");
+        let mut bf = opened(&path);
+        assert!(bf.add_item(a("a")) && bf.write());
+        let mut log = OpenOptions::new().append(true).open(sidecar_path(&path, LOG_EXT)).unwrap();
+        let mut frame = Vec::new();
+        100u32.into_buffer(&mut frame);
+        frame.extend_from_slice(&[OP_ADD, 0, 0]);
+        log.write_all(&frame).unwrap();
+        drop(log);
+        assert_eq!(strings(&read_records::<Astr>(&path).unwrap()), ["a"]);
+        //the next change goes where the half one was
+        let mut bf = opened(&path);
+        assert!(bf.add_item(a("b")) && bf.write());
+        assert_eq!(strings(opened(&path).get_items()), ["a", "b"]);
+    }
+
+    #[test]
+    fn a_log_of_another_file_is_not_used(){
+        let path = data_file("stale");
+        let mut bf = opened(&path);
+        assert!(bf.add_item(a("a")) && bf.write());
+        //written whole by someone that did not know about the log
+        assert!(replace_file(&path, &encode_records(&[a("x")])));
+        assert_eq!(strings(&read_records::<Astr>(&path).unwrap()), ["x"]);
+    }
+
+    //an empty archive next to the data file, of points so the records have a date
+    fn archive(name: &str, days: &[u32]) -> (std::path::PathBuf, ArchiveFile<data::Point>){
+        let path = data_file(name).with_file_name(POINT_ARCHIVE_DIR);
+        let mut af = ArchiveFile::new(path.clone());
+        for d in days{
+            storage::Archive::add_item(&mut af, point(*d));
+        }
+        assert!(storage::Archive::write(&mut af));
+        (path, af)
+    }
+
+    fn point(d: u32) -> data::Point{
+        let dt = data::DT::make_datetime((d, 2, 2030), (12, 0, 0)).unwrap();
+        data::Point::new(dt, a(&d.to_string()), a("deadline"))
+    }
+
+    fn day(d: u32) -> i64{
+        storage::Indexed::date(&point(d)).unwrap()
+    }
+
+    fn select(af: &mut ArchiveFile<data::Point>, query: storage::Query) -> Vec<String>{
+        let titles: Vec<Astr> = storage::Archive::select(af, &query).map(|x| x.title).collect();
+        strings(&titles)
+    }
+
+    fn last(n: usize) -> storage::Query{
+        storage::Query{last: Option::Some(n), ..Default::default()}
+    }
+
+    #[test]
+    fn archive_keeps_its_order(){
+        let (_, mut af) = archive("archive-order", &[3, 1, 2]);
+        storage::Archive::add_item(&mut af, point(4));
+        assert!(storage::Archive::write(&mut af));
+        assert_eq!(select(&mut af, Default::default()), ["3", "1", "2", "4"]);
+        assert_eq!(select(&mut af, last(2)), ["2", "4"]);
+        assert_eq!(select(&mut af, last(9)), ["3", "1", "2", "4"]);
+    }
+
+    #[test]
+    fn archive_date_range(){
+        let (_, mut af) = archive("archive-range", &[3, 1, 4, 2, 5]);
+        let range = |from: Option<u32>, to: Option<u32>, last: Option<usize>|
+            storage::Query{from: from.map(day), to: to.map(day), last};
+        assert_eq!(select(&mut af, range(Option::Some(2), Option::Some(4), Option::None)), ["3", "2"]);
+        assert_eq!(select(&mut af, range(Option::Some(4), Option::None, Option::None)), ["4", "5"]);
+        assert_eq!(select(&mut af, range(Option::None, Option::Some(3), Option::Some(1))), ["2"]);
+        assert!(select(&mut af, range(Option::Some(6), Option::None, Option::None)).is_empty());
+    }
+
+    #[test]
+    fn archive_index_made_again_after_an_outside_append(){
+        let (path, mut af) = archive("archive-stale", &[1, 2]);
+        assert_eq!(select(&mut af, Default::default()), ["1", "2"]);
+        //like git, without a lock and without the index
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&encode_records(&[point(3)])).unwrap();
+        drop(file);
+        assert_eq!(select(&mut af, last(1)), ["3"]);
+        let file = std::fs::File::open(&path).unwrap();
+        assert_eq!(read_index_header(&path, &file).map(|(_, count)| count), Option::Some(3));
+        storage::Archive::add_item(&mut af, point(4));
+        assert!(storage::Archive::write(&mut af));
+        assert_eq!(select(&mut af, Default::default()), ["1", "2", "3", "4"]);
+    }
+
+    #[test]
+    fn archive_last_decodes_only_the_last(){
+        let (path, mut af) = archive("archive-last", &[1, 2, 3]);
+        assert_eq!(select(&mut af, last(3)), ["1", "2", "3"]);
+        //the first record is broken in place and the archive keeps its stamp, so the index is used
+        let file = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        let modified = file.metadata().unwrap().modified().unwrap();
+        (&file).write_all(&[0xff; 8]).unwrap();
+        file.set_modified(modified).unwrap();
+        drop(file);
+        assert_eq!(select(&mut af, last(2)), ["2", "3"]);
+        assert!(select(&mut af, Default::default()).is_empty());
+    }
+
+    #[test]
+    fn index_entry_offsets_past_4_gib(){
+        let entry = Entry{offset: 5 << 30, len: 7, date: Option::Some(-1)};
+        let mut buf = Vec::new();
+        entry.into_buffer(&mut buf);
+        assert_eq!(buf.len() as u64, INDEX_ENTRY_LEN);
+        let back = Entry::from_buffer(&buf, &mut 0).unwrap();
+        assert_eq!((back.offset, back.len, back.date), (5 << 30, 7, Option::Some(-1)));
+    }
+}
@@ -0,0 +1,472 @@
+use std::collections::VecDeque;
+use std::io::prelude::*;
+
+use super::conz;
+use super::astr;
+use super::astr::{ToAstr};
+use super::parser;
+use super::error;
+
+#[derive(Clone)]
+pub struct Token{
+    pub text: String,
+    pub quoted: bool,
+}
+
+/*
+Splits a line shell style.
+Whitespace separates tokens, '#' at the start of a token starts a comment.
+'single quotes' are taken literally, "double quotes" support \" \\ \n and \t.
+A backslash outside of quotes escapes the next character.
+Quoted or escaped tokens are never seen as part of a command name.
+*/
+pub fn tokenize(line: &str) -> Result<Vec<Token>, &'static str>{
+    split(line, false)
+}
+
+/*
+Splits a line typed at the prompt or given with -e, it never fails.
+A quote only starts quoting at the start of a word and when it is closed later on,
+so a title like Bob's party stays as it is. '#' is not a comment and a trailing backslash is kept.
+*/
+pub fn tokenize_typed(line: &str) -> Vec<Token>{
+    split(line, true).unwrap_or_default()
+}
+
+/*
+Replaces every !! word on a typed line with last, quoted words are left alone.
+Quotes are found the way tokenize_typed finds them. None when there is no !! to replace.
+*/
+pub fn expand_last(line: &str, last: &str) -> Option<String>{
+    let chars: Vec<char> = line.chars().collect();
+    let mut res = String::new();
+    let mut found = false;
+    let mut quote: Option<char> = Option::None;
+    let mut start = true;
+    let mut i = 0;
+    while i < chars.len(){
+        let ch = chars[i];
+        let escaped = ch == '\\' && quote != Option::Some('\'') && i + 1 < chars.len();
+        if escaped{
+            res.push(ch);
+            res.push(chars[i + 1]);
+            i += 2;
+            start = false;
+            continue;
+        }
+        if let Option::Some(q) = quote{
+            if ch == q {quote = Option::None;}
+        }else if ch.is_whitespace(){
+            res.push(ch);
+            i += 1;
+            start = true;
+            continue;
+        }else if start && (ch == '\'' || ch == '"') && chars[i + 1..].contains(&ch){
+            quote = Option::Some(ch);
+        }else if start && chars[i..].starts_with(&['!', '!']) && chars.get(i + 2).is_none_or(|x| x.is_whitespace()){
+            res.push_str(last);
+            found = true;
+            i += 2;
+            start = false;
+            continue;
+        }
+        res.push(ch);
+        i += 1;
+        start = false;
+    }
+    if found {Option::Some(res)} else {Option::None}
+}
+
+fn split(line: &str, typed: bool) -> Result<Vec<Token>, &'static str>{
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut started = false;
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Some(ch) = chars.next(){
+        match ch{
+            '#' if !started && !typed => break,
+            ' ' | '\t' | '\r' | '\n' => {
+                if !started {continue;}
+                tokens.push(Token{ text: current.clone(), quoted, });
+                current.clear();
+                started = false;
+                quoted = false;
+            }
+            '\\' => {
+                match chars.next(){
+                    Option::Some(x) => current.push(x),
+                    Option::None if typed => current.push('\\'),
+                    Option::None => return Err("trailing backslash"),
+                }
+                started = true;
+                quoted = true;
+            }
+            '\'' | '"' if typed && (started || !chars.clone().any(|x| x == ch)) => {
+                current.push(ch);
+                started = true;
+            }
+            '\'' => {
+                started = true;
+                quoted = true;
+                loop{
+                    match chars.next(){
+                        Option::Some('\'') => break,
+                        Option::Some(x) => current.push(x),
+                        Option::None => return Err("unterminated single quote"),
+                    }
+                }
+            }
+            '"' => {
+                started = true;
+                quoted = true;
+                loop{
+                    match chars.next(){
+                        Option::Some('"') => break,
+                        Option::Some('\\') => match chars.next(){
+                            Option::Some('n') => current.push('\n'),
+                            Option::Some('t') => current.push('\t'),
+                            Option::Some(x) => current.push(x),
+                            Option::None => return Err("unterminated double quote"),
+                        },
+                        Option::Some(x) => current.push(x),
+                        Option::None => return Err("unterminated double quote"),
+                    }
+                }
+            }
+            x => {
+                current.push(x);
+                started = true;
+            }
+        }
+    }
+    if started{
+        tokens.push(Token{ text: current, quoted, });
+    }
+    Ok(tokens)
+}
+
+//split on ; outside of quotes, for macros that run several commands
+pub fn split_commands(line: &str) -> Vec<String>{
+    let mut res = Vec::new();
+    let mut current = String::new();
+    let mut quote = Option::None;
+    let mut escaped = false;
+    for ch in line.chars(){
+        if escaped{
+            escaped = false;
+        }else if ch == '\\' && quote != Option::Some('\''){
+            escaped = true;
+        }else if quote.is_some(){
+            if quote == Option::Some(ch) {quote = Option::None;}
+        }else if ch == '\'' || ch == '"'{
+            quote = Option::Some(ch);
+        }else if ch == ';'{
+            res.push(current.trim().to_string());
+            current.clear();
+            continue;
+        }
+        current.push(ch);
+    }
+    res.push(current.trim().to_string());
+    res.into_iter().filter(|c| !c.is_empty()).collect()
+}
+
+/*
+Fills in macro arguments: $1 to $9 anywhere in a token, $@ as a whole token becomes all of them.
+Substituted tokens count as quoted. Err has the number of an argument that is missing.
+*/
+pub fn substitute(tokens: Vec<Token>, args: &[String]) -> Result<Vec<Token>, usize>{
+    let mut res = Vec::new();
+    for token in tokens{
+        if token.text == "$@"{
+            res.extend(args.iter().map(|a| Token{ text: a.clone(), quoted: true, }));
+            continue;
+        }
+        if !token.text.contains('$'){
+            res.push(token);
+            continue;
+        }
+        let mut text = String::new();
+        let mut chars = token.text.chars().peekable();
+        while let Some(ch) = chars.next(){
+            let nr = chars.peek().and_then(|c| c.to_digit(10)).filter(|n| *n > 0);
+            if ch != '$' || nr.is_none(){
+                text.push(ch);
+                continue;
+            }
+            chars.next();
+            let nr = nr.unwrap() as usize;
+            match args.get(nr - 1){
+                Option::Some(x) => text.push_str(x),
+                Option::None => return Err(nr),
+            }
+        }
+        res.push(Token{ text, quoted: true, });
+    }
+    Ok(res)
+}
+
+//split the -i argument on commas, "\," is a literal comma
+pub fn split_inputs(string: &str) -> VecDeque<astr::Astr>{
+    let mut res = VecDeque::new();
+    let mut current = String::new();
+    let mut chars = string.chars();
+    while let Some(ch) = chars.next(){
+        match ch{
+            '\\' => {
+                if let Some(x) = chars.next(){
+                    current.push(x);
+                }
+            }
+            ',' => {
+                if !current.is_empty(){
+                    res.push_back(current.to_astr());
+                }
+                current.clear();
+            }
+            x => current.push(x),
+        }
+    }
+    if !current.is_empty(){
+        res.push_back(current.to_astr());
+    }
+    res
+}
+
+//first the command words, the longest registered command wins, the rest are inputs
+pub fn split_command(parser: &parser::Parser, tokens: &[Token]) -> (String, VecDeque<astr::Astr>){
+    let mut words = Vec::new();
+    let mut in_args = false;
+    for token in tokens{
+        if token.quoted && !in_args {break;}
+        words.push(token.text.clone());
+        if token.text.contains('('){
+            in_args = true;
+        }
+        if token.text.contains(')') {break;}
+    }
+    let has_args = words.iter().any(|w| w.contains('('));
+    let len = if has_args {words.len()}
+    else{
+        let astrs: astr::AstrVec = words.iter().map(|w| w.to_astr()).collect();
+        match parser.command_length(&astrs){
+            0 => words.len(),
+            x => x,
+        }
+    };
+    let command = words[..len].join(" ");
+    let inputs = tokens[len..].iter().map(|t| t.text.to_astr()).collect();
+    (command, inputs)
+}
+
+/*
+Named arguments, --name value or --name=value, go with the command like they do on the prompt,
+the other words are its inputs in order.
+*/
+fn split_named(tokens: &[Token]) -> (Vec<Token>, VecDeque<astr::Astr>){
+    let mut named = Vec::new();
+    let mut inputs = VecDeque::new();
+    let mut iter = tokens.iter();
+    while let Some(token) = iter.next(){
+        if !token.text.starts_with("--") || token.text.len() < 3{
+            inputs.push_back(token.text.to_astr());
+            continue;
+        }
+        named.push(token.clone());
+        if token.text.contains('=') {continue;}
+        if let Some(value) = iter.next(){
+            named.push(Token{ text: value.text.clone(), quoted: true, });
+        }
+    }
+    (named, inputs)
+}
+
+pub fn run_script(parser: &mut parser::Parser, source: &str, name: &str) -> i32{
+    let mut stop_on_error = false;
+    let mut status = 0;
+    for (nr, line) in source.lines().enumerate(){
+        let report = |msg: &str|{
+            conz::print_type(format!("{}:{}: ", name, nr + 1), conz::MsgType::Highlight);
+            conz::println_type(msg, conz::MsgType::Error);
+        };
+        let tokens = match tokenize(line){
+            Ok(x) => x,
+            Err(msg) => {
+                report(&format!("Error: {}.", msg));
+                status = 1;
+                if stop_on_error {return status;}
+                continue;
+            }
+        };
+        if tokens.is_empty() {continue;}
+        if !tokens[0].quoted && tokens[0].text == "set"{
+            match tokens.get(1).map(|t| t.text.as_ref()){
+                Option::Some("-e") => stop_on_error = true,
+                Option::Some("+e") => stop_on_error = false,
+                _ => {
+                    report("Error: set only knows -e and +e.");
+                    status = 1;
+                    if stop_on_error {return status;}
+                }
+            }
+            continue;
+        }
+        let (command, rest) = split_command(parser, &tokens);
+        let cut = tokens.len() - rest.len();
+        let (named, inputs) = split_named(&tokens[cut..]);
+        let mut line_tokens = tokens[..cut].to_vec();
+        line_tokens.extend(named);
+        let res = parser.run_line(&command, line_tokens, Option::Some(inputs));
+        if res.is_ok() {continue;}
+        status = error::exit_code(res);
+        if stop_on_error{
+            report("Stopped: command failed and set -e is active.");
+            return status;
+        }
+    }
+    status
+}
+
+pub fn run_script_file(parser: &mut parser::Parser, path: &str) -> i32{
+    let mut source = String::new();
+    let res = if path == "-"{
+        std::io::stdin().read_to_string(&mut source)
+    }else{
+        std::fs::File::open(path).and_then(|mut f| f.read_to_string(&mut source))
+    };
+    if res.is_err(){
+        conz::println_error("Error: Could not read script: \"", path, "\".");
+        return 1;
+    }
+    let name = if path == "-" {"stdin"} else {path};
+    run_script(parser, &source, name)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn texts(tokens: &[Token]) -> Vec<&str>{
+        tokens.iter().map(|t| t.text.as_str()).collect()
+    }
+
+    fn quoted(tokens: &[Token]) -> Vec<bool>{
+        tokens.iter().map(|t| t.quoted).collect()
+    }
+
+    fn args(xs: &[&str]) -> Vec<String>{
+        xs.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn tokenize_words_and_quotes(){
+        let tokens = tokenize("mk point  'Exam one' \"at \\\"noon\\\"\\n\" a\\ b").unwrap();
+        assert_eq!(texts(&tokens), ["mk", "point", "Exam one", "at \"noon\"\n", "a b"]);
+        assert_eq!(quoted(&tokens), [false, false, true, true, true]);
+    }
+
+    #[test]
+    fn tokenize_single_quotes_are_literal(){
+        let tokens = tokenize("'a\\n\"b'").unwrap();
+        assert_eq!(texts(&tokens), ["a\\n\"b"]);
+    }
+
+    #[test]
+    fn tokenize_empty_quotes_are_a_token(){
+        let tokens = tokenize("mk todo '' \"\"").unwrap();
+        assert_eq!(texts(&tokens), ["mk", "todo", "", ""]);
+    }
+
+    #[test]
+    fn tokenize_comments(){
+        assert_eq!(texts(&tokenize("ls todos # all of them").unwrap()), ["ls", "todos"]);
+        assert!(tokenize("  # only a comment").unwrap().is_empty());
+        //only at the start of a token
+        assert_eq!(texts(&tokenize("mk todo a#b").unwrap()), ["mk", "todo", "a#b"]);
+    }
+
+    #[test]
+    fn tokenize_errors(){
+        assert!(tokenize("mk todo 'open").is_err());
+        assert!(tokenize("mk todo \"open").is_err());
+        assert!(tokenize("mk todo \"open\\").is_err());
+        assert!(tokenize("mk todo trailing\\").is_err());
+    }
+
+    #[test]
+    fn tokenize_typed_keeps_what_is_typed(){
+        assert_eq!(texts(&tokenize_typed("mk point Bob's party")), ["mk", "point", "Bob's", "party"]);
+        assert_eq!(texts(&tokenize_typed("mk todo #1 first")), ["mk", "todo", "#1", "first"]);
+        assert_eq!(texts(&tokenize_typed("mk todo a\\")), ["mk", "todo", "a\\"]);
+        //a quote that is not closed is a character
+        assert_eq!(texts(&tokenize_typed("mk todo 'open")), ["mk", "todo", "'open"]);
+    }
+
+    #[test]
+    fn tokenize_typed_still_quotes(){
+        let tokens = tokenize_typed("mk todo \"Bob's party\" 'a b'");
+        assert_eq!(texts(&tokens), ["mk", "todo", "Bob's party", "a b"]);
+        assert_eq!(quoted(&tokens), [false, false, true, true]);
+    }
+
+    #[test]
+    fn split_commands_on_semicolons(){
+        assert_eq!(split_commands("mk todo a; ls todos ;; "), ["mk todo a", "ls todos"]);
+        assert_eq!(split_commands("mk todo 'a;b'; mk todo \"c;\\\"d\""), ["mk todo 'a;b'", "mk todo \"c;\\\"d\""]);
+        assert_eq!(split_commands("mk todo a\\;b"), ["mk todo a\\;b"]);
+    }
+
+    #[test]
+    fn substitute_numbered(){
+        let tokens = tokenize("mk point $1 --at \"$2 $3\" $10").unwrap();
+        let res = substitute(tokens, &args(&["Exam", "9:00", "1/2/2030"])).ok().unwrap();
+        assert_eq!(texts(&res), ["mk", "point", "Exam", "--at", "9:00 1/2/2030", "Exam0"]);
+        assert_eq!(quoted(&res), [false, false, true, false, true, true]);
+    }
+
+    #[test]
+    fn substitute_all(){
+        let tokens = tokenize("mk todo $@").unwrap();
+        let res = substitute(tokens, &args(&["a b", "c"])).ok().unwrap();
+        assert_eq!(texts(&res), ["mk", "todo", "a b", "c"]);
+        let res = substitute(tokenize("ls $@").unwrap(), &[]).ok().unwrap();
+        assert_eq!(texts(&res), ["ls"]);
+    }
+
+    #[test]
+    fn substitute_leaves_other_dollars(){
+        let tokens = tokenize("mk todo $ $0 $x a$").unwrap();
+        let res = substitute(tokens, &[]).ok().unwrap();
+        assert_eq!(texts(&res), ["mk", "todo", "$", "$0", "$x", "a$"]);
+    }
+
+    #[test]
+    fn substitute_missing(){
+        let tokens = tokenize("mk point $1 $3").unwrap();
+        assert_eq!(substitute(tokens, &args(&["a", "b"])).err(), Option::Some(3));
+    }
+
+    #[test]
+    fn expand_last_words(){
+        assert_eq!(expand_last("!!", "ls todos"), Option::Some("ls todos".to_string()));
+        assert_eq!(expand_last("!! archive", "ls todos"), Option::Some("ls todos archive".to_string()));
+        assert_eq!(expand_last("help !!", "ls"), Option::Some("help ls".to_string()));
+    }
+
+    #[test]
+    fn expand_last_leaves_the_rest(){
+        assert_eq!(expand_last("mk todo wow!!", "ls"), Option::None);
+        assert_eq!(expand_last("mk todo !!!", "ls"), Option::None);
+        assert_eq!(expand_last("mk todo \"say !!\"", "ls"), Option::None);
+        assert_eq!(expand_last("mk todo '!!'", "ls"), Option::None);
+        assert_eq!(expand_last("mk todo \\!!", "ls"), Option::None);
+        assert_eq!(expand_last("ls todos", "ls"), Option::None);
+    }
+
+    #[test]
+    fn expand_last_after_an_open_quote(){
+        //the quote is not closed, so it does not quote
+        assert_eq!(expand_last("mk point Bob's !!", "x"), Option::Some("mk point Bob's x".to_string()));
+    }
+}
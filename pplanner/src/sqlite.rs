@@ -0,0 +1,663 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use rusqlite::{Connection, OptionalExtension, TransactionBehavior, params};
+
+use super::conz;
+use super::misc;
+use super::save;
+use super::storage;
+
+/*
+All records in one table, file is the name the flat file of them would have.
+A record is stored as its bytes in the flat format, with the date and type next to it to index on.
+The version of a file goes up with every write, so others see that it changed.
+*/
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS records(
+    id INTEGER PRIMARY KEY,
+    file TEXT NOT NULL,
+    date INTEGER,
+    type INTEGER,
+    data BLOB NOT NULL
+);
+CREATE INDEX IF NOT EXISTS records_date ON records(file, date);
+CREATE INDEX IF NOT EXISTS records_type ON records(file, type);
+CREATE TABLE IF NOT EXISTS files(
+    file TEXT PRIMARY KEY,
+    version INTEGER NOT NULL
+);";
+//other processes hold the database only for one write, we wait for them
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn db_path() -> Option<std::path::PathBuf>{
+    save::get_data_dir_path(save::DATABASE_FILE)
+}
+
+pub fn exists() -> bool{
+    db_path().map(|p| p.exists()).unwrap_or(false)
+}
+
+fn report(e: rusqlite::Error){
+    conz::print_type("Error: sqlite: ", conz::MsgType::Error);
+    conz::println_type(e, conz::MsgType::Highlight);
+}
+
+fn connect_to(path: &std::path::Path) -> Option<Connection>{
+    let res = Connection::open(path).and_then(|conn|{
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(conn)
+    });
+    res.map_err(report).ok()
+}
+
+fn connect() -> Option<Connection>{
+    connect_to(&db_path()?)
+}
+
+fn encode<T: save::Bufferable>(item: &T) -> save::Buffer{
+    let mut buf = Vec::new();
+    item.into_buffer(&mut buf);
+    buf
+}
+
+fn version(conn: &Connection, name: &str) -> rusqlite::Result<i64>{
+    let res = conn.query_row("SELECT version FROM files WHERE file = ?1", [name], |row| row.get(0)).optional()?;
+    Ok(res.unwrap_or(0))
+}
+
+fn set_version(conn: &Connection, name: &str, version: i64) -> rusqlite::Result<()>{
+    conn.execute("INSERT INTO files(file, version) VALUES(?1, ?2) ON CONFLICT(file) DO UPDATE SET version = ?2",
+        params![name, version])?;
+    Ok(())
+}
+
+fn insert<T: storage::Record>(conn: &Connection, name: &str, item: &T) -> rusqlite::Result<i64>{
+    conn.execute("INSERT INTO records(file, date, type, data) VALUES(?1, ?2, ?3, ?4)",
+        params![name, item.date(), item.kind(), encode(item)])?;
+    Ok(conn.last_insert_rowid())
+}
+
+//false when the row is no longer there
+fn update<T: storage::Record>(conn: &Connection, id: i64, item: &T) -> rusqlite::Result<bool>{
+    let n = conn.execute("UPDATE records SET date = ?1, type = ?2, data = ?3 WHERE id = ?4",
+        params![item.date(), item.kind(), encode(item), id])?;
+    Ok(n > 0)
+}
+
+//the rows of a file with their id, None when one of them can not be read
+fn rows<T: save::Bufferable>(conn: &Connection, name: &str, order: &str) -> rusqlite::Result<Option<Vec<(i64, T)>>>{
+    let mut stmt = conn.prepare(&format!("SELECT id, data FROM records WHERE file = ?1 ORDER BY {}", order))?;
+    let res = decode_rows(stmt.query([name])?);
+    res
+}
+
+/*
+The rows of an archive that match the query, in the order they were added.
+Records without a date do not compare with from or to, so they are left out when one is set.
+*/
+fn select<T: save::Bufferable>(conn: &Connection, name: &str, query: &storage::Query) -> rusqlite::Result<Option<Vec<T>>>{
+    let dated = query.dated();
+    let mut stmt = conn.prepare("SELECT id, data FROM records WHERE file = ?1
+        AND (?2 = 0 OR date IS NOT NULL) AND (?3 IS NULL OR date >= ?3) AND (?4 IS NULL OR date < ?4)
+        ORDER BY id DESC LIMIT ?5")?;
+    //a negative limit is no limit
+    let limit = query.last.map(|x| x as i64).unwrap_or(-1);
+    let res = decode_rows(stmt.query(params![name, dated, query.from, query.to, limit])?)?;
+    Ok(res.map(|x| x.into_iter().rev().map(|(_, x)| x).collect()))
+}
+
+fn decode_rows<T: save::Bufferable>(mut rows: rusqlite::Rows) -> rusqlite::Result<Option<Vec<(i64, T)>>>{
+    let mut res = Vec::new();
+    while let Some(row) = rows.next()?{
+        let data: save::Buffer = row.get(1)?;
+        let mut iter: u32 = 0;
+        match T::from_buffer(&data, &mut iter){
+            Option::Some(x) if iter as usize == data.len() => res.push((row.get(0)?, x)),
+            _ => return Ok(Option::None),
+        }
+    }
+    Ok(Option::Some(res))
+}
+
+fn println_unreadable(name: &str){
+    conz::println_error("", "Error: Cannot read records of: ", name);
+}
+
+/*
+Edits are remembered per row and written in one transaction,
+so a write costs the changed records and not the whole file.
+Changes of other processes to other rows are kept, they are read after our write.
+*/
+pub struct Table<T>{
+    name: String,
+    path: Option<std::path::PathBuf>,
+    conn: Option<Connection>,
+    content: Vec<T>,
+    //the row of each record, None for records that are not written yet
+    ids: Vec<Option<i64>>,
+    deleted: Vec<i64>,
+    changed: HashSet<i64>,
+    //the version our content is from, and the one we last noticed
+    version: i64,
+    seen: i64,
+    dirty: bool,
+    loaded: bool,
+}
+
+impl<T: storage::Record> Table<T>{
+    pub fn new(name: &str) -> Table<T>{
+        Table::at(db_path(), name)
+    }
+
+    //the records of name in the database at path
+    fn at(path: Option<std::path::PathBuf>, name: &str) -> Table<T>{
+        Table{
+            name: name.to_string(),
+            path,
+            conn: Option::None,
+            content: Vec::new(),
+            ids: Vec::new(),
+            deleted: Vec::new(),
+            changed: HashSet::new(),
+            version: 0,
+            seen: 0,
+            dirty: false,
+            loaded: false,
+        }
+    }
+
+    fn conn(&mut self) -> Option<&mut Connection>{
+        if self.conn.is_none(){
+            self.conn = connect_to(self.path.as_deref()?);
+        }
+        self.conn.as_mut()
+    }
+
+    fn db_version(&mut self) -> Option<i64>{
+        let name = self.name.clone();
+        version(self.conn()?, &name).map_err(report).ok()
+    }
+
+    fn load(&mut self) -> Option<(Vec<(i64, T)>, i64)>{
+        let name = self.name.clone();
+        let conn = self.conn()?;
+        let res = version(conn, &name).and_then(|v| rows(conn, &name, "date, type, id").map(|r| r.map(|r| (r, v))));
+        match res{
+            Ok(Option::Some(x)) => Option::Some(x),
+            Ok(Option::None) =>{
+                println_unreadable(&name);
+                Option::None
+            }
+            Err(e) =>{
+                report(e);
+                println_unreadable(&name);
+                Option::None
+            }
+        }
+    }
+
+    fn sort(&mut self){
+        if misc::is_sorted(&self.content) {return;}
+        let mut pairs: Vec<(T, Option<i64>)> = self.content.drain(..).zip(self.ids.drain(..)).collect();
+        pairs.sort_by(|a, b| a.0.cmp(&b.0));
+        (self.content, self.ids) = pairs.into_iter().unzip();
+    }
+
+    fn commit(conn: &mut Connection, name: &str, content: &[T], ids: &[Option<i64>], deleted: &[i64],
+        changed: &HashSet<i64>) -> rusqlite::Result<(Vec<Option<i64>>, i64)>{
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let old = version(&tx, name)?;
+        for id in deleted{
+            tx.execute("DELETE FROM records WHERE id = ?1", [id])?;
+        }
+        let mut new_ids = Vec::with_capacity(ids.len());
+        for (item, id) in content.iter().zip(ids){
+            let id = match id{
+                Option::Some(id) if !changed.contains(id) => *id,
+                //removed by another process, our edit brings it back
+                Option::Some(id) => if update(&tx, *id, item)? {*id} else {insert(&tx, name, item)?},
+                Option::None => insert(&tx, name, item)?,
+            };
+            new_ids.push(Option::Some(id));
+        }
+        set_version(&tx, name, old + 1)?;
+        tx.commit()?;
+        Ok((new_ids, old))
+    }
+}
+
+impl<T: storage::Record> storage::Storage<T> for Table<T>{
+    fn get_items(&mut self) -> &Vec<T>{
+        if !self.loaded {self.read(false);}
+        self.sort();
+        &self.content
+    }
+
+    fn add_item(&mut self, item: T) -> bool{
+        if !self.loaded && !self.read(false){
+            conz::println_type("Error: Cannot add item.", conz::MsgType::Error);
+            return false;
+        }
+        self.content.push(item);
+        self.ids.push(Option::None);
+        self.dirty = true;
+        true
+    }
+
+    fn remove_indices(&mut self, mut indices: Vec<usize>) -> bool{
+        if !misc::is_sorted(&indices){
+            conz::println_type("Warning: remove_indices, should be sorted, is not.", conz::MsgType::Error);
+            indices.sort();
+        }
+        self.sort();
+        for i in indices.into_iter().rev(){
+            if i >= self.content.len() {continue;}
+            self.content.remove(i);
+            if let Some(id) = self.ids.remove(i){
+                self.changed.remove(&id);
+                self.deleted.push(id);
+            }
+        }
+        self.dirty = true;
+        self.write()
+    }
+
+    fn replace(&mut self, indices: Vec<usize>, replacements: Vec<T>) -> bool{
+        if !misc::is_sorted(&indices){
+            conz::println_type("Error: replace, indices should be sorted, is not.", conz::MsgType::Error);
+            return false;
+        }
+        if indices.len() != replacements.len(){
+            conz::println_type("Error: sqlite::replace: indices.len() != replacements.len().", conz::MsgType::Error);
+            return false;
+        }
+        self.sort();
+        for (i, item) in indices.into_iter().zip(replacements){
+            if i >= self.content.len() {break;}
+            self.content[i] = item;
+            if let Some(id) = self.ids[i]{
+                self.changed.insert(id);
+            }
+        }
+        self.sort();
+        self.dirty = true;
+        self.write()
+    }
+
+    fn read(&mut self, force: bool) -> bool{
+        if self.loaded && !force {return true;}
+        let (rows, version) = match self.load(){
+            Option::Some(x) => x,
+            Option::None => return false,
+        };
+        (self.ids, self.content) = rows.into_iter().map(|(id, x)| (Option::Some(id), x)).unzip();
+        self.sort();
+        self.deleted.clear();
+        self.changed.clear();
+        self.version = version;
+        self.seen = version;
+        self.dirty = false;
+        self.loaded = true;
+        true
+    }
+
+    fn write(&mut self) -> bool{
+        if !self.dirty {return true;}
+        if !self.loaded{
+            conz::println_type("Error: Nothing to write, content was never initialized.", conz::MsgType::Error);
+            return false;
+        }
+        let name = self.name.clone();
+        let mut conn = match self.conn.take().or_else(|| connect_to(self.path.as_deref()?)){
+            Option::Some(x) => x,
+            Option::None => return false,
+        };
+        let res = Table::commit(&mut conn, &name, &self.content, &self.ids, &self.deleted, &self.changed);
+        self.conn = Option::Some(conn);
+        let (ids, old) = match res{
+            Ok(x) => x,
+            Err(e) =>{
+                report(e);
+                conz::println_error("", "Error: Cannot write items to: ", &name);
+                return false;
+            }
+        };
+        if old != self.version{
+            //someone else wrote too, read it all to have their records as well
+            conz::println_error("", "Warning: Records were changed by another process, merged the changes: ", &name);
+            self.dirty = false;
+            return self.read(true);
+        }
+        self.ids = ids;
+        self.deleted.clear();
+        self.changed.clear();
+        self.version = old + 1;
+        self.seen = old + 1;
+        self.dirty = false;
+        true
+    }
+
+    fn is_clean(&self) -> bool{
+        !self.dirty
+    }
+
+    fn changed_on_disk(&mut self) -> bool{
+        if !self.loaded {return false;}
+        match self.db_version(){
+            Option::Some(v) => v != self.seen,
+            Option::None => false,
+        }
+    }
+
+    /*
+    Their rows with our edits on top: what we removed stays removed, what we edited is ours,
+    what we added is added. An edit of a row they removed adds it again.
+    */
+    fn merge_from_disk(&mut self) -> bool{
+        let (rows, version) = match self.load(){
+            Option::Some(x) => x,
+            Option::None => return false,
+        };
+        let mut ours: HashMap<i64, T> = HashMap::new();
+        let mut added = Vec::new();
+        for (item, id) in self.content.drain(..).zip(self.ids.drain(..)){
+            match id{
+                Option::Some(id) if self.changed.contains(&id) =>{
+                    ours.insert(id, item);
+                }
+                Option::Some(_) => {}
+                Option::None => added.push(item),
+            }
+        }
+        for (id, item) in rows{
+            if self.deleted.contains(&id) {continue;}
+            self.content.push(ours.remove(&id).unwrap_or(item));
+            self.ids.push(Option::Some(id));
+        }
+        for (id, item) in ours{
+            self.changed.remove(&id);
+            self.content.push(item);
+            self.ids.push(Option::None);
+        }
+        for item in added{
+            self.content.push(item);
+            self.ids.push(Option::None);
+        }
+        self.sort();
+        self.version = version;
+        self.seen = version;
+        true
+    }
+
+    fn ignore_disk(&mut self){
+        if let Some(v) = self.db_version(){
+            self.seen = v;
+        }
+    }
+}
+
+pub struct ArchiveTable<T>{
+    name: String,
+    path: Option<std::path::PathBuf>,
+    content: Vec<T>,
+    dirty: bool,
+}
+
+impl<T: storage::Record> ArchiveTable<T>{
+    pub fn new(name: &str) -> ArchiveTable<T>{
+        ArchiveTable::at(db_path(), name)
+    }
+
+    fn at(path: Option<std::path::PathBuf>, name: &str) -> ArchiveTable<T>{
+        ArchiveTable{
+            name: name.to_string(),
+            path,
+            content: Vec::new(),
+            dirty: false,
+        }
+    }
+
+    fn connect(&self) -> Option<Connection>{
+        connect_to(self.path.as_deref()?)
+    }
+
+    fn append(&self, conn: &mut Connection) -> rusqlite::Result<()>{
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        for item in &self.content{
+            insert(&tx, &self.name, item)?;
+        }
+        let old = version(&tx, &self.name)?;
+        set_version(&tx, &self.name, old + 1)?;
+        tx.commit()
+    }
+}
+
+impl<T: storage::Record> storage::Archive<T> for ArchiveTable<T>{
+    //the database only decodes the rows the query asks for, they are not streamed further
+    fn select(&mut self, query: &storage::Query) -> Box<dyn Iterator<Item = T>>{
+        let res = match self.connect(){
+            Option::Some(conn) => select(&conn, &self.name, query),
+            Option::None => Ok(Option::None),
+        };
+        match res{
+            Ok(Option::Some(x)) => Box::new(x.into_iter()),
+            Ok(Option::None) =>{
+                println_unreadable(&self.name);
+                Box::new(std::iter::empty())
+            }
+            Err(e) =>{
+                report(e);
+                println_unreadable(&self.name);
+                Box::new(std::iter::empty())
+            }
+        }
+    }
+
+    fn add_item(&mut self, item: T){
+        self.content.push(item);
+        self.dirty = true;
+    }
+
+    fn write(&mut self) -> bool{
+        if !self.dirty {return true;}
+        let res = match self.connect(){
+            Option::Some(mut conn) => self.append(&mut conn),
+            Option::None => Err(rusqlite::Error::InvalidPath(self.path.clone().unwrap_or_default())),
+        };
+        if let Err(e) = res{
+            report(e);
+            conz::println_error("", "Error: Cannot write items to: ", &self.name);
+            return false;
+        }
+        self.content.clear();
+        self.dirty = false;
+        true
+    }
+
+    fn is_clean(&self) -> bool{
+        !self.dirty
+    }
+}
+
+/*
+A new database, filled in one transaction next to where it goes.
+It is only moved into place when finished, so it is whole or not there.
+*/
+pub struct Import{
+    path: std::path::PathBuf,
+    conn: Connection,
+}
+
+impl Import{
+    fn tmp_path() -> Option<std::path::PathBuf>{
+        let mut tmp = db_path()?.into_os_string();
+        tmp.push(".tmp");
+        Option::Some(std::path::PathBuf::from(tmp))
+    }
+
+    pub fn new() -> Option<Import>{
+        let path = Import::tmp_path()?;
+        let _ = std::fs::remove_file(&path);
+        let conn = connect_to(&path)?;
+        conn.execute_batch("BEGIN").map_err(report).ok()?;
+        Option::Some(Import{path, conn})
+    }
+
+    pub fn add<T: storage::Record>(&mut self, name: &str, records: &[T]) -> bool{
+        let res = records.iter().try_for_each(|x| insert(&self.conn, name, x).map(|_| ()))
+            .and_then(|_| set_version(&self.conn, name, 1));
+        res.map_err(report).is_ok()
+    }
+
+    pub fn finish(self) -> bool{
+        let target = match db_path(){
+            Option::Some(x) => x,
+            Option::None => return false,
+        };
+        let ok = self.conn.execute_batch("COMMIT").map_err(report).is_ok() && self.conn.close().is_ok()
+            && std::fs::rename(&self.path, &target).is_ok();
+        if !ok{
+            let _ = std::fs::remove_file(&self.path);
+            conz::println_error("", "Error: Cannot create the database: ", &target.to_string_lossy());
+        }
+        ok
+    }
+
+    pub fn abort(self){
+        drop(self.conn);
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+//the records of a file in the flat format, in the order they were added
+pub fn export(name: &str) -> Option<save::Buffer>{
+    let conn = connect()?;
+    let res = conn.prepare("SELECT data FROM records WHERE file = ?1 ORDER BY id").and_then(|mut stmt|{
+        let mut rows = stmt.query([name])?;
+        let mut buf = Vec::new();
+        while let Some(row) = rows.next()?{
+            let data: save::Buffer = row.get(0)?;
+            buf.extend(data);
+        }
+        Ok(buf)
+    });
+    res.map_err(report).ok()
+}
+
+pub fn remove() -> bool{
+    let path = match db_path(){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    if std::fs::remove_file(&path).is_err(){
+        conz::println_error("", "Error: could not remove the database: ", &path.to_string_lossy());
+        return false;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::astr;
+    use crate::data;
+    use storage::{Archive, Storage};
+
+    //a new database in a directory of its own
+    fn db(name: &str) -> std::path::PathBuf{
+        let dir = std::env::temp_dir().join(format!("pplanner-sqlite-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join(save::DATABASE_FILE)
+    }
+
+    fn day(d: u32) -> data::DT{
+        data::DT::make_datetime((d, 2, 2030), (12, 0, 0)).unwrap()
+    }
+
+    fn point(title: &str, d: u32) -> data::Point{
+        data::Point::new(day(d), astr::from_str(title), astr::from_str("deadline"))
+    }
+
+    fn titles(xs: &[data::Point]) -> Vec<String>{
+        xs.iter().map(|x| String::from_utf8_lossy(&x.title.0).into_owned()).collect()
+    }
+
+    fn table(path: &std::path::Path) -> Table<data::Point>{
+        let mut table = Table::at(Option::Some(path.to_path_buf()), save::POINT_DIR);
+        assert!(table.read(false));
+        table
+    }
+
+    fn file_version(path: &std::path::Path) -> i64{
+        version(&connect_to(path).unwrap(), save::POINT_DIR).unwrap()
+    }
+
+    #[test]
+    fn added_records_wait_for_write(){
+        let path = db("add");
+        let mut ours = table(&path);
+        assert!(ours.add_item(point("b", 2)) && ours.add_item(point("a", 1)));
+        assert!(titles(table(&path).get_items()).is_empty());
+        assert!(ours.write());
+        assert_eq!(file_version(&path), 1);
+        assert_eq!(titles(table(&path).get_items()), ["a", "b"]);
+        assert!(ours.write());
+        assert_eq!(file_version(&path), 1);
+    }
+
+    #[test]
+    fn replace_and_remove_write_right_away(){
+        let path = db("replace");
+        let mut ours = table(&path);
+        assert!(ours.add_item(point("a", 1)) && ours.add_item(point("b", 2)) && ours.write());
+        assert!(ours.replace(vec![0], vec![point("c", 3)]));
+        assert_eq!(file_version(&path), 2);
+        assert_eq!(titles(table(&path).get_items()), ["b", "c"]);
+        assert!(ours.remove_indices(vec![0]));
+        assert_eq!(file_version(&path), 3);
+        assert_eq!(titles(table(&path).get_items()), ["c"]);
+        assert!(ours.is_clean());
+    }
+
+    #[test]
+    fn changes_of_another_table_are_noticed_and_merged(){
+        let path = db("other");
+        let mut ours = table(&path);
+        assert!(ours.add_item(point("a", 1)) && ours.add_item(point("b", 2)) && ours.write());
+        let mut theirs = table(&path);
+        assert!(theirs.add_item(point("c", 3)) && theirs.write());
+        assert!(ours.changed_on_disk());
+        assert!(ours.replace(vec![0], vec![point("d", 4)]));
+        assert!(!ours.changed_on_disk());
+        assert_eq!(titles(ours.get_items()), ["b", "c", "d"]);
+        assert_eq!(titles(table(&path).get_items()), ["b", "c", "d"]);
+    }
+
+    #[test]
+    fn archive_select(){
+        let path = db("archive");
+        let mut archive = ArchiveTable::at(Option::Some(path.clone()), save::POINT_ARCHIVE_DIR);
+        for (title, d) in [("c", 3), ("a", 1), ("d", 4), ("b", 2)]{
+            archive.add_item(point(title, d));
+        }
+        assert!(archive.write() && archive.is_clean());
+        let select = |archive: &mut ArchiveTable<data::Point>, from: Option<u32>, to: Option<u32>, last: Option<usize>|{
+            let query = storage::Query{
+                from: from.map(|d| day(d).dt.timestamp()),
+                to: to.map(|d| day(d).dt.timestamp()),
+                last,
+            };
+            titles(&archive.select(&query).collect::<Vec<_>>())
+        };
+        assert_eq!(select(&mut archive, Option::None, Option::None, Option::None), ["c", "a", "d", "b"]);
+        assert_eq!(select(&mut archive, Option::None, Option::None, Option::Some(2)), ["d", "b"]);
+        assert_eq!(select(&mut archive, Option::Some(2), Option::Some(4), Option::None), ["c", "b"]);
+        assert_eq!(select(&mut archive, Option::Some(2), Option::None, Option::Some(1)), ["b"]);
+        assert_eq!(select(&mut archive, Option::None, Option::Some(2), Option::None), ["a"]);
+        assert!(select(&mut archive, Option::Some(5), Option::None, Option::None).is_empty());
+        assert_eq!(file_version(&path), 0);
+        assert_eq!(version(&connect_to(&path).unwrap(), save::POINT_ARCHIVE_DIR).unwrap(), 1);
+    }
+}
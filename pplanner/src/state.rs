@@ -1,47 +1,53 @@
-use std::collections::HashSet;
+use std::collections::BTreeMap;
 
 use super::save;
 use super::data;
 use super::astr;
+use super::help;
+use super::hooks;
+use super::sync;
+use super::conz;
+use super::storage;
 
 pub struct State{
-    pub fset: HashSet<astr::Astr>,
-    pub points: save::BufferFile<data::Point>,
-    pub points_archive: save::ArchiveFile<data::Point>,
-    pub plans: save::BufferFile<data::Plan>,
-    pub plans_archive: save::ArchiveFile<data::Plan>,
-    pub slices: save::BufferFile<data::Slice>,
-    pub slices_archive: save::ArchiveFile<data::Slice>,
-    pub todos: save::BufferFile<data::Todo>,
-    pub todos_archive: save::ArchiveFile<data::Todo>,
+    pub commands: BTreeMap<astr::Astr, help::CmdHelp>,
+    pub points: Box<dyn storage::Storage<data::Point>>,
+    pub points_archive: Box<dyn storage::Archive<data::Point>>,
+    pub plans: Box<dyn storage::Storage<data::Plan>>,
+    pub plans_archive: Box<dyn storage::Archive<data::Plan>>,
+    pub slices: Box<dyn storage::Storage<data::Slice>>,
+    pub slices_archive: Box<dyn storage::Archive<data::Slice>>,
+    pub todos: Box<dyn storage::Storage<data::Todo>>,
+    pub todos_archive: Box<dyn storage::Archive<data::Todo>>,
+    pub sessions: Box<dyn storage::Storage<data::Session>>,
+    pub habits: Box<dyn storage::Storage<data::Habit>>,
 }
 
 impl State{
     pub fn new() -> Option<Self>{
-        let msg = "State::new(), file should be here";
-        //main.rs should not continue if save::setup_config_dir fails
-        //save::setup_config_dir fails should return false if not all files are there
-        let points_path = save::get_data_dir_path(save::POINT_DIR).expect(msg);
-        let points_archive_path = save::get_data_dir_path(save::POINT_ARCHIVE_DIR).expect(msg);
-        let plans_path = save::get_data_dir_path(save::PLAN_DIR).expect(msg);
-        let plans_archive_path = save::get_data_dir_path(save::PLAN_ARCHIVE_DIR).expect(msg);
-        let slices_path = save::get_data_dir_path(save::SLICE_DIR).expect(msg);
-        let slices_archive_path = save::get_data_dir_path(save::SLICE_ARCHIVE_DIR).expect(msg);
-        let todos_path = save::get_data_dir_path(save::TODO_DIR).expect(msg);
-        let todos_archive_path = save::get_data_dir_path(save::TODO_ARCHIVE_DIR).expect(msg);
         Option::Some(State{
-            fset: HashSet::new(),
-            points: save::BufferFile::new(points_path),
-            points_archive: save::ArchiveFile::new(points_archive_path),
-            plans: save::BufferFile::new(plans_path),
-            plans_archive: save::ArchiveFile::new(plans_archive_path),
-            slices: save::BufferFile::new(slices_path),
-            slices_archive: save::ArchiveFile::new(slices_archive_path),
-            todos: save::BufferFile::new(todos_path),
-            todos_archive: save::ArchiveFile::new(todos_archive_path),
+            commands: BTreeMap::new(),
+            points: storage::open(save::POINT_DIR),
+            points_archive: storage::open_archive(save::POINT_ARCHIVE_DIR),
+            plans: storage::open(save::PLAN_DIR),
+            plans_archive: storage::open_archive(save::PLAN_ARCHIVE_DIR),
+            slices: storage::open(save::SLICE_DIR),
+            slices_archive: storage::open_archive(save::SLICE_ARCHIVE_DIR),
+            todos: storage::open(save::TODO_DIR),
+            todos_archive: storage::open_archive(save::TODO_ARCHIVE_DIR),
+            sessions: storage::open(save::SESSION_DIR),
+            habits: storage::open(save::HABIT_DIR),
         })
     }
 
+    //opens the storage again, after it was migrated
+    pub fn reopen(&mut self){
+        if let Some(mut state) = State::new(){
+            state.commands = std::mem::take(&mut self.commands);
+            *self = state;
+        }
+    }
+
     pub fn is_clean(&self) -> bool{
         self.points.is_clean()
         && self.points_archive.is_clean()
@@ -51,10 +57,30 @@ impl State{
         && self.plans_archive.is_clean()
         && self.todos.is_clean()
         && self.todos_archive.is_clean()
+        && self.sessions.is_clean()
+        && self.habits.is_clean()
     }
 
+    //the names of the files with unsaved changes
+    fn dirty_files(&self) -> Vec<&'static str>{
+        [
+            (self.points.is_clean(), "points"),
+            (self.points_archive.is_clean(), "points archive"),
+            (self.slices.is_clean(), "slices"),
+            (self.slices_archive.is_clean(), "slices archive"),
+            (self.plans.is_clean(), "plans"),
+            (self.plans_archive.is_clean(), "plans archive"),
+            (self.todos.is_clean(), "todos"),
+            (self.todos_archive.is_clean(), "todos archive"),
+            (self.sessions.is_clean(), "sessions"),
+            (self.habits.is_clean(), "habits"),
+        ].iter().filter(|(clean, _)| !clean).map(|(_, name)| *name).collect()
+    }
+
+    //writes all files, then commits what was saved since the last flush when git is on
     pub fn flush_files(&mut self) -> bool{
-        self.points.write()
+        let dirty = self.dirty_files();
+        let ok = self.points.write()
         && self.points_archive.write()
         && self.slices.write()
         && self.slices_archive.write()
@@ -62,5 +88,43 @@ impl State{
         && self.plans_archive.write()
         && self.todos.write()
         && self.todos_archive.write()
+        && self.sessions.write()
+        && self.habits.write();
+        if !ok {return false;}
+        sync::commit(&dirty);
+        if !dirty.is_empty() {hooks::fire_flush();}
+        true
+    }
+
+    /*
+    Picks up changes other pplanner processes made to the files.
+    Clean files are read again, for files with unsaved changes we ask.
+    */
+    pub fn refresh(&mut self){
+        refresh_file(self.points.as_mut(), save::POINT_DIR);
+        refresh_file(self.plans.as_mut(), save::PLAN_DIR);
+        refresh_file(self.slices.as_mut(), save::SLICE_DIR);
+        refresh_file(self.todos.as_mut(), save::TODO_DIR);
+        refresh_file(self.sessions.as_mut(), save::SESSION_DIR);
+        refresh_file(self.habits.as_mut(), save::HABIT_DIR);
+    }
+}
+
+fn refresh_file<T>(bfile: &mut dyn storage::Storage<T>, name: &str){
+    if !bfile.changed_on_disk() {return;}
+    if bfile.is_clean(){
+        bfile.read(true);
+        return;
+    }
+    conz::print_type("Warning: ", conz::MsgType::Error);
+    conz::print_type(name, conz::MsgType::Highlight);
+    conz::println_type(" was changed by another process, and you have unsaved changes to it.", conz::MsgType::Error);
+    if conz::read_bool("Merge the changes into yours? ", &mut Option::None){
+        if bfile.merge_from_disk() {return;}
+    }else if conz::read_bool("Drop your unsaved changes and read the file again? ", &mut Option::None){
+        bfile.read(true);
+        return;
     }
+    conz::println_type("Keeping your changes, the file is merged when it is written.", conz::MsgType::Normal);
+    bfile.ignore_disk();
 }
@@ -0,0 +1,237 @@
+use super::conz;
+use super::data;
+use super::save;
+use super::sqlite;
+use super::crypt;
+
+/*
+Where the records of one kind are kept.
+State only knows these traits, the flat files of save and the sqlite database both implement them.
+get_items gives the records sorted.
+Added records are kept until write, remove_indices and replace write right away.
+*/
+pub trait Storage<T>{
+    fn get_items(&mut self) -> &Vec<T>;
+    fn add_item(&mut self, item: T) -> bool;
+    fn remove_indices(&mut self, indices: Vec<usize>) -> bool;
+    fn replace(&mut self, indices: Vec<usize>, replacements: Vec<T>) -> bool;
+    fn read(&mut self, force: bool) -> bool;
+    fn write(&mut self) -> bool;
+    fn is_clean(&self) -> bool;
+    //true when another process changed the records since we read or wrote them
+    fn changed_on_disk(&mut self) -> bool;
+    //merges the changes of the other process into our unsaved changes
+    fn merge_from_disk(&mut self) -> bool;
+    //stop noticing the current change, it is still merged when we write
+    fn ignore_disk(&mut self);
+}
+
+//which archived records to read, all of them by default
+#[derive(Clone, Copy, Default)]
+pub struct Query{
+    //seconds since the epoch, from included and to not, records without a date are left out when set
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    //only the last ones that match
+    pub last: Option<usize>,
+}
+
+impl Query{
+    pub fn dated(&self) -> bool{
+        self.from.is_some() || self.to.is_some()
+    }
+
+    pub fn matches(&self, date: Option<i64>) -> bool{
+        if !self.dated() {return true;}
+        match date{
+            Option::None => false,
+            Option::Some(x) => self.from.is_none_or(|from| x >= from) && self.to.is_none_or(|to| x < to),
+        }
+    }
+}
+
+//records that are only added to, and read back in the order they were added
+pub trait Archive<T>{
+    //the records are decoded while iterating, only the ones the query asks for
+    fn select(&mut self, query: &Query) -> Box<dyn Iterator<Item = T>>;
+    fn add_item(&mut self, item: T);
+    fn write(&mut self) -> bool;
+    fn is_clean(&self) -> bool;
+}
+
+//what the database indexes records on, None when a kind has no such thing
+pub trait Indexed{
+    //seconds since the epoch
+    fn date(&self) -> Option<i64>;
+    fn kind(&self) -> Option<u8>;
+}
+
+pub trait Record: save::Bufferable + Indexed + Ord + Clone + 'static{}
+
+impl<T: save::Bufferable + Indexed + Ord + Clone + 'static> Record for T{}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Backend{
+    Flat,
+    Sqlite,
+}
+
+impl Backend{
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(string: &str) -> Option<Backend>{
+        match string{
+            "flat" | "files" => Option::Some(Backend::Flat),
+            "sqlite" | "db" => Option::Some(Backend::Sqlite),
+            _ => Option::None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str{
+        match self{
+            Backend::Flat => "flat",
+            Backend::Sqlite => "sqlite",
+        }
+    }
+
+    //the database is used when it is there, migrate storage puts it there or takes it away
+    pub fn current() -> Backend{
+        if sqlite::exists() {Backend::Sqlite} else {Backend::Flat}
+    }
+}
+
+//the files that move between backends, the reminders stay a flat file of the daemon
+pub const STORED_FILES: [&str; 10] = [save::POINT_DIR, save::POINT_ARCHIVE_DIR, save::PLAN_DIR,
+    save::PLAN_ARCHIVE_DIR, save::SLICE_DIR, save::SLICE_ARCHIVE_DIR, save::TODO_DIR, save::TODO_ARCHIVE_DIR,
+    save::SESSION_DIR, save::HABIT_DIR];
+
+fn data_path(name: &str) -> std::path::PathBuf{
+    //main.rs should not continue if save::setup_config_dir fails
+    save::get_data_dir_path(name).expect("storage: the data directory should be there")
+}
+
+pub fn open<T: Record>(name: &str) -> Box<dyn Storage<T>>{
+    match Backend::current(){
+        Backend::Flat => Box::new(save::BufferFile::new(data_path(name))),
+        Backend::Sqlite => Box::new(sqlite::Table::new(name)),
+    }
+}
+
+pub fn open_archive<T: Record>(name: &str) -> Box<dyn Archive<T>>{
+    match Backend::current(){
+        Backend::Flat => Box::new(save::ArchiveFile::new(data_path(name))),
+        Backend::Sqlite => Box::new(sqlite::ArchiveTable::new(name)),
+    }
+}
+
+fn import_file<T: Record>(db: &mut sqlite::Import, name: &str) -> bool{
+    let records = save::read_records::<T>(&data_path(name));
+    match records{
+        Option::Some(x) => db.add(name, &x),
+        Option::None =>{
+            conz::print_type("Error: can not read all records, nothing is migrated: ", conz::MsgType::Error);
+            conz::println_type(name, conz::MsgType::Highlight);
+            false
+        }
+    }
+}
+
+/*
+The new database is filled next to the files and renamed into place when complete,
+only then are the files removed. Stopping halfway leaves the files in use.
+*/
+fn to_sqlite() -> bool{
+    if crypt::enabled(){
+        conz::println_type("Error: the sqlite storage can not be encrypted, decrypt data first.", conz::MsgType::Error);
+        return false;
+    }
+    let mut db = match sqlite::Import::new(){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    let ok = STORED_FILES.iter().all(|name| match *name{
+        save::POINT_DIR | save::POINT_ARCHIVE_DIR => import_file::<data::Point>(&mut db, name),
+        save::PLAN_DIR | save::PLAN_ARCHIVE_DIR => import_file::<data::Plan>(&mut db, name),
+        save::SLICE_DIR | save::SLICE_ARCHIVE_DIR => import_file::<data::Slice>(&mut db, name),
+        save::TODO_DIR | save::TODO_ARCHIVE_DIR => import_file::<data::Todo>(&mut db, name),
+        save::SESSION_DIR => import_file::<data::Session>(&mut db, name),
+        save::HABIT_DIR => import_file::<data::Habit>(&mut db, name),
+        _ => false,
+    });
+    if !ok{
+        db.abort();
+        return false;
+    }
+    if !db.finish() {return false;}
+    for name in STORED_FILES.iter(){
+        if std::fs::remove_file(data_path(name)).is_err(){
+            conz::println_error("", "Warning: could not remove the old file: ", name);
+        }
+        for ext in [".log", ".idx"].iter(){
+            let _ = std::fs::remove_file(data_path(&format!("{}{}", name, ext)));
+        }
+    }
+    true
+}
+
+fn sort_records<T: Record>(buf: &save::Buffer) -> Option<save::Buffer>{
+    let mut records = save::decode_records::<T>(buf)?;
+    records.sort();
+    Option::Some(save::encode_records(&records))
+}
+
+//files that are not archives are stored sorted
+fn sorted(name: &str, buf: save::Buffer) -> Option<save::Buffer>{
+    match name{
+        save::POINT_DIR => sort_records::<data::Point>(&buf),
+        save::PLAN_DIR => sort_records::<data::Plan>(&buf),
+        save::SLICE_DIR => sort_records::<data::Slice>(&buf),
+        save::TODO_DIR => sort_records::<data::Todo>(&buf),
+        save::SESSION_DIR => sort_records::<data::Session>(&buf),
+        save::HABIT_DIR => sort_records::<data::Habit>(&buf),
+        _ => Option::Some(buf),
+    }
+}
+
+//the records are stored the same in both, so the files are the rows put after each other
+fn to_flat() -> bool{
+    for name in STORED_FILES.iter(){
+        let buf = match sqlite::export(name).and_then(|x| sorted(name, x)){
+            Option::Some(x) => x,
+            Option::None => return false,
+        };
+        if !save::replace_file(&data_path(name), &buf) {return false;}
+    }
+    sqlite::remove()
+}
+
+fn compact_file<T: save::Bufferable + Ord + Clone>(name: &str) -> bool{
+    let path = data_path(name);
+    if !path.exists() {return true;}
+    save::BufferFile::<T>::new(path).compact()
+}
+
+/*
+Writes the flat files whole with the changes in their logs, so the files alone have everything.
+For when the files are handled as files, by git or when they are converted.
+*/
+pub fn compact_all() -> bool{
+    if Backend::current() == Backend::Sqlite {return true;}
+    save::DATA_FILES.iter().all(|name| match *name{
+        save::POINT_DIR => compact_file::<data::Point>(name),
+        save::PLAN_DIR => compact_file::<data::Plan>(name),
+        save::SLICE_DIR => compact_file::<data::Slice>(name),
+        save::TODO_DIR => compact_file::<data::Todo>(name),
+        save::SESSION_DIR => compact_file::<data::Session>(name),
+        save::HABIT_DIR => compact_file::<data::Habit>(name),
+        save::REMINDER_DIR => compact_file::<data::Reminder>(name),
+        //archives are only appended to, they have no log
+        _ => true,
+    })
+}
+
+pub fn migrate(to: Backend) -> bool{
+    match to{
+        Backend::Sqlite => to_sqlite(),
+        Backend::Flat => to_flat(),
+    }
+}
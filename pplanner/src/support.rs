@@ -5,8 +5,12 @@ use super::astr;
 use super::astr::{AStr};
 use super::data;
 use super::save;
+use super::storage;
 use super::conz;
+use super::hooks;
+use super::wizard;
 use super::wizard::{Wizardable};
+use super::error::{CmdRes,CmdError};
 
 #[derive(PartialEq)]
 pub enum MatchResult{
@@ -16,7 +20,7 @@ pub enum MatchResult{
 
 pub fn get_matches<T: Wizardable>(data: &[T], inputs: &mut Option<VecDeque<astr::Astr>>) -> (MatchResult,Vec<usize>){
     let fields = T::get_fields(true);
-    let res = fields.execute(inputs);
+    let res = fields.execute(&[], inputs);
     if res.is_none(){
         return (MatchResult::None, Vec::new());
     }
@@ -46,22 +50,26 @@ pub fn get_matches<T: Wizardable>(data: &[T], inputs: &mut Option<VecDeque<astr:
     (MatchResult::None, vec)
 }
 
-pub fn remove_and_archive<T: save::Bufferable + std::cmp::Ord + Clone>
-    (bf: &mut save::BufferFile<T>, af: &mut save::ArchiveFile<T>,
-    vec: Vec<usize>, data: &[T]){
+pub fn remove_and_archive<T: Wizardable + conz::PrettyPrintable + save::Bufferable + std::cmp::Ord + Clone>
+    (bf: &mut dyn storage::Storage<T>, af: &mut dyn storage::Archive<T>,
+    vec: Vec<usize>, data: &[T]) -> CmdRes where T::ArgType: Default{
     let ok = bf.remove_indices(vec.clone());
     if ok {
         conz::println_type("Success: Items removed.", conz::MsgType::Highlight);
     }else{
-        conz::println_type("Error: Items removing failed.", conz::MsgType::Highlight);
-        return;
+        conz::println_type("Error: Items removing failed.", conz::MsgType::Error);
+        return Err(CmdError::Io);
     }
     for i in &vec{
         af.add_item(data[*i].clone());
     }
     if !af.write(){
         conz::println_type("Error: Could not write items to archive.", conz::MsgType::Error);
+        return Err(CmdError::Io);
     }
+    let removed: Vec<T> = vec.iter().map(|i| data[*i].clone()).collect();
+    hooks::fire(hooks::Event::Remove, &T::get_name().to_string(), &removed);
+    Ok(())
 }
 
 pub fn diff_color(diff: &data::Span) -> conz::MsgType{
@@ -74,7 +82,104 @@ pub fn diff_color(diff: &data::Span) -> conz::MsgType{
     }
 }
 
+fn json_escape(string: &str) -> String{
+    let mut res = String::new();
+    for ch in string.chars(){
+        match ch{
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\t' => res.push_str("\\t"),
+            '\r' => res.push_str("\\r"),
+            x if (x as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", x as u32)),
+            x => res.push(x),
+        }
+    }
+    res
+}
+
+fn tsv_escape(string: &str) -> String{
+    let mut res = String::new();
+    for ch in string.chars(){
+        match ch{
+            '\\' => res.push_str("\\\\"),
+            '\t' => res.push_str("\\t"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            x => res.push(x),
+        }
+    }
+    res
+}
+
+pub fn raw_to_json(keys: &[&str], row: &[conz::Raw]) -> String{
+    let mut res = String::from("{");
+    for (i, (key, val)) in keys.iter().zip(row.iter()).enumerate(){
+        if i > 0 {res.push(',');}
+        res.push_str(&format!("\"{}\":", json_escape(key)));
+        match val{
+            conz::Raw::Text(x) => res.push_str(&format!("\"{}\"", json_escape(&x.to_string()))),
+            conz::Raw::Num(x) => res.push_str(&format!("{}", x)),
+            conz::Raw::Bool(x) => res.push_str(&format!("{}", x)),
+        }
+    }
+    res.push('}');
+    res
+}
+
+fn raw_to_tsv(row: &[conz::Raw]) -> String{
+    let fields: Vec<String> = row.iter().map(|val| match val{
+        conz::Raw::Text(x) => tsv_escape(&x.to_string()),
+        conz::Raw::Num(x) => format!("{}", x),
+        conz::Raw::Bool(x) => format!("{}", x),
+    }).collect();
+    fields.join("\t")
+}
+
+//quoted when needed, like spreadsheets expect
+fn csv_escape(string: &str) -> String{
+    if !string.contains([',', '"', '\n', '\r']) {return string.to_string();}
+    format!("\"{}\"", string.replace('"', "\"\""))
+}
+
+fn raw_to_csv(row: &[conz::Raw]) -> String{
+    let fields: Vec<String> = row.iter().map(|val| match val{
+        conz::Raw::Text(x) => csv_escape(&x.to_string()),
+        conz::Raw::Num(x) => format!("{}", x),
+        conz::Raw::Bool(x) => format!("{}", x),
+    }).collect();
+    fields.join(",")
+}
+
+//json lines, or tsv or csv with a header, full values, one record per line
+pub fn machine_print(keys: &[&str], rows: &[Vec<conz::Raw>]){
+    match conz::output_mode(){
+        conz::OutputMode::Json => {
+            for row in rows{
+                conz::println_data(raw_to_json(keys, row));
+            }
+        }
+        conz::OutputMode::Csv => {
+            conz::println_data(keys.join(","));
+            for row in rows{
+                conz::println_data(raw_to_csv(row));
+            }
+        }
+        _ => {
+            conz::println_data(keys.join("\t"));
+            for row in rows{
+                conz::println_data(raw_to_tsv(row));
+            }
+        }
+    }
+}
+
 pub fn pretty_print<T: conz::PrettyPrintable>(datavec: &[T], arg: &T::ArgType){
+    if conz::is_machine(){
+        let rows: Vec<Vec<conz::Raw>> = datavec.iter().map(|x| x.raw(arg)).collect();
+        machine_print(&T::raw_keys(arg), &rows);
+        return;
+    }
     let count = datavec.len();
     let lengths = T::lengths(arg);
     let titles = T::titles(arg);
@@ -128,9 +233,9 @@ pub fn pretty_print<T: conz::PrettyPrintable>(datavec: &[T], arg: &T::ArgType){
     conz::println_type(divider_hor("=").disp(), conz::MsgType::Highlight);
 }
 
-pub fn rm_items<T: Wizardable + save::Bufferable + std::cmp::Ord + Clone>
-    (items: Vec<T>, bf: &mut save::BufferFile<T>, af: &mut save::ArchiveFile<T>,
-    inputs: &mut Option<VecDeque<astr::Astr>>){
+pub fn rm_items<T: Wizardable + conz::PrettyPrintable + save::Bufferable + std::cmp::Ord + Clone>
+    (items: Vec<T>, bf: &mut dyn storage::Storage<T>, af: &mut dyn storage::Archive<T>,
+    inputs: &mut Option<VecDeque<astr::Astr>>) -> CmdRes where T::ArgType: Default{
     conz::print_type("Remove ", conz::MsgType::Normal);
     conz::print_type(T::get_name().disp(), conz::MsgType::Normal);
     conz::println_type("(search first): ", conz::MsgType::Normal);
@@ -140,9 +245,10 @@ pub fn rm_items<T: Wizardable + save::Bufferable + std::cmp::Ord + Clone>
         match match_res{
             MatchResult::None =>{
                 conz::println_type("Fail: no matches found.", conz::MsgType::Error);
-                if cli {return;}
-                if conz::read_bool("Try again?: ", inputs) {continue;}
-                else {return;}
+                if cli || !conz::read_bool("Try again?: ", inputs) {
+                    return Err(CmdError::NoMatch);
+                }
+                continue;
             }
             MatchResult::Some =>{
                 conz::print_type("Found ", conz::MsgType::Normal);
@@ -154,17 +260,16 @@ pub fn rm_items<T: Wizardable + save::Bufferable + std::cmp::Ord + Clone>
                 if !cli{
                     if conz::read_bool("Delete all?: ", inputs) {}
                     else if conz::read_bool("Try again?: ", inputs) {continue;}
-                    else {return;}
+                    else {return Ok(());}
                 }
-                remove_and_archive(bf, af, vec, &items);
-                return;
+                return remove_and_archive(bf, af, vec, &items);
             }
         }
     }
 }
 
-pub fn edit_items<T: Wizardable + save::Bufferable + std::cmp::Ord + Clone>
-    (bf: &mut save::BufferFile<T>){
+pub fn edit_items<T: Wizardable + conz::PrettyPrintable + save::Bufferable + std::cmp::Ord + Clone>
+    (bf: &mut dyn storage::Storage<T>) -> CmdRes where T::ArgType: Default{
     conz::print_type("Edit ", conz::MsgType::Normal);
     conz::print_type(T::get_name().disp(), conz::MsgType::Normal);
     conz::println_type("(search first): ", conz::MsgType::Normal);
@@ -176,7 +281,7 @@ pub fn edit_items<T: Wizardable + save::Bufferable + std::cmp::Ord + Clone>
             MatchResult::None =>{
                 conz::println_type("Fail: no matches found.", conz::MsgType::Error);
                 if conz::read_bool("Try again?: ", &mut Option::None) {continue;}
-                else {return;}
+                return Err(CmdError::NoMatch);
             }
             MatchResult::Some =>{
                 conz::print_type("Found ", conz::MsgType::Normal);
@@ -191,8 +296,8 @@ pub fn edit_items<T: Wizardable + save::Bufferable + std::cmp::Ord + Clone>
                     for i in &vec{
                         let mut npoint = items[*i].clone();
                         npoint.print();
-                        let res = fields.execute(&mut Option::None);
-                        if res.is_none() {return;}
+                        let res = fields.execute(&[], &mut Option::None);
+                        if res.is_none() {return Err(CmdError::BadInput);}
                         let mut res = res.unwrap();
                         let partial = T::get_partial(&mut res);
                         npoint.replace_parts(&partial);
@@ -203,21 +308,23 @@ pub fn edit_items<T: Wizardable + save::Bufferable + std::cmp::Ord + Clone>
                         indices.push(*i);
                         replacements.push(npoint);
                     }
-                    let ok = bf.replace(indices, replacements);
+                    let ok = bf.replace(indices, replacements.clone());
                     if ok {
                         conz::println_type("Success: Items edited.", conz::MsgType::Highlight);
-                    }else{
-                        conz::println_type("Error: Items editing failed.", conz::MsgType::Highlight);
+                        hooks::fire(hooks::Event::Edit, &T::get_name().to_string(), &replacements);
+                        return Ok(());
                     }
-                    return;
+                    conz::println_type("Error: Items editing failed.", conz::MsgType::Error);
+                    return Err(CmdError::Io);
                 }
                 if conz::read_bool("Try again?: ", &mut Option::None) {continue;}
-                else {return;}
+                return Ok(());
             }
         }
     }
 }
 
+#[allow(clippy::never_loop)]
 pub fn split_todos(todos: &[data::Plan]) -> (Vec<data::Plan>,Vec<data::Plan>,Vec<data::Plan>,Vec<data::Plan>){
     let mut doi = Vec::new();
     let mut tod = Vec::new();
@@ -248,26 +355,74 @@ pub fn split_todos(todos: &[data::Plan]) -> (Vec<data::Plan>,Vec<data::Plan>,Vec
     (doi,tod,lon,ide)
 }
 
-pub fn mk_item<T: Wizardable + save::Bufferable + std::cmp::Ord + Clone>
-    (bfile: &mut save::BufferFile<T>, inputs: &mut Option<VecDeque<astr::Astr>>){
+pub fn mk_item<T: Wizardable + conz::PrettyPrintable + save::Bufferable + std::cmp::Ord + Clone>
+    (bfile: &mut dyn storage::Storage<T>, args: &[astr::Astr], inputs: &mut Option<VecDeque<astr::Astr>>) -> CmdRes
+    where T::ArgType: Default{
     conz::print_type("Add ", conz::MsgType::Normal);
     conz::print_type(T::get_name().disp(), conz::MsgType::Normal);
     conz::println_type(": ", conz::MsgType::Normal);
     let fields = T::get_fields(false);
-    let res = fields.execute(inputs);
-    if res.is_none() {return;}
+    let res = fields.execute(args, inputs);
+    if res.is_none() {return Err(CmdError::BadInput);}
     let mut res = res.unwrap();
     let item = T::extract(&mut res);
-    if item.is_none() {return;}
-    bfile.add_item(item.unwrap());
-    if !bfile.write() {return;}
+    if item.is_none() {return Err(CmdError::BadInput);}
+    let item = item.unwrap();
+    if !bfile.add_item(item.clone()) {return Err(CmdError::Io);}
+    if !bfile.write() {return Err(CmdError::Io);}
     conz::print_type("Success: ", conz::MsgType::Highlight);
     conz::print_type(T::get_name().disp(), conz::MsgType::Highlight);
     conz::println_type(" saved!", conz::MsgType::Highlight);
+    hooks::fire(hooks::Event::Add, &T::get_name().to_string(), &[item]);
+    Ok(())
+}
+
+//titles that are the title apart from case, else the ones that contain it
+pub fn match_title(titles: &[astr::Astr], title: &astr::Astr) -> Vec<usize>{
+    let title = title.to_lower();
+    let exact: Vec<usize> = (0..titles.len()).filter(|i| titles[*i].to_lower() == title).collect();
+    if !exact.is_empty() {return exact;}
+    let part = title.to_string();
+    (0..titles.len()).filter(|i| titles[*i].to_lower().to_string().contains(&part)).collect()
+}
+
+/*
+Which archived records to list, all of them without arguments, nothing is asked then.
+--last n gives the last n, and on dated records --from and --to give those between the dates, both included.
+*/
+pub fn archive_query(args: &[astr::Astr], inputs: &mut Option<VecDeque<astr::Astr>>, dated: bool) -> Option<storage::Query>{
+    if args.is_empty() && !has_inputs(inputs) {return Option::Some(storage::Query::default());}
+    let mut fields = wizard::FieldVec::new();
+    fields.add("last", wizard::InputType::U16, astr::from_str("Last(empty for all): "), wizard::PromptType::Partial);
+    if dated{
+        fields.add("from", wizard::InputType::Date, astr::from_str("From(date, empty for any): "), wizard::PromptType::Partial);
+        fields.add("to", wizard::InputType::Date, astr::from_str("To(date, empty for any): "), wizard::PromptType::Partial);
+    }
+    let mut wres = fields.execute(args, inputs)?;
+    let last = wres.get_u16().unwrap_or(0);
+    let mut query = storage::Query{
+        last: if last == 0 {Option::None} else {Option::Some(usize::from(last))},
+        ..storage::Query::default()
+    };
+    if dated{
+        let from = wres.get_dt().unwrap_or_default();
+        let to = wres.get_dt().unwrap_or_default();
+        if from != data::DT::default() {query.from = Option::Some(from.dt.timestamp());}
+        //the to date is included, so up to the midnight after it
+        if to != data::DT::default() {query.to = Option::Some(to.next_midnight().unwrap_or(to).dt.timestamp());}
+    }
+    Option::Some(query)
+}
+
+pub fn has_inputs(inputs: &Option<VecDeque<astr::Astr>>) -> bool{
+    match inputs{
+        Option::None => false,
+        Option::Some(x) => !x.is_empty(),
+    }
 }
 
 pub fn warn_unused_inputs(inputs: &Option<VecDeque<astr::Astr>>){
-    if inputs.is_none() {return;}
+    if !has_inputs(inputs) {return;}
     conz::println_type("Warning: Inputs for this command where specified but this command does not use any.", conz::MsgType::Error);
 }
 
@@ -279,10 +434,10 @@ pub fn warn_unused_arguments(args: &[astr::Astr]){
 #[macro_export]
 macro_rules! check_unsupported_inputs{
     ($inputs:expr) => {
-        if $inputs.is_some() {
+        if support::has_inputs(&$inputs) {
             conz::println_type("Error: this command does not support execution with givin inputs fro the cli.",
                 conz::MsgType::Error);
-            return;
+            return Err(error::CmdError::BadInput);
         }
     };
 }
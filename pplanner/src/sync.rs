@@ -0,0 +1,266 @@
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Mutex, OnceLock};
+
+use super::conz;
+use super::config;
+use super::save;
+use super::merge;
+use super::crypt;
+use super::storage;
+use super::astr::AStr;
+
+//files that belong to one machine, they are not committed
+const IGNORED: [&str; 6] = [save::REMINDER_DIR, save::HISTORY_FILE, "*.tmp", "*-journal", "*.log", "*.idx"];
+
+struct Settings{
+    dir: PathBuf,
+    remote: Option<String>,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/*
+With "git = on" in the config the data directory is a git repository,
+and the saved changes are committed when the files are flushed, after each command.
+"git remote = url" is where sync pulls from and pushes to.
+*/
+pub fn setup(config: &config::Config){
+    let on = match config.get("git"){
+        Option::None => false,
+        Option::Some(x) => ["on", "yes", "true"].contains(&x),
+    };
+    if !on {return;}
+    //the database is one file, git can not merge it per record
+    if storage::Backend::current() == storage::Backend::Sqlite{
+        conz::println_type("Error: git can not be on with the sqlite storage, migrate storage flat first.", conz::MsgType::Error);
+        return;
+    }
+    let dir = match save::get_data_dir_path(""){
+        Option::Some(x) => x,
+        Option::None => return,
+    };
+    let remote = config.get("git remote").filter(|x| !x.is_empty()).map(String::from);
+    if !dir.join(".git").exists() && !init(&dir) {return;}
+    if !ignore_files(&dir) {return;}
+    let _ = SETTINGS.set(Settings{dir, remote});
+}
+
+fn init(dir: &std::path::Path) -> bool{
+    if git(dir, &["init", "-q"]).is_none() {return false;}
+    //git should not merge our files as text, sync merges them per record
+    let ok = ignore_files(dir)
+        && std::fs::write(dir.join(".gitattributes"), "* -diff -merge -text\n.git* diff merge text\n").is_ok();
+    if !ok{
+        conz::println_type("Error: git: could not write .gitignore and .gitattributes.", conz::MsgType::Error);
+        return false;
+    }
+    conz::print_type("First time use: created git repository in: ", conz::MsgType::Highlight);
+    conz::println_type(dir.display(), conz::MsgType::Value);
+    commit_dir(dir, "init pplanner data")
+}
+
+//adds what is not ignored yet, repositories made by older versions miss newer files
+fn ignore_files(dir: &std::path::Path) -> bool{
+    let path = dir.join(".gitignore");
+    let mut ignore = std::fs::read_to_string(&path).unwrap_or_default();
+    let missing: Vec<&str> = IGNORED.iter().filter(|x| !ignore.lines().any(|l| l == **x)).copied().collect();
+    if missing.is_empty() {return true;}
+    for name in missing{
+        if !ignore.is_empty() && !ignore.ends_with('\n') {ignore.push('\n');}
+        ignore.push_str(name);
+        ignore.push('\n');
+    }
+    if std::fs::write(&path, ignore).is_err(){
+        conz::println_type("Error: git: could not write .gitignore.", conz::MsgType::Error);
+        return false;
+    }
+    true
+}
+
+fn settings() -> Option<&'static Settings>{
+    SETTINGS.get()
+}
+
+pub fn enabled() -> bool{
+    settings().is_some()
+}
+
+//runs git in dir, its output on success, the error is printed on failure
+fn git(dir: &std::path::Path, args: &[&str]) -> Option<String>{
+    let out = Command::new("git").arg("-C").arg(dir).args(args).output();
+    match out{
+        Ok(x) if x.status.success() => Option::Some(String::from_utf8_lossy(&x.stdout).into_owned()),
+        Ok(x) =>{
+            conz::print_type(format!("Error: git {} failed: ", args[0]), conz::MsgType::Error);
+            conz::println_type(String::from_utf8_lossy(&x.stderr).trim(), conz::MsgType::Highlight);
+            Option::None
+        }
+        Err(e) =>{
+            conz::print_type("Error: could not run git: ", conz::MsgType::Error);
+            conz::println_type(e, conz::MsgType::Highlight);
+            Option::None
+        }
+    }
+}
+
+//like git, but a failure is an answer and not an error
+fn git_quiet(dir: &std::path::Path, args: &[&str]) -> Option<Vec<u8>>{
+    let out = Command::new("git").arg("-C").arg(dir).args(args).output().ok()?;
+    if out.status.success() {Option::Some(out.stdout)} else {Option::None}
+}
+
+fn commit_dir(dir: &std::path::Path, msg: &str) -> bool{
+    //the logs are not committed, so the files have to hold all changes
+    if !storage::compact_all() {return false;}
+    if git(dir, &["add", "-A"]).is_none() {return false;}
+    //nothing staged, nothing to commit
+    if git_quiet(dir, &["diff", "--cached", "--quiet"]).is_some() {return true;}
+    git(dir, &["commit", "-q", "-m", msg]).is_some()
+}
+
+//descriptions of the changes that are saved but not committed yet
+static PENDING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+//remembers a saved change, it is committed with the next flush of the files
+pub fn note(msg: String){
+    if settings().is_none() {return;}
+    if let Ok(mut x) = PENDING.lock() {x.push(msg);}
+}
+
+pub fn pending() -> bool{
+    PENDING.lock().map(|x| !x.is_empty()).unwrap_or(false)
+}
+
+/*
+Commits all changes in the data directory, when git is on.
+The message lists the noted changes, or else the files that were flushed.
+*/
+pub fn commit(flushed: &[&str]){
+    let s = match settings(){
+        Option::Some(x) => x,
+        Option::None => return,
+    };
+    let notes = match PENDING.lock(){
+        Ok(mut x) => std::mem::take(&mut *x),
+        Err(_) => Vec::new(),
+    };
+    let msg = if !notes.is_empty() {notes.join("; ")}
+        else if !flushed.is_empty() {format!("flush {}", flushed.join(", "))}
+        else {return;};
+    if !commit_dir(&s.dir, &msg){
+        conz::println_type("Warning: git: the change is saved but not committed.", conz::MsgType::Error);
+    }
+}
+
+//"add point: Exam" from the event, the kind and the titles of the items
+pub fn describe(verb: &str, kind: &str, keys: &[&str], rows: &[Vec<conz::Raw>]) -> String{
+    let pos = keys.iter().position(|k| *k == "title");
+    let titles: Vec<String> = rows.iter().filter_map(|row| match pos.and_then(|p| row.get(p)){
+        Option::Some(conz::Raw::Text(x)) => Option::Some(x.to_string()),
+        _ => Option::None,
+    }).collect();
+    if titles.is_empty() {format!("{} {}", verb, kind)}
+    else {format!("{} {}: {}", verb, kind, titles.join(", "))}
+}
+
+fn branch(dir: &std::path::Path) -> Option<String>{
+    git(dir, &["symbolic-ref", "--short", "HEAD"]).map(|x| x.trim().to_string())
+}
+
+fn require() -> Option<(&'static Settings, &'static str)>{
+    let s = match settings(){
+        Option::Some(x) => x,
+        Option::None =>{
+            conz::println_type("Error: git is off, put \"git = on\" in the config.", conz::MsgType::Error);
+            return Option::None;
+        }
+    };
+    match &s.remote{
+        Option::Some(x) => Option::Some((s, x)),
+        Option::None =>{
+            conz::println_type("Error: no remote, put \"git remote = url\" in the config.", conz::MsgType::Error);
+            Option::None
+        }
+    }
+}
+
+/*
+Merges the remote branch into ours.
+Git does not merge the data files, they are binary, so the ones both sides changed
+are merged here per record, with the base and both sides taken from the index.
+Conflicting records are asked about, a conflict left open undoes the pull.
+*/
+pub fn pull() -> bool{
+    let (s, remote) = match require(){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    if !commit_dir(&s.dir, "update data") {return false;}
+    let branch = match branch(&s.dir){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    let heads = match git(&s.dir, &["ls-remote", "--heads", remote, &branch]){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    if heads.trim().is_empty(){
+        conz::println_type("Nothing to pull, the remote does not have this branch yet.", conz::MsgType::Normal);
+        return true;
+    }
+    if git(&s.dir, &["fetch", "-q", remote, &branch]).is_none() {return false;}
+    let merged = git_quiet(&s.dir, &["merge", "-q", "--no-edit", "--allow-unrelated-histories", "FETCH_HEAD"]);
+    if merged.is_some() {return true;}
+    let conflicted = match git(&s.dir, &["diff", "--name-only", "--diff-filter=U"]){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    for name in conflicted.lines().map(|x| x.trim()).filter(|x| !x.is_empty()){
+        if !resolve(&s.dir, name){
+            conz::print_type("Error: could not merge, the pull is undone: ", conz::MsgType::Error);
+            conz::println_type(name, conz::MsgType::Highlight);
+            let _ = git(&s.dir, &["merge", "--abort"]);
+            return false;
+        }
+    }
+    git(&s.dir, &["commit", "-q", "--no-edit"]).is_some()
+}
+
+pub fn push() -> bool{
+    let (s, remote) = match require(){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    if !commit_dir(&s.dir, "update data") {return false;}
+    let branch = match branch(&s.dir){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    git(&s.dir, &["push", "-q", remote, &format!("HEAD:{}", branch)]).is_some()
+}
+
+//a stage of a conflicted file, empty when that side does not have it
+fn stage(dir: &std::path::Path, n: u8, name: &str) -> save::Buffer{
+    git_quiet(dir, &["show", &format!(":{}:{}", n, name)]).unwrap_or_default()
+}
+
+//only the data files are merged, any other file changed on both sides, like the key file, is left to the user
+fn resolve(dir: &std::path::Path, name: &str) -> bool{
+    if !save::DATA_FILES.contains(&name){
+        conz::print_type("Error: git: only data files are merged, this file changed on both sides: ", conz::MsgType::Error);
+        conz::println_type(name, conz::MsgType::Highlight);
+        return false;
+    }
+    let (base, ours, theirs) = (stage(dir, 1, name), stage(dir, 2, name), stage(dir, 3, name));
+    let open = |x: &save::Buffer| crypt::open(name, x);
+    let (base, ours, theirs) = match (open(&base), open(&ours), open(&theirs)){
+        (Option::Some(b), Option::Some(o), Option::Some(t)) => (b, o, t),
+        _ => return false,
+    };
+    let merged = match merge::merge_named(name, &base, &ours, &theirs, Option::None).ok().and_then(|x| crypt::seal(name, &x)){
+        Option::Some(x) => x,
+        Option::None => return false,
+    };
+    std::fs::write(dir.join(name), merged).is_ok() && git(dir, &["add", name]).is_some()
+}
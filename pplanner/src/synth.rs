@@ -0,0 +1,138 @@
+use chrono::prelude::*;
+
+use super::astr;
+use super::conz;
+use super::data;
+use super::error;
+use super::save;
+
+/*
+Made up records to measure with, written the way pplanner writes its files.
+The same seed gives the same records, so runs can be compared with each other.
+*/
+
+//splitmix64, it only has to spread the records around
+pub struct Rng{
+    state: u64,
+}
+
+impl Rng{
+    pub fn new(seed: u64) -> Rng{
+        Rng{state: seed}
+    }
+
+    pub fn next_u64(&mut self) -> u64{
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn below(&mut self, n: u64) -> u64{
+        if n == 0 {0} else {self.next_u64() % n}
+    }
+}
+
+const WORDS: [&str; 16] = ["report", "exam", "groceries", "meeting", "dentist", "release", "review", "gym",
+    "call", "taxes", "read", "garden", "train", "backup", "invoice", "birthday"];
+//the first of january 2026, dates are spread two years around it
+const BASE: i64 = 1_767_225_600;
+const SPREAD: u64 = 2 * 365 * 24 * 3600;
+
+fn title(rng: &mut Rng, i: usize) -> astr::Astr{
+    let first = WORDS[rng.below(WORDS.len() as u64) as usize];
+    let second = WORDS[rng.below(WORDS.len() as u64) as usize];
+    astr::from_str(&format!("{} {} {}", first, second, i))
+}
+
+//on whole minutes, like the dates typed in
+fn date(rng: &mut Rng) -> data::DT{
+    let secs = BASE - SPREAD as i64 + rng.below(2 * SPREAD) as i64;
+    data::DT{dt: Local.timestamp(secs - secs % 60, 0)}
+}
+
+fn estimate(rng: &mut Rng) -> u32{
+    if rng.below(2) == 0 {0} else {rng.below(8 * 60) as u32}
+}
+
+pub fn points(n: usize, rng: &mut Rng) -> Vec<data::Point>{
+    (0..n).map(|i|{
+        let ptype = ["none", "deadline", "event"][rng.below(3) as usize];
+        data::Point::new(date(rng), title(rng, i), astr::from_str(ptype))
+    }).collect()
+}
+
+pub fn plans(n: usize, rng: &mut Rng) -> Vec<data::Plan>{
+    (0..n).map(|i|{
+        let ptype = ["short", "long", "idea", "current"][rng.below(4) as usize];
+        data::Plan::new(title(rng, i), rng.below(10) as u16, astr::from_str(ptype), estimate(rng))
+    }).collect()
+}
+
+//up to a day long
+pub fn slices(n: usize, rng: &mut Rng) -> Vec<data::Slice>{
+    (0..n).map(|i|{
+        let start = date(rng);
+        let end = data::DT{dt: start.dt + chrono::Duration::minutes(1 + rng.below(24 * 60) as i64)};
+        let stype = ["none", "deadline", "goto", "activity"][rng.below(4) as usize];
+        data::Slice::from(start, end, title(rng, i), data::SliceType::from_astr(&astr::from_str(stype), false))
+    }).collect()
+}
+
+pub fn todos(n: usize, rng: &mut Rng) -> Vec<data::Todo>{
+    (0..n).map(|i| data::Todo{
+        title: title(rng, i),
+        done: rng.below(4) == 0,
+        estimate: estimate(rng),
+    }).collect()
+}
+
+//stored sorted and plain, like pplanner leaves a file when it writes it whole
+fn write_sorted<T: save::Bufferable + Ord>(dir: &std::path::Path, name: &str, mut records: Vec<T>) -> bool{
+    records.sort();
+    save::replace_file(&dir.join(name), &save::encode_records(&records))
+}
+
+/*
+Writes n points, plans, slices and todos into dir, next to empty files for the rest,
+so dir can be used as a data directory. Files that are already there are not overwritten.
+*/
+pub fn write_dataset(dir: &std::path::Path, n: usize, seed: u64) -> bool{
+    if let Some(name) = save::DATA_FILES.iter().find(|x| dir.join(x).exists()){
+        conz::println_error("", "Error: Not overwriting existing data file: ", name);
+        return false;
+    }
+    if std::fs::create_dir_all(dir).is_err(){
+        conz::println_error("", "Error: Cannot create directory: ", &dir.to_string_lossy());
+        return false;
+    }
+    let mut rng = Rng::new(seed);
+    let ok = write_sorted(dir, save::POINT_DIR, points(n, &mut rng))
+        && write_sorted(dir, save::PLAN_DIR, plans(n, &mut rng))
+        && write_sorted(dir, save::SLICE_DIR, slices(n, &mut rng))
+        && write_sorted(dir, save::TODO_DIR, todos(n, &mut rng));
+    if !ok {return false;}
+    save::DATA_FILES.iter().filter(|x| !dir.join(x).exists())
+        .all(|x| save::replace_file(&dir.join(x), &Vec::new()))
+}
+
+//pplanner gen-data n dir [seed]
+pub fn run(args: &[String]) -> i32{
+    let n = args.first().and_then(|x| x.parse::<usize>().ok());
+    let seed = match args.get(2){
+        Option::Some(x) => x.parse::<u64>().ok(),
+        Option::None => Option::Some(0),
+    };
+    let (n, dir, seed) = match (n, args.get(1), seed){
+        (Option::Some(n), Option::Some(dir), Option::Some(seed)) if args.len() <= 3 => (n, dir, seed),
+        _ =>{
+            conz::println_type("Error: usage: pplanner gen-data n dir [seed]", conz::MsgType::Error);
+            return 1;
+        }
+    };
+    if !write_dataset(std::path::Path::new(dir), n, seed) {return error::CmdError::Io.exit_code();}
+    conz::print_type("Success: wrote records of each kind: ", conz::MsgType::Highlight);
+    conz::println_type(n, conz::MsgType::Value);
+    0
+}
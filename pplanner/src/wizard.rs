@@ -1,4 +1,5 @@
 use std::collections::VecDeque;
+use std::io::IsTerminal;
 
 use super::astr;
 use super::data;
@@ -13,6 +14,8 @@ pub enum InputType{
     DateTime,
     U16,
     Bool,
+    Duration,
+    Date,
 }
 
 pub enum PromptType{
@@ -22,6 +25,7 @@ pub enum PromptType{
 }
 
 struct Field{
+    name: &'static str,
     field_type: InputType,
     prompt_msg: astr::Astr,
     prompt_type: PromptType,
@@ -31,6 +35,12 @@ pub struct FieldVec{
     vec: Vec<Field>,
 }
 
+impl Default for FieldVec{
+    fn default() -> Self{
+        Self::new()
+    }
+}
+
 impl FieldVec{
     pub fn new() -> Self{
         FieldVec{
@@ -38,69 +48,165 @@ impl FieldVec{
         }
     }
 
-    pub fn add(&mut self, field_type: InputType, prompt_msg: astr::Astr, prompt_type: PromptType){
+    //name is the flag that fills the field from the command line: --name value
+    pub fn add(&mut self, name: &'static str, field_type: InputType, prompt_msg: astr::Astr, prompt_type: PromptType){
         self.vec.push(Field{
+            name,
             field_type,
             prompt_msg,
             prompt_type,
         });
     }
 
-    pub fn execute(&self, inputs: &mut Option<VecDeque<astr::Astr>>) -> Option<WizardRes>{
+    pub fn names(&self) -> Vec<String>{
+        self.vec.iter().map(|f| format!("--{}", f.name)).collect()
+    }
+
+    /*
+    Takes values from the front of words until all fields are filled or words are empty.
+    When named, "--name value" or "--name=value" fills that field, other words fill the first empty field.
+    */
+    fn assign(&self, values: &mut [Option<astr::Astr>], words: &mut VecDeque<astr::Astr>, named: bool) -> bool{
+        while values.iter().any(|v| v.is_none()){
+            let word = match words.pop_front(){
+                Option::None => break,
+                Option::Some(x) => x.to_string(),
+            };
+            if !named || !word.starts_with("--") || word.len() < 3{
+                let free = values.iter().position(|v| v.is_none()).unwrap();
+                values[free] = Option::Some(word.to_astr());
+                continue;
+            }
+            let (name, value) = match word.find('='){
+                Option::Some(i) => (word[2..i].to_string(), Option::Some(astr::from_str(&word[i + 1..]))),
+                Option::None => (word[2..].to_string(), Option::None),
+            };
+            let index = self.vec.iter().position(|f| f.name == name);
+            if index.is_none(){
+                conz::print_type("Fail: unknown argument: ", conz::MsgType::Error);
+                conz::print_type(&word, conz::MsgType::Highlight);
+                conz::print_type(", expected one of: ", conz::MsgType::Error);
+                conz::println_type(self.names().join(", "), conz::MsgType::Highlight);
+                return false;
+            }
+            let value = match value{
+                Option::Some(x) => x,
+                Option::None => match words.pop_front(){
+                    Option::Some(x) => x,
+                    Option::None => {
+                        conz::print_type("Fail: no value for argument: ", conz::MsgType::Error);
+                        conz::println_type(&word, conz::MsgType::Highlight);
+                        return false;
+                    }
+                },
+            };
+            values[index.unwrap()] = Option::Some(value);
+        }
+        true
+    }
+
+    //optional fields, and fields where an empty answer means something: no, no duration
+    fn has_default(instr: &Field) -> bool{
+        matches!(instr.prompt_type, PromptType::Partial)
+            || matches!(instr.field_type, InputType::Bool | InputType::Duration)
+    }
+
+    /*
+    Fields are filled from the arguments first, then from the inputs.
+    Without inputs the user is asked for the fields that are still missing,
+    optional(Partial) fields are only asked for when no arguments are given.
+    When stdin is not a terminal, fields with a default are not asked for.
+    */
+    pub fn execute(&self, args: &[astr::Astr], inputs: &mut Option<VecDeque<astr::Astr>>) -> Option<WizardRes>{
         let mut texts: VecDeque<astr::Astr> = VecDeque::new();
         let mut datetimes: VecDeque<data::DT> = VecDeque::new();
         let mut u16s: VecDeque<u16> = VecDeque::new();
         let mut bools = VecDeque::new();
+        let mut durations = VecDeque::new();
         let ask = inputs.is_none();
-        for instr in &self.vec{
+        let ask_partial = ask && args.is_empty();
+        let interactive = std::io::stdin().is_terminal();
+        let mut values = vec![Option::None; self.vec.len()];
+        let mut args: VecDeque<astr::Astr> = args.iter().cloned().collect();
+        if !self.assign(&mut values, &mut args, true) {return Option::None;}
+        if !args.is_empty(){
+            conz::print_type("Warning: unused arguments: ", conz::MsgType::Error);
+            conz::println_type(astr::unsplit(&Vec::from(args), b' ').to_string(), conz::MsgType::Highlight);
+        }
+        if let Option::Some(words) = inputs.as_mut(){
+            //inputs are values only, one can start with -- too
+            if !self.assign(&mut values, words, false) {return Option::None;}
+        }
+        for (instr, value) in self.vec.iter().zip(values){
+            let given = value.is_some();
+            let mut value = value;
             loop {
-                let line = if ask{
-                    conz::prompt(&instr.prompt_msg.to_string()).to_astr()
+                let line = if let Option::Some(x) = value.take(){
+                    x
+                }else if ask && !interactive && Self::has_default(instr){
+                    //nobody to ask, the empty answer gives the default
+                    astr::from_str("")
+                }else if ask && (ask_partial || !matches!(instr.prompt_type, PromptType::Partial)){
+                    match conz::prompt(&instr.prompt_msg.to_string()){
+                        Option::Some(x) => x.to_astr(),
+                        Option::None =>{
+                            conz::println_type("Fail: no more input.", conz::MsgType::Error);
+                            return Option::None;
+                        }
+                    }
                 }else{
-                    let mut res = inputs.as_mut().unwrap().pop_front();
-                    if res.is_none(){
-                        match instr.prompt_type{
-                            PromptType::Partial =>{
-                                res = Option::Some(astr::from_str(""));
-                            }
-                            _ =>{
-                                conz::println_type("Error: Not enough inputs provided for command!",
-                                    conz::MsgType::Error);
-                                return Option::None;
-                            }
+                    match instr.prompt_type{
+                        PromptType::Partial => astr::from_str(""),
+                        _ =>{
+                            conz::print_type("Error: Not enough inputs provided for command, missing: ",
+                                conz::MsgType::Error);
+                            conz::println_type(format!("--{}", instr.name), conz::MsgType::Highlight);
+                            return Option::None;
                         }
                     }
-                    res.unwrap()
                 };
+                let empty = line.is_empty();
                 let is_ok = match instr.field_type{
                     InputType::Text => Self::handle_text(&mut texts, line),
                     InputType::DateTime => Self::handle_datetime(&mut datetimes, line),
                     InputType::U16 => Self::handle_u16(&mut u16s, line),
                     InputType::Bool => Self::handle_bool(&mut bools, line),
+                    InputType::Duration => Self::handle_duration(&mut durations, line),
+                    InputType::Date => Self::handle_date(&mut datetimes, line),
                 };
                 if is_ok {break;}
+                if given && (!matches!(instr.prompt_type, PromptType::Partial) || !empty){
+                    conz::print_type("Fail: could not parse: ", conz::MsgType::Error);
+                    conz::println_type(format!("--{}", instr.name), conz::MsgType::Highlight);
+                    return Option::None;
+                }
                 match instr.prompt_type{
                     PromptType::Once =>{
                         conz::println_type("Fail: could not parse.", conz::MsgType::Error);
                         return Option::None;
                     }
                     PromptType::Reprompt =>{
+                        if !ask{
+                            conz::println_type("Fail: could not parse.", conz::MsgType::Error);
+                            return Option::None;
+                        }
                         let redo = conz::prompt("Could not parse, try again? */n: ");
-                        if redo == "n" {return Option::None;}
+                        if redo.as_deref().unwrap_or("n") == "n" {return Option::None;}
                     }
                     PromptType::Partial =>{
                         match instr.field_type{
                             InputType::Text => texts.push_back(astr::Astr::default()),
-                            InputType::DateTime => datetimes.push_back(data::DT::default()),
+                            InputType::DateTime | InputType::Date => datetimes.push_back(data::DT::default()),
                             InputType::U16 => u16s.push_back(u16::default()),
                             InputType::Bool => bools.push_back(bool::default()),
+                            InputType::Duration => durations.push_back(u32::default()),
                         }
                         break;
                     }
                 }
             }
         }
-        let res = WizardRes::new(texts, datetimes, u16s, bools);
+        let res = WizardRes::new(texts, datetimes, u16s, bools, durations);
         Option::Some(res)
     }
 
@@ -128,6 +234,16 @@ impl FieldVec{
         true
     }
 
+    //a date only, at the start of the day
+    fn handle_date(datetimes: &mut VecDeque<data::DT>, line: astr::Astr) -> bool{
+        let dmy = data::parse_dmy(&line);
+        if dmy.is_none() {return false;}
+        let dt = data::DT::make_datetime(dmy.unwrap(), (0, 0, 0));
+        if dt.is_none() {return false;}
+        datetimes.push_back(dt.unwrap());
+        true
+    }
+
     fn handle_u16(u16s: &mut VecDeque<u16>, line: astr::Astr) -> bool{
         let val: Option<u16> = term_basics_linux::string_to_value(&line.to_string());
         if val.is_none() {return false;}
@@ -140,6 +256,18 @@ impl FieldVec{
         bools.push_back(val);
         true
     }
+
+    //empty means no duration
+    fn handle_duration(durations: &mut VecDeque<u32>, line: astr::Astr) -> bool{
+        if line.is_empty(){
+            durations.push_back(0);
+            return true;
+        }
+        let val = data::parse_duration(&line);
+        if val.is_none() {return false;}
+        durations.push_back(val.unwrap());
+        true
+    }
 }
 
 pub struct WizardRes{
@@ -147,15 +275,18 @@ pub struct WizardRes{
     all_datetime: VecDeque<data::DT>,
     all_u16s: VecDeque<u16>,
     all_bool: VecDeque<bool>,
+    all_durations: VecDeque<u32>,
 }
 
 impl WizardRes{
-    pub fn new(text: VecDeque<astr::Astr>, dt: VecDeque<data::DT>, u16s: VecDeque<u16>, bools: VecDeque<bool>) -> Self{
+    pub fn new(text: VecDeque<astr::Astr>, dt: VecDeque<data::DT>, u16s: VecDeque<u16>, bools: VecDeque<bool>,
+        durations: VecDeque<u32>) -> Self{
         WizardRes{
             all_text: text,
             all_datetime: dt,
             all_u16s: u16s,
             all_bool: bools,
+            all_durations: durations,
         }
     }
 
@@ -174,6 +305,10 @@ impl WizardRes{
     pub fn get_bool(&mut self) -> Option<bool>{
         self.all_bool.pop_front()
     }
+
+    pub fn get_duration(&mut self) -> Option<u32>{
+        self.all_durations.pop_front()
+    }
 }
 
 pub trait Wizardable where Self: std::marker::Sized + conz::Printable{
@@ -0,0 +1,198 @@
+use num_traits::FromPrimitive;
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+use pplanner::astr::{self, Astr};
+use pplanner::data::{self, DT};
+use pplanner::save::{self, Bufferable};
+
+/*
+Everything written to a file has to read back the same, and nothing read from a file may panic.
+Some of the types compare on their sort key only, so a record that is read back
+is also written again and compared on its bytes.
+*/
+
+fn bytes<T: Bufferable>(x: &T) -> save::Buffer{
+    let mut buf = Vec::new();
+    x.into_buffer(&mut buf);
+    buf
+}
+
+fn roundtrip<T: Bufferable>(x: &T) -> T{
+    let buf = bytes(x);
+    let mut iter = 0;
+    let res = T::from_buffer(&buf, &mut iter).expect("could not read back what was written");
+    assert_eq!(iter as usize, buf.len(), "not all bytes were read back");
+    assert_eq!(bytes(&res), buf, "writes other bytes after reading back");
+    res
+}
+
+//every record is cut short at each byte, none of those may read
+fn truncated<T: Bufferable>(x: &T) -> bool{
+    let buf = bytes(x);
+    (0..buf.len()).all(|len| T::from_buffer(&buf[..len].to_vec(), &mut 0).is_none())
+}
+
+type Parts = (u32, u32, u32, u32, u32, u32);
+
+//the days that do not exist in a month are left to make_datetime
+fn parts() -> impl Strategy<Value = Parts>{
+    (1u32..=9999, 1u32..=12, 1u32..=31, 0u32..24, 0u32..60, 0u32..60)
+}
+
+fn dt(p: Parts) -> Option<DT>{
+    DT::make_datetime((p.2, p.1, p.0), (p.3, p.4, p.5))
+}
+
+//the wall clock time is what is stored, see dt_that_happens_twice
+fn same(a: &DT, b: &DT) -> bool{
+    a.dt.naive_local() == b.dt.naive_local()
+}
+
+proptest!{
+    #[test]
+    fn astr_roundtrip(s in vec(any::<u8>(), 0..256)){
+        let x = Astr(s);
+        prop_assert!(roundtrip(&x) == x);
+        prop_assert!(truncated(&x));
+    }
+
+    #[test]
+    fn dt_roundtrip(p in parts()){
+        let x = dt(p);
+        prop_assume!(x.is_some());
+        let x = x.unwrap();
+        prop_assert!(same(&roundtrip(&x), &x));
+        prop_assert!(truncated(&x));
+    }
+
+    #[test]
+    fn point_roundtrip(p in parts(), title in vec(any::<u8>(), 0..64), ptype in prop::sample::select(vec![0u8, 1, 2, 255])){
+        let dt = dt(p);
+        prop_assume!(dt.is_some());
+        let x = data::Point{
+            dt: dt.unwrap(),
+            title: Astr(title),
+            ptype: data::PointType::from_u8(ptype).unwrap(),
+        };
+        let y = roundtrip(&x);
+        prop_assert!(same(&y.dt, &x.dt) && y.title == x.title && y.ptype == x.ptype);
+        prop_assert!(truncated(&x));
+    }
+
+    #[test]
+    fn plan_roundtrip(title in vec(any::<u8>(), 0..64), urgency in any::<u16>(), ttype in 0u8..5, estimate in any::<u32>()){
+        let mut x = data::Plan::new(Astr(title), urgency, astr::from_str("short"), estimate);
+        x.ttype = data::PlanType::from_u8(ttype).unwrap();
+        prop_assert!(roundtrip(&x) == x);
+        prop_assert!(truncated(&x));
+    }
+
+    #[test]
+    fn slice_roundtrip(start in parts(), end in parts(), title in vec(any::<u8>(), 0..64), stype in prop::sample::select(vec![0u8, 1, 2, 3, 255])){
+        let (start, end) = (dt(start), dt(end));
+        prop_assume!(start.is_some() && end.is_some());
+        let x = data::Slice::from(start.unwrap(), end.unwrap(), Astr(title), data::SliceType::from_u8(stype).unwrap());
+        let y = roundtrip(&x);
+        prop_assert!(same(&y.start, &x.start) && same(&y.end, &x.end) && y.title == x.title && y.stype == x.stype);
+        prop_assert!(truncated(&x));
+    }
+
+    #[test]
+    fn todo_roundtrip(title in vec(any::<u8>(), 0..64), done in any::<bool>(), estimate in any::<u32>()){
+        let x = data::Todo{title: Astr(title), done, estimate};
+        let y = roundtrip(&x);
+        prop_assert!(y == x && y.title == x.title && y.estimate == x.estimate);
+        prop_assert!(truncated(&x));
+    }
+
+    #[test]
+    fn records_roundtrip(todos in vec((vec(any::<u8>(), 0..16), any::<bool>(), 0u32..600), 0..32)){
+        let todos: Vec<data::Todo> = todos.into_iter()
+            .map(|(title, done, estimate)| data::Todo{title: Astr(title), done, estimate})
+            .collect();
+        let buf = save::encode_records(&todos);
+        let res = save::decode_records::<data::Todo>(&buf);
+        prop_assert!(res.is_some());
+        prop_assert_eq!(save::encode_records(&res.unwrap()), buf);
+    }
+
+    //corrupt files read as nothing, they do not take pplanner down
+    #[test]
+    fn garbage_does_not_panic(buf in vec(any::<u8>(), 0..512)){
+        let _ = save::decode_records::<Astr>(&buf);
+        let _ = save::decode_records::<DT>(&buf);
+        let _ = save::decode_records::<data::Point>(&buf);
+        let _ = save::decode_records::<data::Plan>(&buf);
+        let _ = save::decode_records::<data::Slice>(&buf);
+        let _ = save::decode_records::<data::Todo>(&buf);
+        let _ = save::decode_records::<data::Session>(&buf);
+        let _ = save::decode_records::<data::Habit>(&buf);
+        let _ = save::decode_records::<data::Reminder>(&buf);
+    }
+}
+
+#[test]
+fn astr_length_past_the_end(){
+    assert!(Astr::from_buffer(&vec![0, 0, 0, 2, b'a'], &mut 0).is_none());
+    assert!(Astr::from_buffer(&vec![0xff, 0xff, 0xff, 0xff, b'a'], &mut 0).is_none());
+    assert!(Astr::from_buffer(&vec![0x80, 0, 0, 0], &mut 0).is_none());
+}
+
+#[test]
+fn astr_empty(){
+    let mut iter = 0;
+    let x = Astr::from_buffer(&vec![0, 0, 0, 0], &mut iter);
+    assert!(x == Option::Some(Astr(Vec::new())));
+    assert_eq!(iter, 4);
+}
+
+#[test]
+fn dt_that_does_not_exist(){
+    //the 31st of february, hour 24, minute 60
+    for p in &[(2021u32, 2u8, 31u8, 0u8, 0u8, 0u8), (2021, 1, 1, 24, 0, 0), (2021, 1, 1, 0, 60, 0)]{
+        let mut buf = vec![p.3, p.4, p.5, p.2, p.1];
+        p.0.into_buffer(&mut buf);
+        assert!(DT::from_buffer(&buf, &mut 0).is_none());
+    }
+}
+
+/*
+When the clocks go back in europe and in the us, run with TZ set to see it matter.
+Only the wall clock time is stored, which of the two it is may differ after reading back.
+*/
+#[test]
+fn dt_that_happens_twice(){
+    for x in &[DT::make_datetime((27, 10, 2024), (2, 30, 0)), DT::make_datetime((3, 11, 2024), (1, 30, 0))]{
+        assert!(x.is_some());
+        roundtrip(x.as_ref().unwrap());
+    }
+}
+
+#[test]
+fn todo_with_a_bad_title(){
+    assert!(data::Todo::from_buffer(&vec![0, 0, 0, 9, 1], &mut 0).is_none());
+    assert!(data::Todo::from_buffer(&vec![0xff, 0xff, 0xff, 0xff, 1], &mut 0).is_none());
+}
+
+#[test]
+fn todo_missing_its_estimate(){
+    assert!(data::Todo::from_buffer(&vec![0, 0, 0, 0, 0x81, 0, 0], &mut 0).is_none());
+}
+
+#[test]
+fn slice_short_title(){
+    let start = DT::make_datetime((1, 1, 2021), (12, 0, 0)).unwrap();
+    let x = data::Slice::from(start.clone(), start, astr::from_str("lecture"), data::SliceType::Activity);
+    let mut buf = bytes(&x);
+    assert!(data::Slice::from_buffer(&buf, &mut 0).is_some());
+    buf.truncate(buf.len() - 3);
+    assert!(data::Slice::from_buffer(&buf, &mut 0).is_none());
+}
+
+#[test]
+fn unknown_type_bytes(){
+    let mut buf = bytes(&data::Point::new(DT::make_datetime((1, 1, 2021), (0, 0, 0)).unwrap(), astr::from_str("x"), astr::from_str("event")));
+    *buf.last_mut().unwrap() = 7;
+    assert!(data::Point::from_buffer(&buf, &mut 0).is_none());
+}
@@ -0,0 +1,108 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use pplanner::astr;
+use pplanner::data;
+use pplanner::save;
+
+/*
+The binary is run in a home of its own, the passphrase is read from stdin or PPLANNER_PASSPHRASE.
+The data files are changed under it to see what it reads.
+*/
+
+struct Home(PathBuf);
+
+impl Home{
+    fn new(name: &str) -> Home{
+        let home = std::env::temp_dir().join(format!("pplanner-crypt-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&home);
+        std::fs::create_dir_all(&home).unwrap();
+        Home(home)
+    }
+
+    fn file(&self, name: &str) -> PathBuf{
+        self.0.join(".config").join("pplanner").join(name)
+    }
+
+    //stdout and stderr together
+    fn run(&self, command: &str, stdin: &str) -> String{
+        let mut child = Command::new(env!("CARGO_BIN_EXE_pplanner"))
+            .env("HOME", &self.0)
+            .env("PPLANNER_PASSPHRASE", "secret")
+            .arg("--porcelain")
+            .arg("-e")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+        let out = child.wait_with_output().unwrap();
+        format!("{}{}", String::from_utf8_lossy(&out.stdout), String::from_utf8_lossy(&out.stderr))
+    }
+
+    //one todo and the data encrypted
+    fn encrypted(name: &str) -> Home{
+        let home = Home::new(name);
+        home.run("mk todo milk --done n", "");
+        let out = home.run("encrypt data", "secret\nsecret\n");
+        assert!(out.contains("Success"), "{}", out);
+        home
+    }
+}
+
+impl Drop for Home{
+    fn drop(&mut self){
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn read(path: &Path) -> Vec<u8>{
+    std::fs::read(path).unwrap()
+}
+
+//the todos file as it is written while the data is not encrypted
+fn plain_todos() -> Vec<u8>{
+    save::encode_records(&[data::Todo{title: astr::from_str("milk"), done: false, estimate: 0}])
+}
+
+#[test]
+fn encrypted_data_reads_back(){
+    let home = Home::encrypted("back");
+    let sealed = read(&home.file("todos"));
+    assert!(sealed.starts_with(b"PPLCRYPT"));
+    assert!(!sealed.windows(4).any(|w| w == b"milk"));
+    assert!(home.run("ls todos", "").contains("false\tmilk\t0"));
+}
+
+#[test]
+fn a_changed_byte_is_not_read(){
+    let home = Home::encrypted("tampered");
+    let mut sealed = read(&home.file("todos"));
+    let last = sealed.len() - 1;
+    sealed[last] ^= 1;
+    std::fs::write(home.file("todos"), sealed).unwrap();
+    let out = home.run("ls todos", "");
+    assert!(out.contains("could not decrypt"), "{}", out);
+    assert!(!out.contains("milk"), "{}", out);
+}
+
+#[test]
+fn a_file_under_another_name_is_not_read(){
+    let home = Home::encrypted("renamed");
+    std::fs::copy(home.file("todos"), home.file("todos_archive")).unwrap();
+    let out = home.run("ls todos archive", "");
+    assert!(out.contains("could not decrypt"), "{}", out);
+    assert!(!out.contains("milk"), "{}", out);
+}
+
+#[test]
+fn a_plain_file_is_not_read_while_encrypted(){
+    let home = Home::encrypted("plain");
+    std::fs::write(home.file("todos"), plain_todos()).unwrap();
+    let out = home.run("ls todos", "");
+    assert!(out.contains("this file is not"), "{}", out);
+    assert!(!out.contains("milk"), "{}", out);
+}
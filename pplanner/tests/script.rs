@@ -0,0 +1,55 @@
+use std::process::{Command, Stdio};
+
+/*
+Scripts are run by the binary, in a home of its own so the data starts empty.
+Output is tab separated so it is easy to look for the records.
+*/
+
+fn run_script(name: &str, script: &str) -> (i32, String){
+    let home = std::env::temp_dir().join(format!("pplanner-script-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&home);
+    std::fs::create_dir_all(&home).unwrap();
+    let path = home.join("script.pp");
+    std::fs::write(&path, script).unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_pplanner"))
+        .env("HOME", &home)
+        .arg("--porcelain")
+        .arg("-f")
+        .arg(&path)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_dir_all(&home);
+    (out.status.code().unwrap_or(-1), String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+#[test]
+fn named_arguments_like_on_the_prompt(){
+    let (code, out) = run_script("named", "set -e\n\
+        mk point Exam --type deadline --at \"9:00 1/2/2030\"\n\
+        ls points\n");
+    assert_eq!(code, 0, "{}", out);
+    assert!(out.contains("Exam\t2030-02-01T09:00:00+00:00\tDeadline"), "{}", out);
+}
+
+#[test]
+fn named_arguments_and_inputs_mixed(){
+    let (code, out) = run_script("mixed", "set -e\n\
+        mk point --at=9:00\\ 1/2/2030 Exam deadline\n\
+        mk todo milk --done n\n\
+        ls points\n\
+        ls todos\n");
+    assert_eq!(code, 0, "{}", out);
+    assert!(out.contains("Exam\t2030-02-01T09:00:00+00:00\tDeadline"), "{}", out);
+    assert!(out.contains("milk"), "{}", out);
+}
+
+#[test]
+fn a_failing_line_stops_the_script(){
+    let (code, out) = run_script("fails", "set -e\n\
+        mk point Exam --type deadline --at never\n\
+        mk todo milk --done n\n\
+        ls todos\n");
+    assert_ne!(code, 0);
+    assert!(!out.contains("milk"), "{}", out);
+}
@@ -0,0 +1,83 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/*
+The binary is run on made up records in a home of its own,
+moved to the database and back, and everything is listed on the way.
+*/
+
+const LISTS: [&str; 9] = ["ls points", "ls points archive", "ls plans", "ls plans archive", "ls slices",
+    "ls slices archive", "ls todos", "ls todos archive", "ls sessions"];
+const FILES: [&str; 10] = ["points", "points_archive", "plans", "plans_archive", "slices", "slices_archive",
+    "todos", "todos_archive", "sessions", "habits"];
+
+struct Home(PathBuf);
+
+impl Home{
+    fn data(&self) -> PathBuf{
+        self.0.join(".config").join("pplanner")
+    }
+
+    fn run(&self, command: &str, stdin: &str) -> String{
+        let mut child = Command::new(env!("CARGO_BIN_EXE_pplanner"))
+            .env("HOME", &self.0)
+            .arg("--json")
+            .arg("-e")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+        let out = child.wait_with_output().unwrap();
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    }
+
+    //every record as json, one list after the other
+    fn lists(&self) -> Vec<String>{
+        LISTS.iter().map(|x| self.run(x, "")).collect()
+    }
+
+    fn files(&self) -> Vec<Vec<u8>>{
+        FILES.iter().map(|x| std::fs::read(self.data().join(x)).unwrap()).collect()
+    }
+
+    fn migrate(&self, to: &str){
+        self.run(&format!("migrate storage {}", to), "");
+        assert_eq!(self.data().join("pplanner.db").exists(), to == "sqlite");
+        assert_eq!(self.data().join("todos").exists(), to == "flat");
+    }
+}
+
+impl Drop for Home{
+    fn drop(&mut self){
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn migrate_to_sqlite_and_back(){
+    let home = Home(std::env::temp_dir().join(format!("pplanner-storage-{}", std::process::id())));
+    let _ = std::fs::remove_dir_all(&home.0);
+    let gen = Command::new(env!("CARGO_BIN_EXE_pplanner"))
+        .arg("gen-data").arg("30").arg(home.data()).arg("1")
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(gen.success());
+    //the archives are filled in the order things are archived, not sorted
+    home.run("clean todos", "y\n");
+    home.run("clean points", "y\n");
+    let before = home.lists();
+    assert!(before[1].lines().count() > 1 && before[7].lines().count() > 1, "{:?}", before);
+    home.migrate("sqlite");
+    assert_eq!(home.lists(), before);
+    home.migrate("flat");
+    assert_eq!(home.lists(), before);
+    let files = home.files();
+    home.migrate("sqlite");
+    home.migrate("flat");
+    assert_eq!(home.files(), files);
+}